@@ -0,0 +1,101 @@
+"""Type stubs for the BIP-353 Python bindings."""
+
+from typing import Any, Awaitable, Dict, List, Optional, Tuple, Union
+
+class Bip353Error(Exception):
+    """Base class for all BIP-353 errors."""
+
+class DnsError(Bip353Error):
+    """DNS lookup failed."""
+
+class NotFoundError(DnsError):
+    """DNSSEC proves the name has no payment instruction."""
+
+class UnverifiedNotFoundError(DnsError):
+    """The name appears to have no records, but that is not proven."""
+
+class TransportError(DnsError):
+    """The DNS server could not be reached or gave no usable answer."""
+
+class DnssecError(Bip353Error):
+    """DNSSEC validation failed."""
+
+class InvalidAddressError(Bip353Error, ValueError):
+    """Malformed human-readable address."""
+
+class InvalidUriError(Bip353Error, ValueError):
+    """Malformed Bitcoin URI."""
+
+class NoRecordError(Bip353Error):
+    """No Bitcoin URI published for the name."""
+
+class MultipleRecordsError(Bip353Error):
+    """More than one Bitcoin URI published for the name."""
+
+class PaymentType:
+    """Payment instruction type. ``str()`` gives e.g. ``"on-chain"``."""
+
+    OnChain: PaymentType
+    Lightning: PaymentType
+    LightningOffer: PaymentType
+    Unknown: PaymentType
+
+    def __int__(self) -> int: ...
+
+class PyPaymentInstruction:
+    """A BIP-353 payment instruction."""
+
+    @property
+    def uri(self) -> str: ...
+    @property
+    def payment_type(self) -> PaymentType: ...
+    @property
+    def is_reusable(self) -> bool: ...
+    @property
+    def parameters(self) -> Dict[str, str]: ...
+    @property
+    def dnssec_status(self) -> str:
+        """``"secure"``, ``"insecure"``, ``"bogus"`` or ``"indeterminate"``."""
+    def to_dict(self) -> Dict[str, Any]: ...
+    def __eq__(self, other: object) -> bool:
+        """Compare the URI, payment type and parameters only."""
+    def __hash__(self) -> int: ...
+    def to_json(self) -> str: ...
+
+class PyResolver:
+    """Blocking resolver. The GIL is released while waiting on DNS."""
+
+    def __init__(self, nameservers: Optional[List[str]] = None) -> None:
+        """Create a resolver.
+
+        ``nameservers`` are ``"ip:port"`` strings that replace the system
+        configuration. Answers are always validated against the root anchors.
+        """
+    def resolve_address(self, address: str) -> PyPaymentInstruction: ...
+    def parse_address(self, address: str) -> Tuple[str, str]: ...
+
+class PyAsyncResolver:
+    """asyncio resolver. Methods return awaitables bound to the running loop."""
+
+    def __init__(self, nameservers: Optional[List[str]] = None) -> None: ...
+    def resolve_address(self, address: str) -> Awaitable[PyPaymentInstruction]: ...
+    def parse_address(self, address: str) -> Tuple[str, str]: ...
+
+class TestServer:
+    """In-process signed DNS server. Only in builds with ``test-support``."""
+
+    def __init__(self, records: Dict[str, List[Union[str, List[str]]]], zone: str = "example.com.") -> None:
+        """Sign ``records`` (owner name -> TXT records) into ``zone`` and serve it.
+
+        A TXT record is one string or a list of character-strings.
+        """
+    @property
+    def address(self) -> str: ...
+    def resolver(self) -> PyResolver: ...
+    def async_resolver(self) -> PyAsyncResolver: ...
+
+def parse_address(address: str) -> Tuple[str, str]:
+    """Parse ``₿user@domain`` into ``(user, domain)`` without any DNS lookup."""
+
+def parse_uri(uri: str) -> PyPaymentInstruction:
+    """Parse a Bitcoin URI without any DNS lookup."""
@@ -0,0 +1,258 @@
+//! Address book pinning payment instructions on first use (TOFU)
+//!
+//! The first time a name resolves, its instruction and the KSK of the zone
+//! that signed it (the key its DS or a trust anchor matches) are pinned.
+//! Later resolutions are compared with the pin, so a wallet can warn before
+//! paying a name whose payment methods changed or whose zone is anchored by
+//! a different key. Routine ZSK rollovers do not change the pin. A changed result never replaces
+//! the pin by itself; the caller pins it once the user accepts it.
+//!
+//! With the `storage` feature, pins can be kept in a `Storage` so they
+//! survive restarts; every change is written through before it is applied.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+#[cfg(feature = "storage")]
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use trust_dns_proto::rr::dnssec::Algorithm;
+use trust_dns_proto::rr::Name;
+
+#[cfg(feature = "storage")]
+use crate::storage::{self, Storage, PINS_NAMESPACE};
+use crate::{Bip353Error, DnssecInfo, DnssecStatus, HumanReadableName, PaymentInstruction};
+
+/// URI parameters that describe a payment rather than where it goes
+const METADATA_PARAMETERS: [&str; 3] = ["amount", "label", "message"];
+
+/// The key that anchors a signing zone: the KSK a DS or trust anchor matches
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct KeyId {
+    /// Zone the key belongs to
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialization::name"))]
+    pub zone: Name,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialization::algorithm"))]
+    pub algorithm: Algorithm,
+    pub key_tag: u16,
+}
+
+impl KeyId {
+    /// The key anchoring the zone that signed `dnssec`, if the result was
+    /// validated locally
+    pub fn of(dnssec: &DnssecInfo) -> Option<Self> {
+        Some(Self {
+            zone: dnssec.signer.clone()?,
+            algorithm: dnssec.anchor_algorithm?,
+            key_tag: dnssec.anchor_key_tag?,
+        })
+    }
+}
+
+/// What the address book trusts for one name
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Pin {
+    pub uri: String,
+    /// DNSSEC status of the pinned result
+    pub status: DnssecStatus,
+    /// KSK of the zone that signed the pinned record; `None` if it was not validated
+    /// locally, in which case key rotation cannot be detected
+    pub key: Option<KeyId>,
+    /// When this instruction was pinned, in seconds since the Unix epoch
+    pub first_seen: u64,
+    /// When the name last resolved to it, in seconds since the Unix epoch
+    pub last_seen: u64,
+}
+
+impl Pin {
+    fn new(instruction: &PaymentInstruction, now: u64) -> Self {
+        Self {
+            uri: instruction.uri.clone(),
+            status: instruction.dnssec.status,
+            key: KeyId::of(&instruction.dnssec),
+            first_seen: now,
+            last_seen: now,
+        }
+    }
+}
+
+/// A resolution compared with the name's pin
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PinCheck {
+    pub instruction: PaymentInstruction,
+    /// The pin as it was before this resolution; `None` on first use
+    pub previous: Option<Pin>,
+    /// Payment methods added, removed or changed since the pin, sorted:
+    /// `address` for the on-chain address, otherwise the URI parameter
+    pub changed_methods: Vec<String>,
+    /// The signing zone is anchored by a different KSK than the pinned one;
+    /// a ZSK rollover alone does not set this
+    pub key_rotated: bool,
+    /// The pin or this result names no signing key, because it was not
+    /// validated locally, so `key_rotated` could not be checked
+    pub key_unverified: bool,
+    /// The pin was `Secure` but this result is not
+    pub status_downgraded: bool,
+}
+
+impl PinCheck {
+    /// Was the name seen for the first time (and pinned now)?
+    pub fn is_first_use(&self) -> bool {
+        self.previous.is_none()
+    }
+
+    /// Does the result differ from the pin, so the user should confirm it?
+    ///
+    /// An unverified key only counts if the pin had one: a pin made without
+    /// a key never could detect rotation, and is flagged on every check.
+    pub fn is_changed(&self) -> bool {
+        let key_lost = self.key_unverified && self.previous.as_ref().is_some_and(|pin| pin.key.is_some());
+        !self.changed_methods.is_empty() || self.key_rotated || key_lost || self.status_downgraded
+    }
+
+    /// When the name last resolved to the pinned instruction
+    pub fn last_seen(&self) -> Option<u64> {
+        self.previous.as_ref().map(|pin| pin.last_seen)
+    }
+}
+
+/// Pinned payment instructions, keyed by name
+#[derive(Default)]
+pub struct AddressBook {
+    pins: Mutex<HashMap<HumanReadableName, Pin>>,
+    #[cfg(feature = "storage")]
+    storage: Option<Arc<dyn Storage>>,
+}
+
+impl AddressBook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// An address book kept in `storage`, starting with the pins stored there
+    ///
+    /// Fails if a stored pin cannot be read, rather than forgetting it.
+    #[cfg(feature = "storage")]
+    pub fn with_storage(storage: Arc<dyn Storage>) -> Result<Self, Bip353Error> {
+        let pins = storage.entries(PINS_NAMESPACE)?
+            .iter()
+            .map(|(_, value)| storage::decode_pin(value))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { pins: Mutex::new(pins), storage: Some(storage) })
+    }
+
+    #[cfg(feature = "storage")]
+    fn persist(&self, name: &HumanReadableName, pin: Option<&Pin>) -> Result<(), Bip353Error> {
+        let Some(storage) = &self.storage else {
+            return Ok(());
+        };
+        let key = format!("{}@{}", name.user(), name.domain());
+        match pin {
+            Some(pin) => storage.put(PINS_NAMESPACE, &key, &storage::encode_pin(name, pin)?),
+            None => storage.remove(PINS_NAMESPACE, &key),
+        }
+    }
+
+    #[cfg(not(feature = "storage"))]
+    fn persist(&self, _name: &HumanReadableName, _pin: Option<&Pin>) -> Result<(), Bip353Error> {
+        Ok(())
+    }
+
+    pub fn get(&self, name: &HumanReadableName) -> Option<Pin> {
+        self.pins.lock().unwrap().get(name).cloned()
+    }
+
+    /// All pinned names, sorted
+    pub fn names(&self) -> Vec<HumanReadableName> {
+        let mut names: Vec<_> = self.pins.lock().unwrap().keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Trust `instruction` for `name`, e.g. after the user accepted a change
+    pub fn pin(&self, name: &HumanReadableName, instruction: &PaymentInstruction) -> Result<(), Bip353Error> {
+        let mut pins = self.pins.lock().unwrap();
+        let pin = Pin::new(instruction, unix_time());
+        self.persist(name, Some(&pin))?;
+        pins.insert(name.clone(), pin);
+        Ok(())
+    }
+
+    pub fn remove(&self, name: &HumanReadableName) -> Result<Option<Pin>, Bip353Error> {
+        let mut pins = self.pins.lock().unwrap();
+        self.persist(name, None)?;
+        Ok(pins.remove(name))
+    }
+
+    /// Compare `instruction` with the pin for `name`
+    ///
+    /// An unpinned name is pinned, and an unchanged result updates
+    /// `last_seen`. A changed result leaves the pin as it was.
+    pub fn check(&self, name: &HumanReadableName, instruction: PaymentInstruction) -> Result<PinCheck, Bip353Error> {
+        let now = unix_time();
+        let mut pins = self.pins.lock().unwrap();
+        let Some(pin) = pins.get_mut(name) else {
+            let pin = Pin::new(&instruction, now);
+            let key_unverified = pin.key.is_none();
+            self.persist(name, Some(&pin))?;
+            pins.insert(name.clone(), pin);
+            return Ok(PinCheck {
+                instruction,
+                previous: None,
+                changed_methods: Vec::new(),
+                key_rotated: false,
+                key_unverified,
+                status_downgraded: false,
+            });
+        };
+
+        let previous = pin.clone();
+        let changed_methods = changed_methods(&payment_methods(&previous.uri), &payment_methods(&instruction.uri));
+        // A result that was not validated locally says nothing about the key
+        let (key_rotated, key_unverified) = match (&previous.key, KeyId::of(&instruction.dnssec)) {
+            (Some(pinned), Some(key)) => (pinned != &key, false),
+            _ => (false, true),
+        };
+        let status_downgraded = previous.status == DnssecStatus::Secure && instruction.dnssec.status != DnssecStatus::Secure;
+        let check = PinCheck { instruction, previous: Some(previous), changed_methods, key_rotated, key_unverified, status_downgraded };
+        if !check.is_changed() {
+            let seen = Pin { last_seen: now, ..pin.clone() };
+            self.persist(name, Some(&seen))?;
+            *pin = seen;
+        }
+        Ok(check)
+    }
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// Where a URI sends payments: its on-chain address and every parameter
+/// other than amount, label and message, with lowercased keys
+fn payment_methods(uri: &str) -> BTreeMap<String, String> {
+    let Ok(instruction) = PaymentInstruction::from_uri(uri) else {
+        return BTreeMap::new();
+    };
+    let mut methods: BTreeMap<String, String> = instruction.parameters.iter()
+        .map(|(key, value)| (key.to_lowercase(), value.clone()))
+        .filter(|(key, _)| !METADATA_PARAMETERS.contains(&key.as_str()))
+        .collect();
+    if let Some(address) = instruction.onchain_address() {
+        methods.insert("address".into(), address.to_string());
+    }
+    methods
+}
+
+fn changed_methods(old: &BTreeMap<String, String>, new: &BTreeMap<String, String>) -> Vec<String> {
+    let mut changed: Vec<String> = old.keys().chain(new.keys())
+        .filter(|key| old.get(*key) != new.get(*key))
+        .cloned()
+        .collect();
+    changed.sort();
+    changed.dedup();
+    changed
+}
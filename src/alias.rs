@@ -0,0 +1,101 @@
+//! CNAME and DNAME aliases
+//!
+//! A payment name may be an alias, e.g. a DNAME at
+//! `user._bitcoin-payment.example.com` handing every user to a payment
+//! provider. Each hop is followed explicitly and validated on its own, and
+//! the CNAME a server synthesizes from a DNAME is never trusted: the
+//! target is derived from the signed DNAME instead (RFC 6672 5.3.3).
+
+use trust_dns_proto::rr::{Name, RData, Record, RecordType};
+use trust_dns_proto::serialize::binary::{BinDecodable, BinDecoder};
+
+use crate::dnssec::{self, DnssecInfo};
+
+/// Most aliases followed for one lookup
+pub(crate) const MAX_ALIAS_HOPS: usize = 8;
+
+/// DNAME record type (RFC 6672), which `trust-dns` 0.22 has no type for
+pub(crate) const DNAME: RecordType = RecordType::Unknown(39);
+
+/// Kind of alias record
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum AliasKind {
+    Cname,
+    Dname,
+}
+
+/// An alias followed while resolving
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Alias {
+    pub kind: AliasKind,
+    /// Owner of the alias record; an ancestor of `name` for a DNAME
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialization::name"))]
+    pub owner: Name,
+    /// Name that was looked up
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialization::name"))]
+    pub name: Name,
+    /// Name the lookup continued at
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialization::name"))]
+    pub target: Name,
+    /// How the alias record was validated
+    pub dnssec: DnssecInfo,
+}
+
+/// The RRset answering a query: the queried records themselves, or an
+/// alias to follow
+pub(crate) struct Step {
+    /// Owner and type of the RRset to validate
+    pub owner: Name,
+    pub record_type: RecordType,
+    /// Where the query continues, if this is an alias
+    pub target: Option<Name>,
+}
+
+impl Step {
+    pub fn kind(&self) -> AliasKind {
+        if self.record_type == DNAME { AliasKind::Dname } else { AliasKind::Cname }
+    }
+}
+
+/// Find what answers `name`/`record_type` in `records`
+///
+/// A DNAME above `name` wins over a CNAME at it, since that CNAME is the
+/// server's unsigned synthesis.
+pub(crate) fn step(records: &[Record], name: &Name, record_type: RecordType) -> Option<Step> {
+    if !dnssec::rrset(records, name, record_type).is_empty() {
+        return Some(Step { owner: name.clone(), record_type, target: None });
+    }
+
+    let dname = records.iter()
+        .filter(|r| r.record_type() == DNAME && r.name() != name && r.name().zone_of(name))
+        .max_by_key(|r| r.name().num_labels())
+        .and_then(|r| Some((r.name(), dname_target(r)?)));
+    if let Some((owner, target)) = dname {
+        let prefix = Name::from_labels(name.iter().take(name.iter().len() - owner.iter().len())).ok()?;
+        let target = prefix.append_domain(&target).ok()?;
+        return Some(Step { owner: owner.clone(), record_type: DNAME, target: Some(target) });
+    }
+
+    records.iter()
+        .filter(|r| r.name() == name)
+        .find_map(|r| match r.data() {
+            Some(RData::CNAME(target)) => Some(target.clone()),
+            _ => None,
+        })
+        .map(|target| Step { owner: name.clone(), record_type: RecordType::CNAME, target: Some(target) })
+}
+
+/// Target of a DNAME record, whose RDATA is a single uncompressed name
+fn dname_target(record: &Record) -> Option<Name> {
+    match record.data() {
+        Some(RData::Unknown { code: 39, rdata }) => {
+            let mut target = Name::read(&mut BinDecoder::new(rdata.anything())).ok()?;
+            target.set_fqdn(true);
+            Some(target)
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,65 @@
+//! Plain DNS over TCP on async-io, for async-std and smol
+//!
+//! async-io drives its sockets from its own reactor thread, so this
+//! transport works under any executor, or none (`futures_lite::future::block_on`).
+
+use async_io::Timer;
+use async_net::TcpStream;
+use async_trait::async_trait;
+use futures_lite::{AsyncReadExt, AsyncWriteExt, FutureExt};
+
+use crate::tcp::TcpConfig;
+use crate::transport::{self, DnsTransport};
+use crate::Bip353Error;
+
+/// DNS-over-TCP transport on async-io
+///
+/// Like `TcpTransport`, it opens a fresh connection per query.
+pub struct AsyncIoTransport {
+    config: TcpConfig,
+}
+
+impl AsyncIoTransport {
+    /// Create a transport from `config`
+    ///
+    /// SOCKS5 proxies are only supported by the Tokio `TcpTransport`.
+    pub fn new(config: TcpConfig) -> Result<Self, Bip353Error> {
+        if config.proxy.is_some() {
+            return Err(Bip353Error::DnsError("SOCKS5 proxies need the `tokio` feature".into()));
+        }
+        Ok(Self { config })
+    }
+
+    async fn send(&self, query: &[u8]) -> Result<Vec<u8>, Bip353Error> {
+        let mut stream = TcpStream::connect(self.config.address)
+            .await
+            .map_err(|e| Bip353Error::TransportError(format!("Cannot connect to {}: {}", self.config.address, e)))?;
+        stream_exchange(&mut stream, query)
+            .await
+            .map_err(|e| Bip353Error::TransportError(format!("TCP I/O error: {}", e)))
+    }
+}
+
+#[async_trait]
+impl DnsTransport for AsyncIoTransport {
+    async fn exchange(&self, query: &[u8]) -> Result<Vec<u8>, Bip353Error> {
+        let timeout = async {
+            Timer::after(self.config.timeout).await;
+            Err(Bip353Error::TransportError("TCP request timed out".into()))
+        };
+        self.send(query).or(timeout).await
+    }
+}
+
+/// Exchange a length-prefixed message over an async-io stream, framed
+/// like `transport::stream_exchange`
+async fn stream_exchange(stream: &mut TcpStream, query: &[u8]) -> std::io::Result<Vec<u8>> {
+    stream.write_all(&transport::frame(query)?).await?;
+    stream.flush().await?;
+
+    let mut prefix = [0u8; 2];
+    stream.read_exact(&mut prefix).await?;
+    let mut response = transport::frame_buffer(prefix);
+    stream.read_exact(&mut response).await?;
+    Ok(response)
+}
@@ -0,0 +1,133 @@
+//! Synchronous resolver for callers without an async runtime
+//!
+//! `BlockingResolver` owns a small Tokio runtime and blocks the calling
+//! thread on it. Lookups run on the runtime's own worker threads, so it can
+//! also be called from inside another Tokio runtime (e.g. a library used
+//! from an async application) without the "cannot block_on inside a
+//! runtime" panic; the calling thread is blocked either way.
+
+use std::future::Future;
+use std::sync::{mpsc, Arc};
+
+use tokio::runtime::{Builder, Handle, Runtime};
+
+use crate::{
+    AddressBook, Bip353Config, Bip353Error, Finding, HumanReadableName, LintConfig, PaymentInstruction, PinCheck,
+    Resolver,
+};
+
+/// A `Resolver` with blocking methods
+pub struct BlockingResolver {
+    resolver: Arc<Resolver>,
+    // Only `None` while being dropped
+    runtime: Option<Runtime>,
+}
+
+impl Drop for BlockingResolver {
+    fn drop(&mut self) {
+        // Dropping a runtime normally waits for its workers, which panics
+        // inside another runtime and can deadlock language bindings
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+impl BlockingResolver {
+    /// Create a resolver using the system nameservers, like `Resolver::new`
+    pub fn new() -> Result<Self, Bip353Error> {
+        Self::build(Resolver::new)
+    }
+
+    /// Create a resolver from a `Bip353Config`
+    pub fn from_config(config: Bip353Config) -> Result<Self, Bip353Error> {
+        Self::build(|| Resolver::from_config(config))
+    }
+
+    /// Wrap an existing resolver
+    pub fn from_resolver(resolver: Resolver) -> Result<Self, Bip353Error> {
+        Self::build(|| Ok(resolver))
+    }
+
+    fn build(resolver: impl FnOnce() -> Result<Resolver, Bip353Error>) -> Result<Self, Bip353Error> {
+        let runtime = Builder::new_multi_thread()
+            .enable_all()
+            .thread_name("bip353")
+            .build()
+            .map_err(|e| Bip353Error::DnsError(format!("Cannot start runtime: {}", e)))?;
+        // Resolvers may spawn background tasks when created
+        let resolver = {
+            let _guard = runtime.enter();
+            resolver()?
+        };
+        Ok(Self { resolver: Arc::new(resolver), runtime: Some(runtime) })
+    }
+
+    /// The underlying async resolver
+    pub fn resolver(&self) -> &Resolver {
+        &self.resolver
+    }
+
+    pub fn resolve(&self, user: &str, domain: &str) -> Result<PaymentInstruction, Bip353Error> {
+        let (user, domain) = (user.to_string(), domain.to_string());
+        self.run(|resolver| async move { resolver.resolve(&user, &domain).await })
+    }
+
+    pub fn resolve_address(&self, address: &str) -> Result<PaymentInstruction, Bip353Error> {
+        let address = address.to_string();
+        self.run(|resolver| async move { resolver.resolve_address(&address).await })
+    }
+
+    /// Resolve many addresses, like `Resolver::resolve_many` without progress
+    pub fn resolve_many<S: AsRef<str>>(
+        &self,
+        addresses: &[S],
+        concurrency: usize,
+    ) -> Vec<Result<PaymentInstruction, Bip353Error>> {
+        let addresses: Vec<String> = addresses.iter().map(|address| address.as_ref().to_string()).collect();
+        self.run(move |resolver| async move { resolver.resolve_many(&addresses, concurrency, |_, _| {}).await })
+    }
+
+    /// Resolve `name` and compare the result with its pin in `book`
+    pub fn resolve_pinned(&self, name: &HumanReadableName, book: &AddressBook) -> Result<PinCheck, Bip353Error> {
+        let instruction = self.resolve(name.user(), name.domain())?;
+        book.check(name, instruction)
+    }
+
+    pub fn lint(&self, user: &str, domain: &str, config: &LintConfig) -> Result<Vec<Finding>, Bip353Error> {
+        let (user, domain, config) = (user.to_string(), domain.to_string(), config.clone());
+        self.run(|resolver| async move { resolver.lint(&user, &domain, &config).await })
+    }
+
+    /// Run `f` on this resolver's runtime, blocking until it finishes
+    fn run<T, F, Fut>(&self, f: F) -> T
+    where
+        T: Send + 'static,
+        F: FnOnce(Arc<Resolver>) -> Fut,
+        Fut: Future<Output = T> + Send + 'static,
+    {
+        let runtime = self.runtime.as_ref().expect("runtime is only taken on drop");
+        if Handle::try_current().is_err() {
+            return runtime.block_on(f(self.resolver.clone()));
+        }
+        // Inside another runtime, which may not allow blocking its own
+        // threads on a nested runtime: wait on a channel instead
+        let (sender, receiver) = mpsc::sync_channel(1);
+        let future = f(self.resolver.clone());
+        runtime.spawn(async move {
+            let _ = sender.send(future.await);
+        });
+        receiver.recv().expect("lookup task panicked")
+    }
+
+    /// Spawn `f` on this resolver's runtime without waiting for it
+    #[cfg(feature = "python")]
+    pub(crate) fn spawn<F, Fut>(&self, f: F)
+    where
+        F: FnOnce(Arc<Resolver>) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let runtime = self.runtime.as_ref().expect("runtime is only taken on drop");
+        runtime.spawn(f(self.resolver.clone()));
+    }
+}
@@ -0,0 +1,238 @@
+//! Cache of resolved payment instructions
+//!
+//! Entries are keyed by the human-readable name and kept no longer than
+//! both the record TTL and the RRSIG validity allow, so a cached answer is
+//! never one that would fail validation if fetched again. NXDOMAIN answers
+//! are kept for the negative TTL from the zone's SOA (RFC 2308).
+//!
+//! With the `storage` feature, entries are also written to a `Storage` and
+//! loaded from it when the cache is created, so they survive restarts.
+//! Storage failures only cost cache hits, so they are ignored.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+#[cfg(feature = "storage")]
+use std::sync::Arc;
+#[cfg(feature = "storage")]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "storage")]
+use crate::storage::{self, Storage, CACHE_NAMESPACE};
+use crate::{Bip353Error, PaymentInstruction};
+
+/// Cache settings
+#[derive(Clone)]
+pub struct CacheConfig {
+    /// Maximum number of names kept; the soonest-expiring entry is evicted first
+    pub max_entries: usize,
+    /// Upper bound on how long any entry is kept, whatever its TTL
+    pub max_ttl: Duration,
+    /// Where entries are persisted; `None` keeps them in memory only
+    #[cfg(feature = "storage")]
+    pub storage: Option<Arc<dyn Storage>>,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            max_entries: 10_000,
+            max_ttl: Duration::from_secs(86400),
+            #[cfg(feature = "storage")]
+            storage: None,
+        }
+    }
+}
+
+impl fmt::Debug for CacheConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut debug = f.debug_struct("CacheConfig");
+        debug.field("max_entries", &self.max_entries).field("max_ttl", &self.max_ttl);
+        #[cfg(feature = "storage")]
+        debug.field("storage", &self.storage.is_some());
+        debug.finish()
+    }
+}
+
+/// Cache hit/miss counters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// Names currently cached, including not yet evicted expired ones
+    pub entries: usize,
+}
+
+pub(crate) enum Cached {
+    Instruction(Box<PaymentInstruction>),
+    /// A name known to have no records, and the error reporting it
+    Absent(Bip353Error),
+}
+
+struct Entry {
+    value: Cached,
+    expires: Instant,
+}
+
+pub(crate) struct Cache {
+    config: CacheConfig,
+    entries: Mutex<HashMap<String, Entry>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl Cache {
+    pub fn new(config: CacheConfig) -> Self {
+        let cache = Self {
+            config,
+            entries: Mutex::new(HashMap::new()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        };
+        cache.restore();
+        cache
+    }
+
+    /// Load the unexpired entries from storage, and drop the rest there
+    #[cfg(feature = "storage")]
+    fn restore(&self) {
+        let Some(storage) = &self.config.storage else {
+            return;
+        };
+        let now = unix_time();
+        let mut entries = self.entries.lock().unwrap();
+        for (key, value) in storage.entries(CACHE_NAMESPACE).unwrap_or_default() {
+            match storage::decode_cached(&value) {
+                Some((value, expires)) if expires > now && entries.len() < self.config.max_entries => {
+                    let ttl = Duration::from_secs(expires - now).min(self.config.max_ttl);
+                    entries.insert(key, Entry { value, expires: Instant::now() + ttl });
+                }
+                _ => {
+                    let _ = storage.remove(CACHE_NAMESPACE, &key);
+                }
+            }
+        }
+    }
+
+    #[cfg(not(feature = "storage"))]
+    fn restore(&self) {}
+
+    #[cfg(feature = "storage")]
+    fn persist(&self, key: &str, value: &Cached, ttl: Duration) {
+        if let Some(storage) = &self.config.storage {
+            if let Ok(value) = storage::encode_cached(value, unix_time() + ttl.as_secs()) {
+                let _ = storage.put(CACHE_NAMESPACE, key, &value);
+            }
+        }
+    }
+
+    #[cfg(not(feature = "storage"))]
+    fn persist(&self, _key: &str, _value: &Cached, _ttl: Duration) {}
+
+    #[cfg(feature = "storage")]
+    fn unpersist(&self, key: &str) {
+        if let Some(storage) = &self.config.storage {
+            let _ = storage.remove(CACHE_NAMESPACE, key);
+        }
+    }
+
+    #[cfg(not(feature = "storage"))]
+    fn unpersist(&self, _key: &str) {}
+
+    /// Cache key for `user`@`domain`; DNS names are case-insensitive
+    pub fn key(user: &str, domain: &str) -> String {
+        format!("{}@{}", user, domain.trim_end_matches('.')).to_lowercase()
+    }
+
+    /// Look up `key`, counting a hit or miss
+    pub fn get(&self, key: &str) -> Option<Result<PaymentInstruction, Bip353Error>> {
+        let mut entries = self.entries.lock().unwrap();
+        let result = match entries.get(key) {
+            Some(entry) if entry.expires > Instant::now() => Some(match &entry.value {
+                Cached::Instruction(instruction) => Ok((**instruction).clone()),
+                Cached::Absent(error) => Err(error.clone()),
+            }),
+            Some(_) => {
+                entries.remove(key);
+                self.unpersist(key);
+                None
+            }
+            None => None,
+        };
+
+        let counter = if result.is_some() { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        result
+    }
+
+    /// Cache an instruction for `ttl` seconds
+    pub fn insert(&self, key: String, instruction: PaymentInstruction, ttl: u32) {
+        self.store(key, Cached::Instruction(Box::new(instruction)), ttl);
+    }
+
+    /// Cache a non-existent name for `ttl` seconds
+    pub fn insert_absent(&self, key: String, error: &Bip353Error, ttl: u32) {
+        self.store(key, Cached::Absent(error.clone()), ttl);
+    }
+
+    fn store(&self, key: String, value: Cached, ttl: u32) {
+        let ttl = Duration::from_secs(ttl.into()).min(self.config.max_ttl);
+        if ttl.is_zero() || self.config.max_entries == 0 {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.config.max_entries && !entries.contains_key(&key) {
+            let now = Instant::now();
+            entries.retain(|key, entry| {
+                let keep = entry.expires > now;
+                if !keep {
+                    self.unpersist(key);
+                }
+                keep
+            });
+            if entries.len() >= self.config.max_entries {
+                let soonest = entries.iter()
+                    .min_by_key(|(_, entry)| entry.expires)
+                    .map(|(key, _)| key.clone());
+                if let Some(soonest) = soonest {
+                    entries.remove(&soonest);
+                    self.unpersist(&soonest);
+                }
+            }
+        }
+        self.persist(&key, &value, ttl);
+        entries.insert(key, Entry { value, expires: Instant::now() + ttl });
+    }
+
+    /// Drop the entry for `key`, if any
+    pub fn flush(&self, key: &str) {
+        self.entries.lock().unwrap().remove(key);
+        self.unpersist(key);
+    }
+
+    /// Drop every entry
+    pub fn clear(&self) {
+        let mut entries = self.entries.lock().unwrap();
+        for key in entries.keys() {
+            self.unpersist(key);
+        }
+        entries.clear();
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: self.entries.lock().unwrap().len(),
+        }
+    }
+}
+
+#[cfg(feature = "storage")]
+fn unix_time() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
@@ -0,0 +1,260 @@
+//! Authenticated denial of existence (NSEC, RFC 4035; NSEC3, RFC 5155)
+//!
+//! Used to prove that a name has no TXT records, that an unsigned answer
+//! sits below a delegation without a DS record, and, for a
+//! wildcard-expanded answer, that no name closer to the query than the
+//! wildcard's closest encloser exists; otherwise a signed wildcard could be
+//! replayed over a real name.
+
+use trust_dns_proto::rr::dnssec::rdata::{DNSSECRData, NSEC, NSEC3};
+use trust_dns_proto::rr::{Name, RData, Record, RecordType};
+
+use crate::dnssec::{self, AlgorithmPolicy, ZoneKeys};
+
+/// NSEC3 records with more hash iterations are ignored (RFC 9276 3.2)
+pub(crate) const MAX_NSEC3_ITERATIONS: u16 = 150;
+
+/// The NSEC and NSEC3 records of one zone that verify
+pub(crate) struct Denials<'a> {
+    zone: &'a Name,
+    nsec: Vec<(&'a Name, &'a NSEC)>,
+    nsec3: Vec<(&'a Name, Vec<u8>, &'a NSEC3)>,
+}
+
+impl<'a> Denials<'a> {
+    /// Collect the NSEC and NSEC3 records in `records` validly signed by `zone`
+    pub fn new(
+        records: &'a [Record],
+        zone: &'a Name,
+        zone_keys: &ZoneKeys,
+        policy: &AlgorithmPolicy,
+        now: u32,
+    ) -> Self {
+        let verified = |owner: &Name, record_type: RecordType| {
+            dnssec::verify_exact(records, owner, record_type, zone_keys, policy, now)
+                .is_ok_and(|proof| proof.sig.signer_name() == zone)
+        };
+
+        let mut denials = Self { zone, nsec: Vec::new(), nsec3: Vec::new() };
+        for record in records.iter().filter(|r| zone.zone_of(r.name())) {
+            match record.data() {
+                Some(RData::DNSSEC(DNSSECRData::NSEC(nsec))) if verified(record.name(), RecordType::NSEC) => {
+                    denials.nsec.push((record.name(), nsec));
+                }
+                Some(RData::DNSSEC(DNSSECRData::NSEC3(nsec3))) => {
+                    let Some(hash) = nsec3_owner_hash(record.name(), zone) else { continue };
+                    if nsec3.iterations() <= MAX_NSEC3_ITERATIONS && verified(record.name(), RecordType::NSEC3) {
+                        denials.nsec3.push((record.name(), hash, nsec3));
+                    }
+                }
+                _ => {}
+            }
+        }
+        denials
+    }
+
+    /// Is it proven that no name between `closest_encloser` and `name` exists?
+    pub fn no_closer_match(&self, name: &Name, closest_encloser: &Name) -> bool {
+        // If the next closer name does not exist, neither does anything below it
+        let next_closer = name.trim_to(closest_encloser.num_labels() as usize + 1);
+        self.nsec_covering(&next_closer).is_some() || self.nsec3_covering(&next_closer, true)
+    }
+
+    /// Is it proven that `name` has no `record_type` RRset, either because
+    /// the name does not exist (NXDOMAIN) or because it has no such records
+    /// (NODATA), directly or at the wildcard that would have matched?
+    pub fn no_rrset(&self, name: &Name, record_type: RecordType) -> bool {
+        self.nsec_no_rrset(name, record_type) || self.nsec3_no_rrset(name, record_type)
+    }
+
+    /// The NSEC or NSEC3 proving that `name` is at or below a delegation
+    /// without a DS record, i.e. in an unsigned child zone, as its owner
+    /// and type
+    ///
+    /// An NSEC3 opt-out delegation has no record of its own; the opt-out
+    /// span covering the next closer name stands in for it (RFC 5155 8.6).
+    pub fn insecure_delegation(&self, name: &Name) -> Option<(&'a Name, RecordType)> {
+        let unsigned_cut = |types: &[RecordType]| is_delegation(types) && !types.contains(&RecordType::DS);
+        for cut in (self.zone.num_labels() + 1..=name.num_labels()).map(|labels| name.trim_to(labels as usize)) {
+            if let Some((owner, _)) = self.nsec.iter().find(|(owner, nsec)| **owner == cut && unsigned_cut(nsec.type_bit_maps())) {
+                return Some((owner, RecordType::NSEC));
+            }
+            if let Some((owner, nsec3)) = self.nsec3_matching_record(&cut) {
+                // A signed delegation has a DS, and then the child's own
+                // records decide
+                return unsigned_cut(nsec3.type_bit_maps()).then_some((owner, RecordType::NSEC3));
+            }
+        }
+
+        let closest_encloser = (self.zone.num_labels()..name.num_labels())
+            .rev()
+            .map(|labels| name.trim_to(labels as usize))
+            .find(|ancestor| self.nsec3_matching(ancestor).is_some())?;
+        let next_closer = name.trim_to(closest_encloser.num_labels() as usize + 1);
+        let opt_out = self.nsec3_spanning(&next_closer).find(|(_, nsec3)| nsec3.opt_out());
+        opt_out.map(|(owner, _)| (owner, RecordType::NSEC3))
+    }
+
+    fn nsec_no_rrset(&self, name: &Name, record_type: RecordType) -> bool {
+        if let Some((_, nsec)) = self.nsec.iter().find(|(owner, _)| *owner == name) {
+            return lacks(nsec.type_bit_maps(), record_type);
+        }
+        let Some((owner, nsec)) = self.nsec_covering(name) else {
+            // An empty non-terminal: the NSEC before it points below it
+            return self.nsec.iter().any(|(owner, nsec)| {
+                let next = nsec.next_domain_name();
+                *owner < name && name < next && name.zone_of(next)
+            });
+        };
+
+        // No such name, so the wildcard at the closest encloser must not exist
+        // either, or at least have no such records
+        let closest_encloser = (0..name.num_labels())
+            .rev()
+            .map(|labels| name.trim_to(labels as usize))
+            .find(|ancestor| ancestor.zone_of(owner) || ancestor.zone_of(nsec.next_domain_name()));
+        let Some(wildcard) = closest_encloser.as_ref().and_then(wildcard) else { return false };
+        match self.nsec.iter().find(|(owner, _)| **owner == wildcard) {
+            Some((_, nsec)) => lacks(nsec.type_bit_maps(), record_type),
+            None => self.nsec_covering(&wildcard).is_some(),
+        }
+    }
+
+    fn nsec3_no_rrset(&self, name: &Name, record_type: RecordType) -> bool {
+        if let Some(nsec3) = self.nsec3_matching(name) {
+            return lacks(nsec3.type_bit_maps(), record_type);
+        }
+
+        // Closest encloser proof (RFC 5155 8.3): an ancestor that exists,
+        // and a next closer name that does not
+        let Some(closest_encloser) = (self.zone.num_labels()..name.num_labels())
+            .rev()
+            .map(|labels| name.trim_to(labels as usize))
+            .find(|ancestor| self.nsec3_matching(ancestor).is_some())
+        else {
+            return false;
+        };
+        let delegation = self.nsec3_matching(&closest_encloser)
+            .is_some_and(|nsec3| is_delegation(nsec3.type_bit_maps()));
+        let next_closer = name.trim_to(closest_encloser.num_labels() as usize + 1);
+        if delegation || !self.nsec3_covering(&next_closer, false) {
+            return false;
+        }
+
+        let Some(wildcard) = wildcard(&closest_encloser) else { return false };
+        match self.nsec3_matching(&wildcard) {
+            Some(nsec3) => lacks(nsec3.type_bit_maps(), record_type),
+            None => self.nsec3_covering(&wildcard, false),
+        }
+    }
+
+    /// The NSEC whose span covers `name`
+    ///
+    /// A parent zone's NSEC at a delegation above `name` proves nothing about
+    /// the child zone, so it is skipped.
+    fn nsec_covering(&self, name: &Name) -> Option<(&'a Name, &'a NSEC)> {
+        self.nsec.iter()
+            .find(|(owner, nsec)| {
+                nsec_covers(owner, nsec.next_domain_name(), name)
+                    && !(owner.zone_of(name) && is_delegation(nsec.type_bit_maps()))
+            })
+            .copied()
+    }
+
+    fn nsec3_matching(&self, name: &Name) -> Option<&'a NSEC3> {
+        self.nsec3_matching_record(name).map(|(_, nsec3)| nsec3)
+    }
+
+    fn nsec3_matching_record(&self, name: &Name) -> Option<(&'a Name, &'a NSEC3)> {
+        self.nsec3.iter()
+            .find(|(_, owner_hash, nsec3)| nsec3_hash(nsec3, name).is_some_and(|hash| hash == *owner_hash))
+            .map(|(owner, _, nsec3)| (*owner, *nsec3))
+    }
+
+    /// Is `name` covered by an NSEC3? An opt-out NSEC3 may hide unsigned
+    /// delegations, so it only counts if `allow_opt_out`
+    fn nsec3_covering(&self, name: &Name, allow_opt_out: bool) -> bool {
+        self.nsec3_spanning(name).any(|(_, nsec3)| allow_opt_out || !nsec3.opt_out())
+    }
+
+    /// The NSEC3 records whose hash span covers `name`
+    fn nsec3_spanning<'s>(&'s self, name: &'s Name) -> impl Iterator<Item = (&'a Name, &'a NSEC3)> + 's {
+        self.nsec3.iter()
+            .filter(move |(_, owner_hash, nsec3)| {
+                let Some(hash) = nsec3_hash(nsec3, name) else { return false };
+                let (owner_hash, hash, next) = (owner_hash.as_slice(), hash.as_slice(), nsec3.next_hashed_owner_name());
+                if owner_hash < next {
+                    owner_hash < hash && hash < next
+                } else {
+                    owner_hash < hash || hash < next
+                }
+            })
+            .map(|(owner, _, nsec3)| (*owner, *nsec3))
+    }
+}
+
+/// Apex of the zone denying records at `name`: the deepest SOA above it
+pub(crate) fn zone(records: &[Record], name: &Name) -> Option<Name> {
+    records.iter()
+        .filter(|r| r.record_type() == RecordType::SOA && r.name().zone_of(name))
+        .map(|r| r.name().clone())
+        .max_by_key(Name::num_labels)
+}
+
+/// Negative caching TTL from the zone's SOA: min(SOA TTL, SOA minimum) (RFC 2308 5)
+pub(crate) fn negative_ttl(records: &[Record], zone: &Name) -> Option<u32> {
+    records.iter()
+        .filter(|r| r.name() == zone)
+        .find_map(|record| match record.data() {
+            Some(RData::SOA(soa)) => Some(record.ttl().min(soa.minimum())),
+            _ => None,
+        })
+}
+
+/// Does the NSEC at `owner`, pointing at `next`, show that `name` does not exist?
+fn nsec_covers(owner: &Name, next: &Name, name: &Name) -> bool {
+    // The last NSEC of a zone points back at the apex
+    let in_span = if owner < next {
+        owner < name && name < next
+    } else {
+        owner < name || name < next
+    };
+    // A name with descendants exists even without records of its own
+    in_span && !name.zone_of(next)
+}
+
+/// Does a type bitmap show no `record_type` records and no CNAME?
+///
+/// The parent side of a zone cut says nothing about the child's records,
+/// except that there is no DS.
+fn lacks(types: &[RecordType], record_type: RecordType) -> bool {
+    !types.contains(&record_type)
+        && !types.contains(&RecordType::CNAME)
+        && (record_type == RecordType::DS || !is_delegation(types))
+}
+
+/// Is this the parent side of a zone cut: NS but no SOA?
+fn is_delegation(types: &[RecordType]) -> bool {
+    types.contains(&RecordType::NS) && !types.contains(&RecordType::SOA)
+}
+
+/// The wildcard `*.<closest_encloser>`
+pub(crate) fn wildcard(closest_encloser: &Name) -> Option<Name> {
+    let mut wildcard = Name::from_labels(std::iter::once(&b"*"[..]).chain(closest_encloser.iter())).ok()?;
+    wildcard.set_fqdn(true);
+    Some(wildcard)
+}
+
+/// The hash in an NSEC3 owner name, if the owner is directly below `zone`
+fn nsec3_owner_hash(owner: &Name, zone: &Name) -> Option<Vec<u8>> {
+    if owner.base_name() != *zone {
+        return None;
+    }
+    let label = owner.iter().next()?;
+    data_encoding::BASE32HEX_NOPAD.decode(&label.to_ascii_uppercase()).ok()
+}
+
+fn nsec3_hash(nsec3: &NSEC3, name: &Name) -> Option<Vec<u8>> {
+    let hash = nsec3.hash_algorithm().hash(nsec3.salt(), name, nsec3.iterations()).ok()?;
+    Some(hash.as_ref().to_vec())
+}
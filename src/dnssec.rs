@@ -0,0 +1,654 @@
+//! Local DNSSEC validation
+//!
+//! Validates an RRset against a bag of records containing the RRSIGs,
+//! DNSKEYs and DSes needed to build a chain of trust down from the trust
+//! anchors. Nothing here touches the network, so the same code checks
+//! answers fetched over any transport.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use trust_dns_proto::rr::dnssec::rdata::{DNSSECRData, DNSKEY, DS, SIG};
+use trust_dns_proto::rr::dnssec::{Algorithm, DigestType, Verifier};
+use trust_dns_proto::rr::rdata::NULL;
+use trust_dns_proto::rr::{DNSClass, Name, RData, Record, RecordType};
+
+use crate::{denial, Bip353Error};
+
+/// IANA root KSK-2017 (key tag 20326)
+const ROOT_DS_2017: &str = "E06D44B80B8F1D39A95C0B0D7C65D08458E880409BBC683457104237C7F8EC8D";
+/// IANA root KSK-2024 (key tag 38696)
+const ROOT_DS_2024: &str = "683D2D0ACB8C9B712A1948B27F741219298D0A450D612C483AF444A4C0FB2B16";
+
+/// Root of trust for DNSSEC validation
+///
+/// Defaults to the IANA root zone KSKs. Anchors may be given for any zone,
+/// e.g. the root of a private test hierarchy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrustAnchors {
+    ds: Vec<(Name, DS)>,
+    dnskeys: Vec<(Name, DNSKEY)>,
+}
+
+impl Default for TrustAnchors {
+    fn default() -> Self {
+        let mut anchors = Self::new();
+        for (key_tag, digest) in [(20326, ROOT_DS_2017), (38696, ROOT_DS_2024)] {
+            let digest = data_encoding::HEXUPPER.decode(digest.as_bytes())
+                .expect("root DS digests are valid hex");
+            anchors.add_ds(Name::root(), DS::new(key_tag, Algorithm::RSASHA256, DigestType::SHA256, digest));
+        }
+        anchors
+    }
+}
+
+impl TrustAnchors {
+    /// Create an empty set of trust anchors
+    pub fn new() -> Self {
+        Self { ds: Vec::new(), dnskeys: Vec::new() }
+    }
+
+    /// Parse DS and DNSKEY records in zone file presentation format
+    ///
+    /// Accepts the format of e.g. unbound's `root.key` or `dig DS .`
+    /// output: one record per line (or split with parentheses), with
+    /// optional TTL and class, and `;` comments. Other record types are
+    /// ignored, so a whole `dig` answer can be pasted in.
+    pub fn parse(text: &str) -> Result<Self, Bip353Error> {
+        let mut anchors = Self::new();
+        for line in logical_lines(text) {
+            let invalid = |what: &str| Bip353Error::DnssecError(format!("Invalid trust anchor ({}): {}", what, line));
+            let mut fields = line.split_whitespace();
+            let zone = fields.next().ok_or_else(|| invalid("empty"))?;
+            let mut zone = Name::from_ascii(zone).map_err(|_| invalid("owner name"))?;
+            zone.set_fqdn(true);
+
+            let mut fields = fields.skip_while(|f| f.parse::<u32>().is_ok() || f.eq_ignore_ascii_case("IN"));
+            let record_type = fields.next().ok_or_else(|| invalid("record type"))?.to_ascii_uppercase();
+            let mut number = |what: &str| -> Result<u16, Bip353Error> {
+                fields.next().and_then(|f| f.parse().ok()).ok_or_else(|| invalid(what))
+            };
+            match record_type.as_str() {
+                "DS" => {
+                    let key_tag = number("key tag")?;
+                    let algorithm = Algorithm::from_u8(number("algorithm")? as u8);
+                    let digest_type = DigestType::from_u8(number("digest type")? as u8).map_err(|_| invalid("digest type"))?;
+                    let digest: String = fields.collect();
+                    let digest = data_encoding::HEXUPPER_PERMISSIVE.decode(digest.as_bytes()).map_err(|_| invalid("digest"))?;
+                    anchors.add_ds(zone, DS::new(key_tag, algorithm, digest_type, digest));
+                }
+                "DNSKEY" => {
+                    let flags = number("flags")?;
+                    let _protocol = number("protocol")?;
+                    let algorithm = Algorithm::from_u8(number("algorithm")? as u8);
+                    let key: String = fields.collect();
+                    let key = data_encoding::BASE64.decode(key.as_bytes()).map_err(|_| invalid("public key"))?;
+                    let dnskey = DNSKEY::new(flags & 0x0100 != 0, flags & 0x0001 != 0, flags & 0x0080 != 0, algorithm, key);
+                    anchors.add_dnskey(zone, dnskey);
+                }
+                _ => {}
+            }
+        }
+        Ok(anchors)
+    }
+
+    /// Trust any key of `zone` matching this DS record
+    pub fn add_ds(&mut self, zone: Name, ds: DS) {
+        self.ds.push((zone, ds));
+    }
+
+    /// Trust this key of `zone` directly
+    pub fn add_dnskey(&mut self, zone: Name, dnskey: DNSKEY) {
+        self.dnskeys.push((zone, dnskey));
+    }
+
+    /// Is some anchor at or above `name`?
+    fn covers(&self, name: &Name) -> bool {
+        self.ds.iter().map(|(zone, _)| zone)
+            .chain(self.dnskeys.iter().map(|(zone, _)| zone))
+            .any(|zone| zone.zone_of(name))
+    }
+
+    /// Is `key` of `zone` one of the anchors?
+    fn trusts(&self, zone: &Name, key: &DNSKEY) -> bool {
+        self.dnskeys.iter().any(|(name, anchor)| name == zone && anchor == key)
+            || ds_matches(self.ds.iter().filter(|(name, _)| name == zone).map(|(_, ds)| ds), zone, key)
+    }
+}
+
+/// Join parenthesized multi-line records and drop comments and blank lines
+fn logical_lines(text: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0usize;
+    for line in text.lines() {
+        let line = line.split(';').next().unwrap_or_default();
+        for c in line.chars() {
+            match c {
+                '(' => depth += 1,
+                ')' => depth = depth.saturating_sub(1),
+                _ => current.push(c),
+            }
+        }
+        current.push(' ');
+        if depth == 0 {
+            if !current.trim().is_empty() {
+                lines.push(current.trim().to_string());
+            }
+            current.clear();
+        }
+    }
+    lines
+}
+
+/// Which DNSSEC signing algorithms validation accepts
+///
+/// Applies to every signature in the chain of trust, from the trust
+/// anchor down to the answer, so an allow list must include the
+/// algorithms the parent zones sign with (the root uses RSA/SHA-256).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AlgorithmPolicy {
+    /// If non-empty, only these algorithms are accepted
+    pub allowed: Vec<Algorithm>,
+    /// Never accepted, even if listed in `allowed`
+    pub forbidden: Vec<Algorithm>,
+}
+
+impl AlgorithmPolicy {
+    /// Does the policy accept signatures made with `algorithm`?
+    pub fn permits(&self, algorithm: Algorithm) -> bool {
+        !self.forbidden.contains(&algorithm) && (self.allowed.is_empty() || self.allowed.contains(&algorithm))
+    }
+}
+
+/// Outcome of DNSSEC validation (RFC 4033 section 5)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum DnssecStatus {
+    /// Signed, with a chain of trust down from a trust anchor
+    Secure,
+    /// Provably unsigned: a validated parent zone shows the delegation has no DS
+    Insecure,
+    /// Should be signed, but signatures are missing, invalid or expired
+    Bogus,
+    /// Not validated: no trust anchor covers the name, or validation was off
+    #[default]
+    Indeterminate,
+}
+
+impl DnssecStatus {
+    /// Stable string name, as used by the Python bindings
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DnssecStatus::Secure => "secure",
+            DnssecStatus::Insecure => "insecure",
+            DnssecStatus::Bogus => "bogus",
+            DnssecStatus::Indeterminate => "indeterminate",
+        }
+    }
+
+    /// The less trustworthy of two statuses
+    pub(crate) fn weaker(self, other: Self) -> Self {
+        let rank = |status: Self| match status {
+            DnssecStatus::Secure => 0,
+            DnssecStatus::Insecure => 1,
+            DnssecStatus::Indeterminate => 2,
+            DnssecStatus::Bogus => 3,
+        };
+        if rank(other) > rank(self) { other } else { self }
+    }
+}
+
+impl fmt::Display for DnssecStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Why a result was (or was not) trusted, for audit logs
+///
+/// The signature fields describe the RRSIG that decided the status: the
+/// one over the answer if Secure or Bogus, the one over the parent's proof
+/// of an unsigned delegation if Insecure. They are `None` when no RRSIG
+/// decided it, e.g. for a name no trust anchor covers, or for an instruction
+/// parsed from a URI rather than resolved.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DnssecInfo {
+    pub status: DnssecStatus,
+    /// Zone whose key made the signature
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialization::option_name"))]
+    pub signer: Option<Name>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialization::option_algorithm"))]
+    pub algorithm: Option<Algorithm>,
+    /// Key tag of the DNSKEY that made the signature
+    pub key_tag: Option<u16>,
+    /// Algorithm of the signer's key that a DS or trust anchor matches,
+    /// i.e. its KSK
+    #[cfg_attr(feature = "serde", serde(default, with = "crate::serialization::option_algorithm"))]
+    pub anchor_algorithm: Option<Algorithm>,
+    /// Key tag of the signer's key that a DS or trust anchor matches; unlike
+    /// `key_tag`, it survives a ZSK rollover
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub anchor_key_tag: Option<u16>,
+    /// Start of the signature's validity period, in seconds since the Unix epoch
+    pub inception: Option<u32>,
+    /// End of the signature's validity period, in seconds since the Unix epoch
+    pub expiration: Option<u32>,
+    /// Wildcard the answer was expanded from, e.g. `*.user._bitcoin-payment.example.`
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialization::option_name"))]
+    pub wildcard: Option<Name>,
+}
+
+impl DnssecInfo {
+    fn signed(status: DnssecStatus, sig: &SIG, zone_keys: &ZoneKeys) -> Self {
+        let anchor = zone_keys.anchor(sig.signer_name());
+        Self {
+            status,
+            signer: Some(sig.signer_name().clone()),
+            algorithm: Some(sig.algorithm()),
+            key_tag: Some(sig.key_tag()),
+            anchor_algorithm: anchor.map(|(algorithm, _)| algorithm),
+            anchor_key_tag: anchor.map(|(_, key_tag)| key_tag),
+            inception: Some(sig.sig_inception()),
+            expiration: Some(sig.sig_expiration()),
+            wildcard: None,
+        }
+    }
+}
+
+/// An RRset whose signature chained back to a trust anchor
+#[derive(Debug, Clone)]
+pub(crate) struct VerifiedRrset {
+    pub records: Vec<Record>,
+    /// Smallest of the record TTLs and the RRSIG's original TTL
+    pub ttl: u32,
+    /// The RRSIG that verified
+    pub sig: SIG,
+    /// Wildcard the RRset was expanded from, if any
+    pub wildcard: Option<Name>,
+}
+
+impl VerifiedRrset {
+    /// Seconds the RRset may be cached: min(TTL, time left until the RRSIG expires)
+    pub fn cache_ttl(&self, now: u32) -> u32 {
+        self.ttl.min(self.sig.sig_expiration().saturating_sub(now))
+    }
+}
+
+/// A classified answer
+#[derive(Debug, Clone)]
+pub(crate) struct Validated {
+    pub records: Vec<Record>,
+    pub info: DnssecInfo,
+    /// Seconds the answer may be cached
+    pub ttl: u32,
+    /// Why the answer is not Secure
+    pub reason: Option<String>,
+}
+
+/// Current time as used for RRSIG validity checks
+pub(crate) fn now() -> u32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as u32)
+        .unwrap_or(0)
+}
+
+/// DNSKEYs of every zone whose key set chains back to a trust anchor
+#[derive(Debug, Default)]
+pub(crate) struct ZoneKeys {
+    keys: HashMap<Name, Vec<DNSKEY>>,
+    /// Algorithm and key tag of the key, matched by a DS or a trust anchor,
+    /// that signed each zone's DNSKEY RRset
+    anchors: HashMap<Name, (Algorithm, u16)>,
+}
+
+impl ZoneKeys {
+    pub fn get(&self, zone: &Name) -> Option<&Vec<DNSKEY>> {
+        self.keys.get(zone)
+    }
+
+    /// The key that anchors `zone`, i.e. its KSK
+    pub fn anchor(&self, zone: &Name) -> Option<(Algorithm, u16)> {
+        self.anchors.get(zone).copied()
+    }
+}
+
+/// Verify the `name`/`record_type` RRset found in `records`
+///
+/// `zone_keys` comes from `validate_zone_keys`, run over records holding
+/// the DNSKEY and DS RRsets that link the RRset's signer to a trust anchor.
+/// A wildcard expansion is only accepted if `records` also hold the NSEC
+/// or NSEC3 proof that no closer match exists.
+pub(crate) fn verify_rrset(
+    records: &[Record],
+    name: &Name,
+    record_type: RecordType,
+    zone_keys: &ZoneKeys,
+    policy: &AlgorithmPolicy,
+    now: u32,
+) -> Result<VerifiedRrset, Bip353Error> {
+    verify(records, name, record_type, zone_keys, policy, now, true)
+}
+
+/// Like `verify_rrset`, but refuse wildcard expansions; for the denial
+/// records that wildcard proofs themselves rest on
+pub(crate) fn verify_exact(
+    records: &[Record],
+    name: &Name,
+    record_type: RecordType,
+    zone_keys: &ZoneKeys,
+    policy: &AlgorithmPolicy,
+    now: u32,
+) -> Result<VerifiedRrset, Bip353Error> {
+    verify(records, name, record_type, zone_keys, policy, now, false)
+}
+
+fn verify(
+    records: &[Record],
+    name: &Name,
+    record_type: RecordType,
+    zone_keys: &ZoneKeys,
+    policy: &AlgorithmPolicy,
+    now: u32,
+    allow_wildcard: bool,
+) -> Result<VerifiedRrset, Bip353Error> {
+    let rrset = rrset(records, name, record_type);
+    if rrset.is_empty() {
+        return Err(Bip353Error::DnssecError(format!("No {} records for {}", record_type, name)));
+    }
+
+    let signed = rrsigs(records, name, record_type).next().is_some();
+    if signed && !rrsigs(records, name, record_type).any(|sig| policy.permits(sig.algorithm())) {
+        return Err(Bip353Error::DnssecError(format!("{} {} is only signed with forbidden algorithms", name, record_type)));
+    }
+    let mut unproven_wildcard = false;
+    for sig in rrsigs(records, name, record_type) {
+        let keys = match zone_keys.get(sig.signer_name()) {
+            Some(keys) if sig.signer_name().zone_of(name) => keys,
+            _ => continue,
+        };
+        if !verify_with_any(keys, sig, &rrset, name, policy, now) {
+            continue;
+        }
+
+        // Fewer labels than the owner name: expanded from *.<closest encloser>
+        let wildcard = if sig.num_labels() < name.num_labels() {
+            let closest_encloser = name.trim_to(sig.num_labels() as usize);
+            let proven = allow_wildcard
+                && denial::Denials::new(records, sig.signer_name(), zone_keys, policy, now)
+                    .no_closer_match(name, &closest_encloser);
+            match denial::wildcard(&closest_encloser) {
+                Some(wildcard) if proven => Some(wildcard),
+                _ => {
+                    unproven_wildcard = true;
+                    continue;
+                }
+            }
+        } else {
+            None
+        };
+        let ttl = rrset.iter().map(Record::ttl).fold(sig.original_ttl(), u32::min);
+        return Ok(VerifiedRrset { records: rrset, ttl, sig: sig.clone(), wildcard });
+    }
+
+    if unproven_wildcard {
+        return Err(Bip353Error::DnssecError(format!(
+            "{} {} is a wildcard expansion without proof that no closer match exists", name, record_type,
+        )));
+    }
+    Err(Bip353Error::DnssecError(format!("No valid signature chain for {} {}", name, record_type)))
+}
+
+/// Validate the `name`/`record_type` RRset found in `records` and classify it
+///
+/// Fails only if the RRset is missing altogether. An RRset that does not
+/// validate, signed or not, is Insecure if `records` also hold an NSEC or
+/// NSEC3, signed by a validated parent, showing a delegation at or above
+/// `name` without a DS record, or an NSEC3 opt-out span that may hide one.
+pub(crate) fn validate(
+    records: &[Record],
+    name: &Name,
+    record_type: RecordType,
+    zone_keys: &ZoneKeys,
+    anchors: &TrustAnchors,
+    policy: &AlgorithmPolicy,
+    now: u32,
+) -> Result<Validated, Bip353Error> {
+    let rrset = rrset(records, name, record_type);
+    let Some(record_ttl) = rrset.iter().map(Record::ttl).min() else {
+        return Err(Bip353Error::DnssecError(format!("No {} records for {}", record_type, name)));
+    };
+
+    if !anchors.covers(name) {
+        return Ok(Validated {
+            records: rrset,
+            info: DnssecInfo::default(),
+            ttl: record_ttl,
+            reason: Some(format!("No trust anchor covers {}", name)),
+        });
+    }
+
+    let error = match verify_rrset(records, name, record_type, zone_keys, policy, now) {
+        Ok(verified) => {
+            return Ok(Validated {
+                ttl: verified.cache_ttl(now),
+                info: DnssecInfo { wildcard: verified.wildcard, ..DnssecInfo::signed(DnssecStatus::Secure, &verified.sig, zone_keys) },
+                records: verified.records,
+                reason: None,
+            });
+        }
+        Err(e) => e.to_string(),
+    };
+
+    // Signed or not, a zone its parent proves has no DS is Insecure
+    // (RFC 4035 5.2); its own signatures cannot be chained to an anchor
+    if let Some(proof) = prove_insecure(records, name, zone_keys, policy, now) {
+        return Ok(Validated {
+            info: DnssecInfo::signed(DnssecStatus::Insecure, &proof.sig, zone_keys),
+            ttl: proof.cache_ttl(now).min(record_ttl),
+            records: rrset,
+            reason: Some(format!("{} is below a delegation from {} without a DS record", name, proof.sig.signer_name())),
+        });
+    }
+
+    // Bogus answers are never cached
+    match rrsigs(records, name, record_type).next() {
+        Some(sig) => Ok(Validated { records: rrset, info: DnssecInfo::signed(DnssecStatus::Bogus, sig, zone_keys), ttl: 0, reason: Some(error) }),
+        None => Ok(Validated {
+            records: rrset,
+            info: DnssecInfo { status: DnssecStatus::Bogus, ..Default::default() },
+            ttl: 0,
+            reason: Some(format!("{} is not signed", name)),
+        }),
+    }
+}
+
+/// Zone whose keys are needed to validate the `name`/`record_type` answer:
+/// the answer's signer, or else the signer of an NSEC or NSEC3 that may
+/// prove it insecure
+pub(crate) fn signer(records: &[Record], name: &Name, record_type: RecordType) -> Option<Name> {
+    rrsigs(records, name, record_type)
+        .chain(denial_sigs(records, name))
+        .map(|sig| sig.signer_name().clone())
+        .next()
+}
+
+/// RRSIGs over NSEC or NSEC3 records, signed by a zone above `name`
+pub(crate) fn denial_sigs<'a>(records: &'a [Record], name: &'a Name) -> impl Iterator<Item = &'a SIG> {
+    records.iter()
+        .filter(|r| r.record_type() == RecordType::RRSIG)
+        .filter_map(as_sig)
+        .filter(|sig| matches!(sig.type_covered(), RecordType::NSEC | RecordType::NSEC3))
+        .filter(move |sig| sig.signer_name().zone_of(name) && sig.signer_name() != name)
+}
+
+/// Find a validated NSEC or NSEC3, signed by a zone above `name`, proving
+/// that `name` is in an unsigned zone below it
+fn prove_insecure(records: &[Record], name: &Name, zone_keys: &ZoneKeys, policy: &AlgorithmPolicy, now: u32) -> Option<VerifiedRrset> {
+    let mut zones: Vec<&Name> = denial_sigs(records, name).map(SIG::signer_name).collect();
+    zones.sort();
+    zones.dedup();
+    zones.into_iter().find_map(|zone| {
+        let (owner, record_type) = denial::Denials::new(records, zone, zone_keys, policy, now).insecure_delegation(name)?;
+        verify_exact(records, owner, record_type, zone_keys, policy, now).ok()
+    })
+}
+
+/// Find every zone whose DNSKEY RRset chains back to a trust anchor
+///
+/// Repeatedly walks DS -> DNSKEY links until nothing new is validated, so
+/// the records may appear in any order.
+pub(crate) fn validate_zone_keys(records: &[Record], anchors: &TrustAnchors, policy: &AlgorithmPolicy, now: u32) -> ZoneKeys {
+    let mut zone_keys = ZoneKeys::default();
+    let mut zone_ds: HashMap<Name, Vec<DS>> = HashMap::new();
+
+    let mut zones: Vec<Name> = records.iter()
+        .filter(|r| r.record_type() == RecordType::DNSKEY)
+        .map(|r| r.name().clone())
+        .collect();
+    zones.sort();
+    zones.dedup();
+
+    loop {
+        let mut progress = false;
+
+        // Validate DNSKEY RRsets signed by a key anchored directly or through a DS
+        for zone in &zones {
+            if zone_keys.keys.contains_key(zone) {
+                continue;
+            }
+            let dnskeys = rrset(records, zone, RecordType::DNSKEY);
+            let keys: Vec<DNSKEY> = dnskeys.iter().filter_map(as_dnskey).cloned().collect();
+            let trusted: Vec<DNSKEY> = keys.iter()
+                .filter(|key| {
+                    anchors.trusts(zone, key)
+                        || zone_ds.get(zone).is_some_and(|ds| ds_matches(ds.iter(), zone, key))
+                })
+                .cloned()
+                .collect();
+            if trusted.is_empty() {
+                continue;
+            }
+
+            let anchor = rrsigs(records, zone, RecordType::DNSKEY)
+                .find(|sig| sig.signer_name() == zone && verify_with_any(&trusted, sig, &dnskeys, zone, policy, now));
+            if let Some(sig) = anchor {
+                zone_keys.keys.insert(zone.clone(), keys);
+                zone_keys.anchors.insert(zone.clone(), (sig.algorithm(), sig.key_tag()));
+                progress = true;
+            }
+        }
+
+        // Validate DS RRsets for child zones signed by an already validated parent
+        for zone in &zones {
+            if zone_ds.contains_key(zone) || zone.is_root() {
+                continue;
+            }
+            let ds_records = rrset(records, zone, RecordType::DS);
+            if ds_records.is_empty() {
+                continue;
+            }
+            let signed = rrsigs(records, zone, RecordType::DS).any(|sig| {
+                let parent = sig.signer_name();
+                parent != zone && parent.zone_of(zone)
+                    && zone_keys.get(parent).is_some_and(|keys| verify_with_any(keys, sig, &ds_records, zone, policy, now))
+            });
+            if signed {
+                zone_ds.insert(zone.clone(), ds_records.iter().filter_map(as_ds).cloned().collect());
+                progress = true;
+            }
+        }
+
+        if !progress {
+            return zone_keys;
+        }
+    }
+}
+
+/// Check `sig` over `rrset` with any of `keys`, including the validity period
+/// and the algorithm policy
+fn verify_with_any(keys: &[DNSKEY], sig: &SIG, rrset: &[Record], name: &Name, policy: &AlgorithmPolicy, now: u32) -> bool {
+    if !policy.permits(sig.algorithm()) {
+        return false;
+    }
+    if now < sig.sig_inception() || now > sig.sig_expiration() {
+        return false;
+    }
+    if sig.num_labels() > name.num_labels() {
+        return false;
+    }
+
+    keys.iter().any(|key| {
+        key.zone_key()
+            && !key.revoke()
+            && key.algorithm() == sig.algorithm()
+            && key.calculate_key_tag().ok() == Some(sig.key_tag())
+            && key.verify_rrsig(name, DNSClass::IN, sig, rrset).is_ok()
+    })
+}
+
+/// Does `key` of `zone` match any of the DS records?
+fn ds_matches<'a>(mut ds: impl Iterator<Item = &'a DS>, zone: &Name, key: &DNSKEY) -> bool {
+    ds.any(|ds| {
+        ds.algorithm() == key.algorithm()
+            && key.calculate_key_tag().ok() == Some(ds.key_tag())
+            && key.to_digest(zone, ds.digest_type()).is_ok_and(|digest| digest.as_ref() == ds.digest())
+    })
+}
+
+/// All records of the given name and type, in canonical form and without
+/// duplicates (RFC 4034 6.2, 6.3)
+pub(crate) fn rrset(records: &[Record], name: &Name, record_type: RecordType) -> Vec<Record> {
+    let mut rrset: Vec<Record> = Vec::new();
+    for record in records.iter().filter(|r| r.record_type() == record_type && r.name() == name) {
+        let record = canonical(record);
+        if !rrset.iter().any(|seen| seen.data() == record.data()) {
+            rrset.push(record);
+        }
+    }
+    rrset
+}
+
+/// `trust-dns` leaves DNAME RDATA opaque, so lowercase its name here; the
+/// length octets are below 64 and unaffected
+fn canonical(record: &Record) -> Record {
+    let mut record = record.clone();
+    if let Some(RData::Unknown { code: 39, rdata }) = record.data() {
+        let rdata = NULL::with(rdata.anything().to_ascii_lowercase());
+        record.set_data(Some(RData::Unknown { code: 39, rdata }));
+    }
+    record
+}
+
+/// All RRSIGs at `name` covering `record_type`
+pub(crate) fn rrsigs<'a>(records: &'a [Record], name: &'a Name, record_type: RecordType) -> impl Iterator<Item = &'a SIG> {
+    records.iter()
+        .filter(move |r| r.record_type() == RecordType::RRSIG && r.name() == name)
+        .filter_map(as_sig)
+        .filter(move |sig| sig.type_covered() == record_type)
+}
+
+pub(crate) fn as_sig(record: &Record) -> Option<&SIG> {
+    match record.data() {
+        Some(RData::DNSSEC(DNSSECRData::SIG(sig))) => Some(sig),
+        _ => None,
+    }
+}
+
+fn as_dnskey(record: &Record) -> Option<&DNSKEY> {
+    match record.data() {
+        Some(RData::DNSSEC(DNSSECRData::DNSKEY(key))) => Some(key),
+        _ => None,
+    }
+}
+
+fn as_ds(record: &Record) -> Option<&DS> {
+    match record.data() {
+        Some(RData::DNSSEC(DNSSECRData::DS(ds))) => Some(ds),
+        _ => None,
+    }
+}
@@ -0,0 +1,260 @@
+//! DNS-over-HTTPS transport (RFC 8484)
+//!
+//! Speaks just enough HTTP/1.1 to POST or GET `application/dns-message`
+//! bodies. Responses are validated locally, so the DoH server only needs to
+//! be trusted for availability and, through TLS, for query privacy.
+
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::socks::{self, Socks5Config, Target};
+use crate::transport::{self, DnsTransport};
+use crate::Bip353Error;
+
+const DNS_MESSAGE: &str = "application/dns-message";
+
+/// Most bytes read from a DoH response, headers included
+///
+/// DNS messages are at most 64 KiB, so anything longer is not an answer.
+const MAX_RESPONSE: u64 = 64 * 1024;
+
+/// HTTP method used for DoH queries
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DohMethod {
+    /// `GET` with the query base64url-encoded in the `dns` parameter
+    Get,
+    /// `POST` with the query as the request body
+    #[default]
+    Post,
+}
+
+/// DNS-over-HTTPS settings
+#[derive(Debug, Clone)]
+pub struct DohConfig {
+    /// Endpoint URL, e.g. `https://dns.example/dns-query`
+    pub url: String,
+    /// Addresses of the DoH server, to avoid a plaintext lookup of its name
+    pub bootstrap_ips: Vec<IpAddr>,
+    pub method: DohMethod,
+    /// Connect through this SOCKS5 proxy (e.g. Tor)
+    ///
+    /// The host name is then resolved by the proxy and `bootstrap_ips` is
+    /// not used.
+    pub proxy: Option<Socks5Config>,
+    /// DER-encoded root certificates to trust instead of the Mozilla roots
+    pub root_certificates: Vec<Vec<u8>>,
+    pub timeout: Duration,
+}
+
+impl DohConfig {
+    /// Create a config for `url` with default settings
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            bootstrap_ips: Vec::new(),
+            method: DohMethod::default(),
+            proxy: None,
+            root_certificates: Vec::new(),
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+/// DNS-over-HTTPS transport
+pub struct DohTransport {
+    host: String,
+    port: u16,
+    path: String,
+    config: DohConfig,
+    connector: tokio_rustls::TlsConnector,
+}
+
+impl DohTransport {
+    /// Create a transport from `config`
+    pub fn new(config: DohConfig) -> Result<Self, Bip353Error> {
+        let url = url::Url::parse(&config.url)
+            .map_err(|e| Bip353Error::DnsError(format!("Invalid DoH URL: {}", e)))?;
+        if url.scheme() != "https" {
+            return Err(Bip353Error::DnsError("DoH URL must use https".into()));
+        }
+        let host = url.host_str()
+            .ok_or_else(|| Bip353Error::DnsError("DoH URL has no host".into()))?
+            .to_string();
+        let port = url.port_or_known_default().unwrap_or(443);
+        let path = match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string(),
+        };
+        transport::server_name(&host)?;
+        let connector = transport::tls_connector(&config.root_certificates, &[b"http/1.1"])?;
+
+        Ok(Self { host, port, path, config, connector })
+    }
+
+    async fn addresses(&self) -> Result<Vec<SocketAddr>, Bip353Error> {
+        if !self.config.bootstrap_ips.is_empty() {
+            return Ok(self.config.bootstrap_ips.iter().map(|ip| SocketAddr::new(*ip, self.port)).collect());
+        }
+        let host = self.host.trim_start_matches('[').trim_end_matches(']');
+        tokio::net::lookup_host((host, self.port))
+            .await
+            .map(|addrs| addrs.collect())
+            .map_err(|e| Bip353Error::TransportError(format!("Cannot resolve {}: {}", self.host, e)))
+    }
+
+    async fn connect(&self, lookup: u64) -> Result<TcpStream, Bip353Error> {
+        if let Some(proxy) = &self.config.proxy {
+            let host = self.host.trim_start_matches('[').trim_end_matches(']');
+            return socks::connect(proxy, Target::Host(host, self.port), lookup).await;
+        }
+
+        let mut last_error = None;
+        for addr in self.addresses().await? {
+            match TcpStream::connect(addr).await {
+                Ok(stream) => return Ok(stream),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(Bip353Error::TransportError(match last_error {
+            Some(e) => format!("Cannot connect to {}: {}", self.host, e),
+            None => format!("No addresses for {}", self.host),
+        }))
+    }
+
+    fn request(&self, query: &[u8]) -> Vec<u8> {
+        let host = match self.port {
+            443 => self.host.clone(),
+            port => format!("{}:{}", self.host, port),
+        };
+        match self.config.method {
+            DohMethod::Get => {
+                let separator = if self.path.contains('?') { '&' } else { '?' };
+                let dns = data_encoding::BASE64URL_NOPAD.encode(query);
+                format!(
+                    "GET {}{}dns={} HTTP/1.1\r\nHost: {}\r\nAccept: {}\r\nConnection: close\r\n\r\n",
+                    self.path, separator, dns, host, DNS_MESSAGE,
+                ).into_bytes()
+            }
+            DohMethod::Post => {
+                let mut request = format!(
+                    "POST {} HTTP/1.1\r\nHost: {}\r\nAccept: {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    self.path, host, DNS_MESSAGE, DNS_MESSAGE, query.len(),
+                ).into_bytes();
+                request.extend_from_slice(query);
+                request
+            }
+        }
+    }
+
+    async fn send(&self, query: &[u8], lookup: u64) -> Result<Vec<u8>, Bip353Error> {
+        let tcp = self.connect(lookup).await?;
+        let tls = self.connector
+            .connect(transport::server_name(&self.host)?, tcp)
+            .await
+            .map_err(|e| Bip353Error::TransportError(format!("TLS error: {}", e)))?;
+        http_exchange(tls, &self.request(query)).await
+    }
+}
+
+#[async_trait]
+impl DnsTransport for DohTransport {
+    async fn exchange(&self, query: &[u8]) -> Result<Vec<u8>, Bip353Error> {
+        self.exchange_isolated(query, rand::random()).await
+    }
+
+    async fn exchange_isolated(&self, query: &[u8], lookup: u64) -> Result<Vec<u8>, Bip353Error> {
+        if query.len() < 2 {
+            return Err(Bip353Error::DnsError("Query too short".into()));
+        }
+
+        // RFC 8484 4.1: use ID 0 so responses are cache friendly
+        let mut query = query.to_vec();
+        let id = [query[0], query[1]];
+        query[..2].copy_from_slice(&[0, 0]);
+
+        let mut response = tokio::time::timeout(self.config.timeout, self.send(&query, lookup))
+            .await
+            .map_err(|_| Bip353Error::TransportError("DoH request timed out".into()))??;
+        if response.len() < 2 {
+            return Err(Bip353Error::TransportError("DoH response too short".into()));
+        }
+        response[..2].copy_from_slice(&id);
+        Ok(response)
+    }
+}
+
+/// Send an HTTP/1.1 request and return the body of a 200 response
+async fn http_exchange<S>(mut stream: S, request: &[u8]) -> Result<Vec<u8>, Bip353Error>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let io_error = |e: std::io::Error| Bip353Error::TransportError(format!("DoH I/O error: {}", e));
+
+    stream.write_all(request).await.map_err(io_error)?;
+    stream.flush().await.map_err(io_error)?;
+
+    let mut raw = Vec::new();
+    if let Err(e) = (&mut stream).take(MAX_RESPONSE + 1).read_to_end(&mut raw).await {
+        // Servers commonly close without a TLS close_notify
+        if e.kind() != std::io::ErrorKind::UnexpectedEof || raw.is_empty() {
+            return Err(io_error(e));
+        }
+    }
+    if raw.len() as u64 > MAX_RESPONSE {
+        return Err(Bip353Error::TransportError("DoH response too large".into()));
+    }
+
+    let invalid = || Bip353Error::TransportError("Invalid HTTP response".into());
+    let header_end = raw.windows(4).position(|w| w == b"\r\n\r\n").ok_or_else(invalid)?;
+    let head = std::str::from_utf8(&raw[..header_end]).map_err(|_| invalid())?;
+    let body = &raw[header_end + 4..];
+
+    let mut lines = head.split("\r\n");
+    let status = lines.next().and_then(|line| line.split(' ').nth(1)).ok_or_else(invalid)?;
+    if status != "200" {
+        return Err(Bip353Error::TransportError(format!("DoH server returned HTTP {}", status)));
+    }
+
+    let mut content_length = None;
+    let mut chunked = false;
+    for line in lines {
+        let Some((name, value)) = line.split_once(':') else { continue };
+        let value = value.trim();
+        match name.trim().to_ascii_lowercase().as_str() {
+            "content-type" if !value.starts_with(DNS_MESSAGE) => {
+                return Err(Bip353Error::TransportError(format!("Unexpected DoH content type {}", value)));
+            }
+            "content-length" => content_length = Some(value.parse::<usize>().map_err(|_| invalid())?),
+            "transfer-encoding" => chunked = value.eq_ignore_ascii_case("chunked"),
+            _ => {}
+        }
+    }
+
+    if chunked {
+        decode_chunked(body).ok_or_else(invalid)
+    } else if let Some(len) = content_length {
+        body.get(..len).map(<[u8]>::to_vec).ok_or_else(invalid)
+    } else {
+        Ok(body.to_vec())
+    }
+}
+
+/// Decode a `Transfer-Encoding: chunked` body
+fn decode_chunked(mut body: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    loop {
+        let line_end = body.windows(2).position(|w| w == b"\r\n")?;
+        let size = std::str::from_utf8(&body[..line_end]).ok()?;
+        let size = usize::from_str_radix(size.split(';').next()?.trim(), 16).ok()?;
+        body = &body[line_end + 2..];
+        if size == 0 {
+            return Some(out);
+        }
+        out.extend_from_slice(body.get(..size)?);
+        body = body.get(size + 2..)?;
+    }
+}
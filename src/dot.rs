@@ -0,0 +1,118 @@
+//! DNS-over-TLS transport (RFC 7858)
+//!
+//! Keeps a few TLS connections open and reuses them across lookups,
+//! reconnecting transparently if the server has closed one in the meantime.
+
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tokio::net::TcpStream;
+use tokio_rustls::client::TlsStream;
+
+use crate::transport::{self, DnsTransport};
+use crate::Bip353Error;
+
+/// Most idle connections kept open for reuse
+const POOL_SIZE: usize = 4;
+
+/// DNS-over-TLS settings
+#[derive(Debug, Clone)]
+pub struct DotConfig {
+    /// Address of the DoT server, usually port 853
+    pub address: SocketAddr,
+    /// Name the server's certificate must be valid for, also sent as SNI
+    pub server_name: String,
+    /// DER-encoded root certificates to trust instead of the Mozilla roots
+    pub root_certificates: Vec<Vec<u8>>,
+    pub timeout: Duration,
+}
+
+impl DotConfig {
+    /// Create a config for the server at `address` named `server_name`
+    pub fn new(address: SocketAddr, server_name: impl Into<String>) -> Self {
+        Self {
+            address,
+            server_name: server_name.into(),
+            root_certificates: Vec::new(),
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+/// DNS-over-TLS transport
+///
+/// Each query takes an idle connection from a small pool, or opens a new
+/// one if all are busy, so concurrent lookups do not wait on each other.
+/// Lookups are not isolated: queries for different names may share a
+/// connection, which the server can link together.
+pub struct DotTransport {
+    config: DotConfig,
+    server_name: rustls::ServerName,
+    connector: tokio_rustls::TlsConnector,
+    idle: Mutex<Vec<TlsStream<TcpStream>>>,
+}
+
+impl DotTransport {
+    /// Create a transport from `config`
+    ///
+    /// No connection is made until the first query.
+    pub fn new(config: DotConfig) -> Result<Self, Bip353Error> {
+        let server_name = transport::server_name(&config.server_name)?;
+        let connector = transport::tls_connector(&config.root_certificates, &[b"dot"])?;
+
+        Ok(Self { config, server_name, connector, idle: Mutex::new(Vec::new()) })
+    }
+
+    async fn connect(&self) -> Result<TlsStream<TcpStream>, Bip353Error> {
+        let tcp = TcpStream::connect(self.config.address)
+            .await
+            .map_err(|e| Bip353Error::TransportError(format!("Cannot connect to {}: {}", self.config.address, e)))?;
+        self.connector
+            .connect(self.server_name.clone(), tcp)
+            .await
+            .map_err(|e| Bip353Error::TransportError(format!("TLS error: {}", e)))
+    }
+
+    async fn send(&self, query: &[u8]) -> Result<Vec<u8>, Bip353Error> {
+        // A pooled connection may have been closed by the server, so retry once on a fresh one
+        let pooled = self.idle.lock().unwrap().pop();
+        if let Some(mut stream) = pooled {
+            if let Ok(response) = transport::stream_exchange(&mut stream, query).await {
+                self.release(stream);
+                return Ok(response);
+            }
+        }
+
+        let mut stream = self.connect().await?;
+        let response = transport::stream_exchange(&mut stream, query)
+            .await
+            .map_err(|e| Bip353Error::TransportError(format!("DoT I/O error: {}", e)))?;
+        self.release(stream);
+        Ok(response)
+    }
+
+    /// Return a connection to the pool, or close it if the pool is full
+    fn release(&self, stream: TlsStream<TcpStream>) {
+        let mut idle = self.idle.lock().unwrap();
+        if idle.len() < POOL_SIZE {
+            idle.push(stream);
+        }
+    }
+}
+
+#[async_trait]
+impl DnsTransport for DotTransport {
+    async fn exchange(&self, query: &[u8]) -> Result<Vec<u8>, Bip353Error> {
+        tokio::time::timeout(self.config.timeout, self.send(query))
+            .await
+            .map_err(|_| Bip353Error::TransportError("DoT request timed out".into()))?
+    }
+
+    async fn exchange_isolated(&self, query: &[u8], lookup: u64) -> Result<Vec<u8>, Bip353Error> {
+        // Connections are pooled across lookups, see `DotTransport`
+        let _ = lookup;
+        self.exchange(query).await
+    }
+}
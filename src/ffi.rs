@@ -2,19 +2,55 @@
 //!
 //! These bindings would provide a simple C API for Bitcoin Core integration.
 
+// Every entry point takes raw pointers from C callers and checks them for NULL
+#![allow(clippy::not_unsafe_ptr_arg_deref)]
+
 use std::ffi::{c_char, CStr, CString};
 use std::ptr;
-use tokio::runtime::Runtime;
 
-use crate::{Bip353Error, PaymentInstruction, Resolver};
+use crate::{BlockingResolver, PaymentInstruction, Resolver};
 
 /// Opaque pointer for the resolver
-pub struct ResolverPtr(*mut Resolver);
+pub struct ResolverPtr(*mut BlockingResolver);
+
+/// Opaque handle for a payment instruction (`bip353_instruction*` in C)
+pub struct InstructionPtr {
+    instruction: PaymentInstruction,
+    // Parameters in a fixed order so they can be iterated by index
+    parameters: Vec<(String, String)>,
+}
+
+impl From<PaymentInstruction> for InstructionPtr {
+    fn from(instruction: PaymentInstruction) -> Self {
+        let parameters = instruction.sorted_parameters()
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        Self { instruction, parameters }
+    }
+}
+
+/// Copy an optional string into a newly allocated C string (NULL if absent)
+fn to_c_string(value: Option<&str>) -> *mut c_char {
+    match value.map(CString::new) {
+        Some(Ok(s)) => s.into_raw(),
+        _ => ptr::null_mut(),
+    }
+}
+
+/// Borrow the instruction behind a handle
+fn instruction_ref<'a>(ptr: *const InstructionPtr) -> Option<&'a InstructionPtr> {
+    if ptr.is_null() {
+        None
+    } else {
+        Some(unsafe { &*ptr })
+    }
+}
 
 /// Create a new resolver
 #[no_mangle]
 pub extern "C" fn bip353_resolver_create() -> *mut ResolverPtr {
-    match Resolver::new() {
+    match BlockingResolver::new() {
         Ok(resolver) => {
             let resolver_ptr = Box::new(resolver);
             let ptr = Box::new(ResolverPtr(Box::into_raw(resolver_ptr)));
@@ -37,6 +73,62 @@ pub extern "C" fn bip353_resolver_free(ptr: *mut ResolverPtr) {
     }
 }
 
+/// Parse a human-readable Bitcoin address into user and domain
+///
+/// Does not touch the network. On success `user_out` and `domain_out` must be
+/// freed with `bip353_string_free`.
+#[no_mangle]
+pub extern "C" fn bip353_parse_address(
+    address: *const c_char,
+    user_out: *mut *mut c_char,
+    domain_out: *mut *mut c_char,
+) -> bool {
+    if address.is_null() || user_out.is_null() || domain_out.is_null() {
+        return false;
+    }
+    
+    let address_str = match unsafe { CStr::from_ptr(address) }.to_str() {
+        Ok(s) => s,
+        Err(_) => return false,
+    };
+    
+    match Resolver::parse_address(address_str) {
+        Ok((user, domain)) => {
+            let (user, domain) = match (CString::new(user), CString::new(domain)) {
+                (Ok(user), Ok(domain)) => (user, domain),
+                _ => return false,
+            };
+            unsafe {
+                *user_out = user.into_raw();
+                *domain_out = domain.into_raw();
+            }
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+/// Parse a Bitcoin URI into an instruction handle
+///
+/// Does not touch the network. Returns NULL if the URI is invalid.
+/// The handle must be freed with `bip353_instruction_free`.
+#[no_mangle]
+pub extern "C" fn bip353_parse_uri(uri: *const c_char) -> *mut InstructionPtr {
+    if uri.is_null() {
+        return ptr::null_mut();
+    }
+    
+    let uri_str = match unsafe { CStr::from_ptr(uri) }.to_str() {
+        Ok(s) => s,
+        Err(_) => return ptr::null_mut(),
+    };
+    
+    match PaymentInstruction::from_uri(uri_str) {
+        Ok(instruction) => Box::into_raw(Box::new(InstructionPtr::from(instruction))),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
 /// Resolve a human-readable Bitcoin address
 #[no_mangle]
 pub extern "C" fn bip353_resolve(
@@ -58,14 +150,8 @@ pub extern "C" fn bip353_resolve(
         Err(_) => return false,
     };
     
-    // Create a new runtime for async resolution
-    let rt = match Runtime::new() {
-        Ok(rt) => rt,
-        Err(_) => return false,
-    };
-    
     // Resolve the address
-    match rt.block_on(resolver.resolve_address(address_str)) {
+    match resolver.resolve_address(address_str) {
         Ok(instruction) => {
             // Set the URI
             unsafe {
@@ -77,14 +163,7 @@ pub extern "C" fn bip353_resolve(
                 }
                 
                 // Set the type
-                let type_str = match instruction.payment_type {
-                    crate::PaymentType::OnChain => "on-chain",
-                    crate::PaymentType::Lightning => "lightning",
-                    crate::PaymentType::LightningOffer => "lightning-offer",
-                    crate::PaymentType::Unknown => "unknown",
-                };
-                
-                match CString::new(type_str) {
+                match CString::new(instruction.payment_type.as_str()) {
                     Ok(type_cstring) => {
                         *type_out = type_cstring.into_raw();
                     }
@@ -109,4 +188,114 @@ pub extern "C" fn bip353_string_free(ptr: *mut c_char) {
             let _ = CString::from_raw(ptr);
         }
     }
-}
\ No newline at end of file
+}
+
+/// Resolve a human-readable Bitcoin address into an instruction handle
+///
+/// Returns NULL on failure. The handle must be freed with `bip353_instruction_free`.
+#[no_mangle]
+pub extern "C" fn bip353_resolve_instruction(
+    ptr: *mut ResolverPtr,
+    address: *const c_char,
+) -> *mut InstructionPtr {
+    if ptr.is_null() || address.is_null() {
+        return ptr::null_mut();
+    }
+    
+    let resolver_ptr = unsafe { &*ptr };
+    let resolver = unsafe { &*resolver_ptr.0 };
+    
+    let address_str = match unsafe { CStr::from_ptr(address) }.to_str() {
+        Ok(s) => s,
+        Err(_) => return ptr::null_mut(),
+    };
+    
+    match resolver.resolve_address(address_str) {
+        Ok(instruction) => Box::into_raw(Box::new(InstructionPtr::from(instruction))),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Free an instruction handle
+#[no_mangle]
+pub extern "C" fn bip353_instruction_free(ptr: *mut InstructionPtr) {
+    if !ptr.is_null() {
+        unsafe {
+            let _ = Box::from_raw(ptr);
+        }
+    }
+}
+
+/// Get the full BIP-21 URI of an instruction
+#[no_mangle]
+pub extern "C" fn bip353_instruction_uri(ptr: *const InstructionPtr) -> *mut c_char {
+    to_c_string(instruction_ref(ptr).map(|i| i.instruction.uri.as_str()))
+}
+
+/// Get the payment type of an instruction
+#[no_mangle]
+pub extern "C" fn bip353_instruction_type(ptr: *const InstructionPtr) -> *mut c_char {
+    to_c_string(instruction_ref(ptr).map(|i| i.instruction.payment_type.as_str()))
+}
+
+/// Is the instruction reusable?
+#[no_mangle]
+pub extern "C" fn bip353_instruction_is_reusable(ptr: *const InstructionPtr) -> bool {
+    instruction_ref(ptr).is_some_and(|i| i.instruction.is_reusable)
+}
+
+/// Get the on-chain address (NULL if absent)
+#[no_mangle]
+pub extern "C" fn bip353_instruction_onchain_address(ptr: *const InstructionPtr) -> *mut c_char {
+    to_c_string(instruction_ref(ptr).and_then(|i| i.instruction.onchain_address()))
+}
+
+/// Get the BOLT11 Lightning invoice (NULL if absent)
+#[no_mangle]
+pub extern "C" fn bip353_instruction_lightning_invoice(ptr: *const InstructionPtr) -> *mut c_char {
+    to_c_string(instruction_ref(ptr).and_then(|i| i.instruction.lightning_invoice()))
+}
+
+/// Get the BOLT12 Lightning offer (NULL if absent)
+#[no_mangle]
+pub extern "C" fn bip353_instruction_lightning_offer(ptr: *const InstructionPtr) -> *mut c_char {
+    to_c_string(instruction_ref(ptr).and_then(|i| i.instruction.lightning_offer()))
+}
+
+/// Get the amount (NULL if absent)
+#[no_mangle]
+pub extern "C" fn bip353_instruction_amount(ptr: *const InstructionPtr) -> *mut c_char {
+    to_c_string(instruction_ref(ptr).and_then(|i| i.instruction.amount()))
+}
+
+/// Get the label (NULL if absent)
+#[no_mangle]
+pub extern "C" fn bip353_instruction_label(ptr: *const InstructionPtr) -> *mut c_char {
+    to_c_string(instruction_ref(ptr).and_then(|i| i.instruction.label()))
+}
+
+/// Get the message (NULL if absent)
+#[no_mangle]
+pub extern "C" fn bip353_instruction_message(ptr: *const InstructionPtr) -> *mut c_char {
+    to_c_string(instruction_ref(ptr).and_then(|i| i.instruction.message()))
+}
+
+/// Get the number of URI parameters
+#[no_mangle]
+pub extern "C" fn bip353_instruction_param_count(ptr: *const InstructionPtr) -> usize {
+    instruction_ref(ptr).map_or(0, |i| i.parameters.len())
+}
+
+/// Get the key of the parameter at `index` (NULL if out of range)
+///
+/// Parameters are ordered by key.
+#[no_mangle]
+pub extern "C" fn bip353_instruction_param_key(ptr: *const InstructionPtr, index: usize) -> *mut c_char {
+    to_c_string(instruction_ref(ptr).and_then(|i| i.parameters.get(index)).map(|(k, _)| k.as_str()))
+}
+
+/// Get the value of the parameter at `index` (NULL if out of range)
+#[no_mangle]
+pub extern "C" fn bip353_instruction_param_value(ptr: *const InstructionPtr, index: usize) -> *mut c_char {
+    to_c_string(instruction_ref(ptr).and_then(|i| i.parameters.get(index)).map(|(_, v)| v.as_str()))
+}
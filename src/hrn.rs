@@ -0,0 +1,75 @@
+//! Human-readable names (`₿user@domain`)
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{Bip353Error, Resolver};
+
+/// A `user@domain` name, normalized so equal names compare equal
+///
+/// DNS names are case-insensitive, so both parts are lowercased and the
+/// domain's trailing dot is dropped. With `serde`, it is the string
+/// `"₿user@domain"`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(into = "String", try_from = "String"))]
+pub struct HumanReadableName {
+    user: String,
+    domain: String,
+}
+
+impl HumanReadableName {
+    pub fn new(user: &str, domain: &str) -> Result<Self, Bip353Error> {
+        let user = user.trim().to_lowercase();
+        let domain = domain.trim().trim_end_matches('.').to_lowercase();
+        if user.is_empty() || domain.is_empty() {
+            return Err(Bip353Error::InvalidAddress("User and domain cannot be empty".into()));
+        }
+        if user.contains('@') || domain.contains('@') {
+            return Err(Bip353Error::InvalidAddress("User and domain cannot contain '@'".into()));
+        }
+        Ok(Self { user, domain })
+    }
+
+    /// Parse `user@domain`, with or without the `₿` prefix
+    pub fn parse(address: &str) -> Result<Self, Bip353Error> {
+        let (user, domain) = Resolver::parse_address(address)?;
+        Self::new(&user, &domain)
+    }
+
+    pub fn user(&self) -> &str {
+        &self.user
+    }
+
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
+}
+
+impl fmt::Display for HumanReadableName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "₿{}@{}", self.user, self.domain)
+    }
+}
+
+impl FromStr for HumanReadableName {
+    type Err = Bip353Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl From<HumanReadableName> for String {
+    fn from(name: HumanReadableName) -> Self {
+        name.to_string()
+    }
+}
+
+impl TryFrom<String> for HumanReadableName {
+    type Error = Bip353Error;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        Self::parse(&s)
+    }
+}
@@ -6,15 +6,118 @@
 use std::error::Error;
 use std::fmt;
 use std::collections::HashMap;
-use trust_dns_resolver::{TokioAsyncResolver, config::*};
+use std::sync::Arc;
+use futures_util::StreamExt;
+use trust_dns_proto::rr::rdata::TXT;
+use trust_dns_proto::rr::{Name, RData, Record, RecordType};
+#[cfg(feature = "tokio")]
+use trust_dns_proto::op::ResponseCode;
+#[cfg(feature = "tokio")]
+use trust_dns_resolver::error::ResolveErrorKind;
+#[cfg(feature = "tokio")]
+use trust_dns_resolver::config::*;
+#[cfg(feature = "tokio")]
+use system::SystemTransport;
+use transport::ChainMemo;
+
+mod address_book;
+mod alias;
+#[cfg(feature = "smol")]
+mod async_io;
+#[cfg(feature = "tokio")]
+mod blocking;
+mod cache;
+mod denial;
+mod dnssec;
+#[cfg(feature = "tokio")]
+mod doh;
+#[cfg(feature = "tokio")]
+mod dot;
+mod hrn;
+mod lint;
+mod onion;
+#[cfg(feature = "tokio")]
+mod publish;
+#[cfg(feature = "serde")]
+mod serialization;
+mod socks;
+#[cfg(feature = "storage")]
+mod storage;
+#[cfg(feature = "tokio")]
+mod system;
+mod tcp;
+mod transport;
+#[cfg(feature = "tokio")]
+mod tsig;
+
+pub use address_book::{AddressBook, KeyId, Pin, PinCheck};
+pub use alias::{Alias, AliasKind};
+#[cfg(feature = "smol")]
+pub use async_io::AsyncIoTransport;
+#[cfg(feature = "tokio")]
+pub use blocking::BlockingResolver;
+pub use cache::{CacheConfig, CacheStats};
+pub use dnssec::{AlgorithmPolicy, DnssecInfo, DnssecStatus, TrustAnchors};
+#[cfg(feature = "tokio")]
+pub use doh::{DohConfig, DohMethod, DohTransport};
+#[cfg(feature = "tokio")]
+pub use dot::{DotConfig, DotTransport};
+pub use hrn::HumanReadableName;
+pub use lint::{lint_records, Finding, LintCheck, LintConfig, Severity};
+pub use onion::{decode_dnssec_proof, encode_dnssec_query, OnionMessageTransport, DNSSEC_PROOF_TYPE, DNSSEC_QUERY_TYPE};
+#[cfg(feature = "tokio")]
+pub use publish::{Publisher, UpdateConfig};
+pub use socks::{Socks5Config, StreamIsolation};
+#[cfg(feature = "storage")]
+pub use storage::{Cipher, FileStorage, MemoryStorage, Storage};
+pub use tcp::TcpConfig;
+#[cfg(feature = "tokio")]
+pub use tcp::TcpTransport;
+pub use transport::DnsTransport;
+#[cfg(feature = "tokio")]
+pub use tsig::TsigKey;
+
+#[cfg(feature = "ffi")]
+pub mod ffi;
+
+#[cfg(feature = "python")]
+mod python;
+
+#[cfg(feature = "signer")]
+pub mod signer;
+
+#[cfg(feature = "test-support")]
+pub mod testing;
 
 /// Main error type for BIP-353 operations
-#[derive(Debug)]
+///
+/// With `serde`, errors are `{"kind": "not-found", "message": "..."}`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", content = "message", rename_all = "kebab-case"))]
 pub enum Bip353Error {
     DnsError(String),
     InvalidAddress(String),
     InvalidRecord(String),
     DnssecError(String),
+    /// The name has TXT records but none is a Bitcoin URI
+    NoRecord(String),
+    /// The name has more than one Bitcoin URI (BIP-353 requires exactly one)
+    MultipleRecords(String),
+    /// DNSSEC proves the name has no TXT records (NXDOMAIN or NODATA), so
+    /// it has no payment instruction
+    NotFound(String),
+    /// The server says the name has no TXT records, but the denial is
+    /// unsigned or could not be validated
+    UnverifiedNotFound(String),
+    /// No usable answer from the DNS server: connection failure, timeout,
+    /// SERVFAIL or a malformed response
+    TransportError(String),
+    /// A dynamic update was rejected, failed authentication, or did not
+    /// show up when resolved afterwards
+    UpdateError(String),
+    /// The cache or address book storage could not be read or written
+    StorageError(String),
 }
 
 impl fmt::Display for Bip353Error {
@@ -24,20 +127,52 @@ impl fmt::Display for Bip353Error {
             Bip353Error::InvalidAddress(msg) => write!(f, "Invalid address: {}", msg),
             Bip353Error::InvalidRecord(msg) => write!(f, "Invalid record: {}", msg),
             Bip353Error::DnssecError(msg) => write!(f, "DNSSEC error: {}", msg),
+            Bip353Error::NoRecord(msg) => write!(f, "No record: {}", msg),
+            Bip353Error::MultipleRecords(msg) => write!(f, "Multiple records: {}", msg),
+            Bip353Error::NotFound(msg) => write!(f, "Not found: {}", msg),
+            Bip353Error::UnverifiedNotFound(msg) => write!(f, "Unverified not found: {}", msg),
+            Bip353Error::TransportError(msg) => write!(f, "Transport error: {}", msg),
+            Bip353Error::UpdateError(msg) => write!(f, "Update error: {}", msg),
+            Bip353Error::StorageError(msg) => write!(f, "Storage error: {}", msg),
         }
     }
 }
 
 impl Error for Bip353Error {}
 
+#[cfg(feature = "tokio")]
 impl From<trust_dns_resolver::error::ResolveError> for Bip353Error {
     fn from(err: trust_dns_resolver::error::ResolveError) -> Self {
-        Bip353Error::DnsError(err.to_string())
+        match err.kind() {
+            ResolveErrorKind::NoRecordsFound { response_code: ResponseCode::NXDomain | ResponseCode::NoError, .. } => {
+                Bip353Error::UnverifiedNotFound(err.to_string())
+            }
+            ResolveErrorKind::NoRecordsFound { .. }
+            | ResolveErrorKind::Io(_)
+            | ResolveErrorKind::Proto(_)
+            | ResolveErrorKind::Timeout => Bip353Error::TransportError(err.to_string()),
+            _ => Bip353Error::DnsError(err.to_string()),
+        }
+    }
+}
+
+/// A failed lookup, with how long the failure may be cached if the name
+/// does not exist
+pub(crate) struct LookupError {
+    pub error: Bip353Error,
+    pub negative_ttl: Option<u32>,
+}
+
+impl From<Bip353Error> for LookupError {
+    fn from(error: Bip353Error) -> Self {
+        Self { error, negative_ttl: None }
     }
 }
 
 /// Payment instruction type
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
 pub enum PaymentType {
     OnChain,
     Lightning,
@@ -45,13 +180,39 @@ pub enum PaymentType {
     Unknown,
 }
 
+impl PaymentType {
+    /// Stable string name, as used by the C and Python bindings
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PaymentType::OnChain => "on-chain",
+            PaymentType::Lightning => "lightning",
+            PaymentType::LightningOffer => "lightning-offer",
+            PaymentType::Unknown => "unknown",
+        }
+    }
+}
+
 /// BIP-353 payment instruction
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PaymentInstruction {
     pub uri: String,
     pub payment_type: PaymentType,
     pub is_reusable: bool,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialization::sorted"))]
     pub parameters: HashMap<String, String>,
+    /// How the record was validated; `Indeterminate` if parsed from a URI
+    ///
+    /// If aliases were followed, the status is the weakest of all hops.
+    pub dnssec: DnssecInfo,
+    /// CNAME/DNAME hops from the queried name to the TXT records, in order;
+    /// only known when validating locally
+    pub aliases: Vec<Alias>,
+    /// RFC 9102 proof the instruction was validated from: the TXT, alias,
+    /// DNSKEY, DS, RRSIG and NSEC/NSEC3 records as uncompressed wire-format
+    /// records, which `verify_proof` accepts; `None` if parsed from a URI
+    #[cfg_attr(feature = "serde", serde(default, with = "serialization::option_hex"))]
+    pub proof: Option<Vec<u8>>,
 }
 
 impl PaymentInstruction {
@@ -84,8 +245,8 @@ impl PaymentInstruction {
         } else if parameters.contains_key("lno") {
             payment_type = PaymentType::LightningOffer;
             is_reusable = true;
-        } else if !uri[8..].contains('?') && uri.len() > 8 {
-            // Simple on-chain address
+        } else if uri_address(uri).is_some() {
+            // On-chain address, with or without query parameters
             payment_type = PaymentType::OnChain;
             is_reusable = true;
         }
@@ -95,28 +256,208 @@ impl PaymentInstruction {
             payment_type,
             is_reusable,
             parameters,
+            dnssec: DnssecInfo::default(),
+            aliases: Vec::new(),
+            proof: None,
         })
     }
+
+    /// Was the record expanded from a wildcard (e.g. `*.user._bitcoin-payment.example.com`)?
+    ///
+    /// Only known when validating locally.
+    pub fn is_wildcard(&self) -> bool {
+        self.dnssec.wildcard.is_some()
+    }
+
+    /// Get the on-chain address, if the URI has one
+    pub fn onchain_address(&self) -> Option<&str> {
+        uri_address(&self.uri)
+    }
+
+    /// Get the BOLT11 Lightning invoice, if present
+    pub fn lightning_invoice(&self) -> Option<&str> {
+        self.parameter("lightning")
+    }
+
+    /// Get the BOLT12 Lightning offer, if present
+    pub fn lightning_offer(&self) -> Option<&str> {
+        self.parameter("lno")
+    }
+
+    /// Get the requested amount, if present
+    pub fn amount(&self) -> Option<&str> {
+        self.parameter("amount")
+    }
+
+    /// Get the label, if present
+    pub fn label(&self) -> Option<&str> {
+        self.parameter("label")
+    }
+
+    /// Get the message, if present
+    pub fn message(&self) -> Option<&str> {
+        self.parameter("message")
+    }
+
+    /// Get a URI parameter by key
+    pub fn parameter(&self, key: &str) -> Option<&str> {
+        self.parameters.get(key).map(String::as_str)
+    }
+
+    /// Get all URI parameters sorted by key, for stable iteration
+    pub fn sorted_parameters(&self) -> Vec<(&str, &str)> {
+        let mut params: Vec<(&str, &str)> = self.parameters
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+        params.sort();
+        params
+    }
+}
+
+/// How DNS queries leave the process
+#[derive(Clone, Default)]
+pub enum TransportConfig {
+    /// The nameservers of `trust-dns-resolver`'s default configuration
+    /// over UDP, falling back to TCP, validated locally
+    ///
+    /// Needs the `tokio` feature; without it, `Resolver::from_config`
+    /// refuses this and a `Custom` or `OnionMessage` transport is required.
+    #[default]
+    System,
+    /// Plain DNS over TCP, validated locally
+    #[cfg(feature = "tokio")]
+    Tcp(TcpConfig),
+    /// DNS-over-HTTPS, validated locally
+    #[cfg(feature = "tokio")]
+    Https(DohConfig),
+    /// DNS-over-TLS, validated locally
+    #[cfg(feature = "tokio")]
+    Tls(DotConfig),
+    /// A caller-supplied transport, validated locally; works with any
+    /// async runtime the transport does
+    Custom(Arc<dyn DnsTransport>),
+    /// Proofs fetched from a Lightning node over onion messages (bLIP-32),
+    /// validated locally
+    OnionMessage(Arc<dyn OnionMessageTransport>),
+}
+
+/// Which DNSSEC outcomes `resolve` returns rather than refuses
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum ValidationMode {
+    /// Only `Secure` results
+    #[default]
+    RequireSecure,
+    /// Also `Insecure` and `Indeterminate` results; `Bogus` is still refused
+    AllowUnvalidated,
+    /// Every result, even `Bogus`; for diagnostics only
+    Permissive,
+}
+
+impl ValidationMode {
+    fn accepts(&self, status: DnssecStatus) -> bool {
+        match self {
+            ValidationMode::RequireSecure => status == DnssecStatus::Secure,
+            ValidationMode::AllowUnvalidated => status != DnssecStatus::Bogus,
+            ValidationMode::Permissive => true,
+        }
+    }
+}
+
+/// Resolver configuration
+#[derive(Clone, Default)]
+pub struct Bip353Config {
+    pub transport: TransportConfig,
+    /// Anchors DNSSEC validation starts from
+    pub trust_anchors: TrustAnchors,
+    /// Signing algorithms DNSSEC validation accepts
+    pub algorithm_policy: AlgorithmPolicy,
+    /// Cache resolved instructions; `None` queries the network every time
+    pub cache: Option<CacheConfig>,
+    pub validation: ValidationMode,
+}
+
+/// Where the resolver gets the DNSSEC records it validates from
+enum ProofSource {
+    Dns(Arc<dyn DnsTransport>),
+    Onion(Arc<dyn OnionMessageTransport>),
 }
 
 /// BIP-353 resolver
 pub struct Resolver {
-    resolver: TokioAsyncResolver,
+    source: ProofSource,
+    trust_anchors: TrustAnchors,
+    policy: AlgorithmPolicy,
+    cache: Option<cache::Cache>,
+    validation: ValidationMode,
 }
 
 impl Resolver {
     /// Create a new resolver
+    ///
+    /// Queries `trust-dns-resolver`'s default nameservers and validates
+    /// DNSSEC locally against the root trust anchors.
+    #[cfg(feature = "tokio")]
     pub fn new() -> Result<Self, Bip353Error> {
-        // Create a new resolver with DNSSEC validation
-        let mut opts = ResolverOpts::default();
-        opts.validate = true; // Enable DNSSEC validation
-        
-        let resolver = TokioAsyncResolver::tokio(
-            ResolverConfig::default(),
-            opts,
-        )?;
+        Self::from_config(Bip353Config::default())
+    }
+    
+    /// Create a resolver with a custom DNS configuration
+    ///
+    /// Queries the nameservers of `config` and validates DNSSEC locally.
+    /// Without `opts.validate`, every result is returned whatever its
+    /// status (`ValidationMode::Permissive`) rather than only `Secure` ones.
+    #[cfg(feature = "tokio")]
+    pub fn with_config(config: ResolverConfig, opts: ResolverOpts) -> Result<Self, Bip353Error> {
+        let validation = if opts.validate { ValidationMode::RequireSecure } else { ValidationMode::Permissive };
+        Ok(Self::system(&config, &opts, Bip353Config { validation, ..Default::default() }))
+    }
+
+    /// A resolver querying the nameservers of `config`, otherwise set up
+    /// from `bip353`
+    #[cfg(feature = "tokio")]
+    pub(crate) fn system(config: &ResolverConfig, opts: &ResolverOpts, bip353: Bip353Config) -> Self {
+        Self {
+            source: ProofSource::Dns(Arc::new(SystemTransport::new(config, opts))),
+            trust_anchors: bip353.trust_anchors,
+            policy: bip353.algorithm_policy,
+            cache: bip353.cache.map(cache::Cache::new),
+            validation: bip353.validation,
+        }
+    }
+
+    /// Create a resolver from a `Bip353Config`
+    pub fn from_config(config: Bip353Config) -> Result<Self, Bip353Error> {
+        let source = match &config.transport {
+            #[cfg(feature = "tokio")]
+            TransportConfig::System => {
+                return Ok(Self::system(&ResolverConfig::default(), &ResolverOpts::default(), config));
+            }
+            #[cfg(not(feature = "tokio"))]
+            TransportConfig::System => {
+                return Err(Bip353Error::DnsError(
+                    "The system resolver needs the `tokio` feature; use a custom or onion message transport".into(),
+                ));
+            }
+            #[cfg(feature = "tokio")]
+            TransportConfig::Tcp(tcp) => ProofSource::Dns(Arc::new(TcpTransport::new(tcp.clone()))),
+            #[cfg(feature = "tokio")]
+            TransportConfig::Https(doh) => ProofSource::Dns(Arc::new(DohTransport::new(doh.clone())?)),
+            #[cfg(feature = "tokio")]
+            TransportConfig::Tls(dot) => ProofSource::Dns(Arc::new(DotTransport::new(dot.clone())?)),
+            TransportConfig::Custom(transport) => ProofSource::Dns(transport.clone()),
+            TransportConfig::OnionMessage(transport) => ProofSource::Onion(transport.clone()),
+        };
         
-        Ok(Self { resolver })
+        Ok(Self {
+            source,
+            trust_anchors: config.trust_anchors,
+            policy: config.algorithm_policy,
+            cache: config.cache.map(cache::Cache::new),
+            validation: config.validation,
+        })
     }
     
     /// Parse a human-readable Bitcoin address
@@ -144,39 +485,374 @@ impl Resolver {
     
     /// Resolve a human-readable Bitcoin address
     pub async fn resolve(&self, user: &str, domain: &str) -> Result<PaymentInstruction, Bip353Error> {
-        // Construct DNS name
-        let dns_name = format!("{}.user._bitcoin-payment.{}", user, domain);
-        
-        // Query TXT records - with opts.validate=true, this will fail if DNSSEC validation fails
-        let response = self.resolver.txt_lookup(&dns_name).await?;
-        
-        // Extract and concatenate TXT record strings
-        let mut bitcoin_uris = Vec::new();
-        
-        for txt in response.iter() {
-            let txt_data: Vec<String> = txt.txt_data()
-                .iter()
-                .map(|bytes| String::from_utf8_lossy(bytes).into_owned())
-                .collect();
-            
-            let concatenated = txt_data.join("");
-            
-            if concatenated.to_lowercase().starts_with("bitcoin:") {
-                bitcoin_uris.push(concatenated);
+        self.resolve_with(user, domain, &ChainMemo::default()).await
+    }
+
+    async fn resolve_with(&self, user: &str, domain: &str, memo: &ChainMemo) -> Result<PaymentInstruction, Bip353Error> {
+        let Some(cache) = &self.cache else {
+            return self.lookup(user, domain, memo).await.map(|(instruction, _)| instruction).map_err(|e| e.error);
+        };
+
+        let key = cache::Cache::key(user, domain);
+        if let Some(result) = cache.get(&key) {
+            return result;
+        }
+        match self.lookup(user, domain, memo).await {
+            Ok((instruction, ttl)) => {
+                cache.insert(key, instruction.clone(), ttl);
+                Ok(instruction)
+            }
+            Err(LookupError { error, negative_ttl }) => {
+                if let Some(ttl) = negative_ttl {
+                    cache.insert_absent(key, &error, ttl);
+                }
+                Err(error)
             }
         }
-        
-        // BIP-353 requires exactly one Bitcoin URI
-        match bitcoin_uris.len() {
-            0 => Err(Bip353Error::InvalidRecord("No Bitcoin URI found".into())),
-            1 => PaymentInstruction::from_uri(&bitcoin_uris[0]),
-            _ => Err(Bip353Error::InvalidRecord("Multiple Bitcoin URIs found".into())),
+    }
+
+    /// Resolve without the cache, returning the instruction and how many
+    /// seconds it may be cached for
+    async fn lookup(&self, user: &str, domain: &str, memo: &ChainMemo) -> Result<(PaymentInstruction, u32), LookupError> {
+        let (records, ttl, dnssec, aliases, proof) = self.fetch(user, domain, memo, self.validation).await?;
+        Ok((instruction(&records, dnssec, aliases, proof)?, ttl))
+    }
+
+    /// Fetch the TXT records of `user@domain`, refusing a DNSSEC status
+    /// `validation` does not accept
+    async fn fetch(
+        &self,
+        user: &str,
+        domain: &str,
+        memo: &ChainMemo,
+        validation: ValidationMode,
+    ) -> Result<Answer, LookupError> {
+        let name = payment_name(user, domain)?;
+        self.lookup_validated(name, memo, validation).await
+    }
+
+    /// Fetch and validate the TXT records at `name`, following and
+    /// validating any CNAME/DNAME hops on the way
+    ///
+    /// The returned status is the weakest of all hops.
+    async fn lookup_validated(&self, mut name: Name, memo: &ChainMemo, validation: ValidationMode) -> Result<Answer, LookupError> {
+        let lookup = rand::random();
+        let mut hops = Hops::default();
+
+        loop {
+            let records = match &self.source {
+                ProofSource::Dns(transport) => {
+                    transport::fetch_proof(transport.as_ref(), &name, RecordType::TXT, lookup, memo).await?
+                }
+                ProofSource::Onion(transport) => onion::fetch_proof(transport.as_ref(), &name).await?,
+            };
+            if let Some(answer) = hops.validate(&records, &mut name, &self.trust_anchors, &self.policy, memo, validation)? {
+                return Ok(answer);
+            }
+        }
+    }
+
+    /// Why `name` has no TXT records in `records`: a validated NSEC/NSEC3
+    /// denial makes it proven (and cacheable), anything else is unverified
+    fn absence(
+        records: &[Record],
+        name: &Name,
+        trust_anchors: &TrustAnchors,
+        policy: &AlgorithmPolicy,
+        memo: &ChainMemo,
+    ) -> LookupError {
+        let now = dnssec::now();
+        if let Some(zone) = denial::zone(records, name) {
+            let zone_keys = memo.zone_keys(&zone, records, trust_anchors, policy, now);
+            if denial::Denials::new(records, &zone, &zone_keys, policy, now).no_rrset(name, RecordType::TXT) {
+                return LookupError {
+                    error: Bip353Error::NotFound(format!("{} has no TXT records", name)),
+                    negative_ttl: denial::negative_ttl(records, &zone),
+                };
+            }
+        }
+        Bip353Error::UnverifiedNotFound(format!("{} has no TXT records, but the denial could not be validated", name)).into()
+    }
+
+    /// Refuse a result whose DNSSEC status `validation` does not accept
+    fn check_status(validation: ValidationMode, name: &str, dnssec: &DnssecInfo, reason: &str) -> Result<(), Bip353Error> {
+        if validation.accepts(dnssec.status) {
+            Ok(())
+        } else {
+            Err(Bip353Error::DnssecError(format!("{} is {}: {}", name, dnssec.status, reason)))
         }
     }
     
+    /// Resolve `user@domain` and report problems with its records
+    ///
+    /// Records that do not validate are linted too, with a finding saying
+    /// so. Fails only if the name cannot be looked up at all.
+    pub async fn lint(&self, user: &str, domain: &str, config: &LintConfig) -> Result<Vec<Finding>, Bip353Error> {
+        match self.fetch(user, domain, &ChainMemo::default(), ValidationMode::Permissive).await {
+            Ok((records, _, dnssec, _, _)) => Ok(lint::lint_answer(&records, &dnssec, config)),
+            Err(LookupError { error: error @ (Bip353Error::TransportError(_) | Bip353Error::InvalidAddress(_)), .. }) => Err(error),
+            Err(LookupError { error, .. }) => Ok(vec![Finding::unresolvable(error.to_string())]),
+        }
+    }
+
+    /// Resolve `name` and compare the result with its pin in `book`
+    ///
+    /// The first result for a name is pinned. Results this resolver's
+    /// `ValidationMode` refuses are errors as usual and never pinned, and
+    /// so is a result the book's storage fails to save.
+    pub async fn resolve_pinned(&self, name: &HumanReadableName, book: &AddressBook) -> Result<PinCheck, Bip353Error> {
+        let instruction = self.resolve(name.user(), name.domain()).await?;
+        book.check(name, instruction)
+    }
+
     /// Resolve a human-readable Bitcoin address string
     pub async fn resolve_address(&self, address: &str) -> Result<PaymentInstruction, Bip353Error> {
         let (user, domain) = Self::parse_address(address)?;
         self.resolve(&user, &domain).await
     }
+
+    /// Resolve many human-readable Bitcoin address strings
+    ///
+    /// Results are returned in input order. Identical names are resolved
+    /// once, at most `concurrency` lookups run at a time, and names under
+    /// the same domain share the DNSKEY/DS fetching and validation work.
+    /// `progress` is called with (resolved, total) after each unique name.
+    pub async fn resolve_many<S: AsRef<str>>(
+        &self,
+        addresses: &[S],
+        concurrency: usize,
+        mut progress: impl FnMut(usize, usize),
+    ) -> Vec<Result<PaymentInstruction, Bip353Error>> {
+        let mut unique: Vec<Result<(String, String), Bip353Error>> = Vec::new();
+        let mut index: HashMap<String, usize> = HashMap::new();
+        let slots: Vec<usize> = addresses.iter()
+            .map(|address| {
+                let parsed = Self::parse_address(address.as_ref());
+                let key = match &parsed {
+                    Ok((user, domain)) => cache::Cache::key(user, domain),
+                    Err(_) => address.as_ref().to_string(),
+                };
+                *index.entry(key).or_insert_with(|| {
+                    unique.push(parsed);
+                    unique.len() - 1
+                })
+            })
+            .collect();
+
+        let memo = ChainMemo::default();
+        let total = unique.len();
+        let mut results: Vec<Option<Result<PaymentInstruction, Bip353Error>>> = vec![None; total];
+        let mut lookups = futures_util::stream::iter(unique.into_iter().enumerate())
+            .map(|(i, parsed)| {
+                let memo = &memo;
+                async move {
+                    let result = match parsed {
+                        Ok((user, domain)) => self.resolve_with(&user, &domain, memo).await,
+                        Err(e) => Err(e),
+                    };
+                    (i, result)
+                }
+            })
+            .buffer_unordered(concurrency.max(1));
+
+        let mut done = 0;
+        while let Some((i, result)) = lookups.next().await {
+            results[i] = Some(result);
+            done += 1;
+            progress(done, total);
+        }
+
+        slots.into_iter()
+            .map(|i| results[i].clone().expect("every unique name was resolved"))
+            .collect()
+    }
+
+    /// Cache hit/miss counters, or `None` if caching is disabled
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        self.cache.as_ref().map(cache::Cache::stats)
+    }
+
+    /// Forget any cached answer for `user`@`domain`
+    pub fn flush(&self, user: &str, domain: &str) {
+        if let Some(cache) = &self.cache {
+            cache.flush(&cache::Cache::key(user, domain));
+        }
+    }
+
+    /// Forget all cached answers
+    pub fn flush_all(&self) {
+        if let Some(cache) = &self.cache {
+            cache.clear();
+        }
+    }
+}
+
+/// Validated TXT records, how many seconds they may be cached for, their
+/// DNSSEC status, the aliases followed to reach them and the RFC 9102 proof
+/// of all hops
+type Answer = (Vec<Record>, u32, DnssecInfo, Vec<Alias>, Vec<u8>);
+
+/// The CNAME/DNAME hops validated so far on the way to a name's TXT records
+struct Hops {
+    aliases: Vec<Alias>,
+    ttl: u32,
+    status: DnssecStatus,
+    /// Every record the hops were validated from, without duplicates
+    proof: Vec<Record>,
+}
+
+impl Default for Hops {
+    fn default() -> Self {
+        Self { aliases: Vec::new(), ttl: u32::MAX, status: DnssecStatus::Secure, proof: Vec::new() }
+    }
+}
+
+impl Hops {
+    /// Validate the answer for `name` in `records`
+    ///
+    /// Returns the answer once `name` holds the TXT records; if it is an
+    /// alias, records the hop and moves `name` to its target instead. The
+    /// returned status is the weakest of all hops.
+    fn validate(
+        &mut self,
+        records: &[Record],
+        name: &mut Name,
+        trust_anchors: &TrustAnchors,
+        policy: &AlgorithmPolicy,
+        memo: &ChainMemo,
+        validation: ValidationMode,
+    ) -> Result<Option<Answer>, LookupError> {
+        let Some(step) = alias::step(records, name, RecordType::TXT) else {
+            return Err(Resolver::absence(records, name, trust_anchors, policy, memo));
+        };
+
+        let now = dnssec::now();
+        let zone_keys = match dnssec::signer(records, &step.owner, step.record_type) {
+            Some(signer) => memo.zone_keys(&signer, records, trust_anchors, policy, now),
+            None => Arc::default(),
+        };
+        let validated = dnssec::validate(records, &step.owner, step.record_type, &zone_keys, trust_anchors, policy, now)?;
+        Resolver::check_status(validation, &step.owner.to_string(), &validated.info, validated.reason.as_deref().unwrap_or_default())?;
+        self.ttl = self.ttl.min(validated.ttl);
+        self.status = self.status.weaker(validated.info.status);
+        for record in records {
+            if !self.proof.contains(record) {
+                self.proof.push(record.clone());
+            }
+        }
+
+        let Some(target) = step.target.clone() else {
+            let dnssec = DnssecInfo { status: self.status, ..validated.info };
+            let proof = onion::encode_proof(&self.proof)?;
+            return Ok(Some((validated.records, self.ttl, dnssec, std::mem::take(&mut self.aliases), proof)));
+        };
+
+        if self.aliases.len() == alias::MAX_ALIAS_HOPS {
+            return Err(Bip353Error::DnsError(format!("More than {} aliases", alias::MAX_ALIAS_HOPS)).into());
+        }
+        if &target == name || self.aliases.iter().any(|alias| alias.name == target) {
+            return Err(Bip353Error::DnsError(format!("Alias loop at {}", target)).into());
+        }
+        self.aliases.push(Alias {
+            kind: step.kind(),
+            owner: step.owner,
+            name: std::mem::replace(name, target.clone()),
+            target,
+            dnssec: validated.info,
+        });
+        Ok(None)
+    }
+}
+
+/// Verify an RFC 9102 proof of `user@domain`'s payment instruction
+///
+/// `proof` is a concatenation of wire-format records, e.g. from a bLIP-32
+/// `dnssec_proof` message, and must include the records of any alias
+/// targets. Only a `Secure` result is accepted. This needs neither an
+/// async runtime nor network access.
+pub fn verify_proof(
+    user: &str,
+    domain: &str,
+    proof: &[u8],
+    trust_anchors: &TrustAnchors,
+    policy: &AlgorithmPolicy,
+) -> Result<PaymentInstruction, Bip353Error> {
+    let records = onion::parse_proof(proof)?;
+    let mut name = payment_name(user, domain)?;
+    let memo = ChainMemo::default();
+    let mut hops = Hops::default();
+    loop {
+        let validated = hops.validate(&records, &mut name, trust_anchors, policy, &memo, ValidationMode::RequireSecure);
+        if let Some((records, _, dnssec, aliases, proof)) = validated.map_err(|e| e.error)? {
+            return instruction(&records, dnssec, aliases, proof);
+        }
+    }
+}
+
+/// The TXT record publishing `uri` for `user@domain`, split into
+/// 255-byte character-strings as BIP-353 requires for long URIs
+pub fn payment_record(user: &str, domain: &str, uri: &str, ttl: u32) -> Result<Record, Bip353Error> {
+    PaymentInstruction::from_uri(uri)?;
+    let txt = TXT::from_bytes(uri.as_bytes().chunks(255).collect());
+    Ok(Record::from_rdata(payment_name(user, domain)?, ttl, RData::TXT(txt)))
+}
+
+/// The name holding `user@domain`'s payment instruction
+fn payment_name(user: &str, domain: &str) -> Result<Name, Bip353Error> {
+    let mut name = Name::from_utf8(format!("{}.user._bitcoin-payment.{}", user, domain))
+        .map_err(|e| Bip353Error::InvalidAddress(e.to_string()))?;
+    name.set_fqdn(true);
+    Ok(name)
+}
+
+/// The instruction published in a name's validated TXT records
+fn instruction(
+    records: &[Record],
+    dnssec: DnssecInfo,
+    aliases: Vec<Alias>,
+    proof: Vec<u8>,
+) -> Result<PaymentInstruction, Bip353Error> {
+    let txt_strings = records.iter()
+        .filter_map(|record| match record.data() {
+            Some(RData::TXT(txt)) => Some(concat_txt(txt.txt_data())),
+            _ => None,
+        })
+        .collect();
+
+    let mut instruction = select_bitcoin_uri(txt_strings)?;
+    instruction.dnssec = dnssec;
+    instruction.aliases = aliases;
+    instruction.proof = Some(proof);
+    Ok(instruction)
+}
+
+/// Concatenate the character-strings of a TXT record
+fn concat_txt(txt_data: &[Box<[u8]>]) -> String {
+    txt_data
+        .iter()
+        .map(|bytes| String::from_utf8_lossy(bytes).into_owned())
+        .collect()
+}
+
+/// Pick the single Bitcoin URI out of a name's TXT records
+fn select_bitcoin_uri(txt_strings: Vec<String>) -> Result<PaymentInstruction, Bip353Error> {
+    let bitcoin_uris: Vec<String> = txt_strings
+        .into_iter()
+        .filter(|txt| txt.to_lowercase().starts_with("bitcoin:"))
+        .collect();
+
+    // BIP-353 requires exactly one Bitcoin URI
+    match bitcoin_uris.len() {
+        0 => Err(Bip353Error::NoRecord("No Bitcoin URI found".into())),
+        1 => PaymentInstruction::from_uri(&bitcoin_uris[0]),
+        _ => Err(Bip353Error::MultipleRecords("Multiple Bitcoin URIs found".into())),
+    }
+}
+
+/// The on-chain address of a `bitcoin:` URI, before any `?` parameters
+///
+/// `None` if `uri` does not start with `bitcoin:`, in any case.
+fn uri_address(uri: &str) -> Option<&str> {
+    let scheme = uri.get(..8).filter(|scheme| scheme.eq_ignore_ascii_case("bitcoin:"))?;
+    uri[scheme.len()..].split('?').next().filter(|address| !address.is_empty())
 }
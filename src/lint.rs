@@ -0,0 +1,403 @@
+//! Record linting for domain operators
+//!
+//! `Resolver::lint` resolves a name and `lint_records` checks records from
+//! a zone snippet; both report each problem as a `Finding`.
+
+use std::fmt;
+use std::time::Duration;
+
+use trust_dns_proto::rr::dnssec::rdata::DNSSECRData;
+use trust_dns_proto::rr::{Name, RData, Record, RecordType};
+
+use crate::dnssec::{self, DnssecInfo, DnssecStatus};
+use crate::{concat_txt, PaymentInstruction};
+
+/// bech32 alphabet (BIP-173)
+const BECH32_CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+/// BOLT11 signatures are 520 bits, i.e. 104 5-bit groups
+const BOLT11_SIGNATURE_LEN: usize = 104;
+
+/// BOLT11 expiry when the invoice has no `x` field, in seconds
+const BOLT11_DEFAULT_EXPIRY: u64 = 3600;
+
+/// How bad a finding is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Severity {
+    /// Worth knowing, nothing to fix
+    Info,
+    /// Works today, but some clients may fail or it will break later
+    Warning,
+    /// Clients will refuse to pay, or pay somewhere unintended
+    Error,
+}
+
+impl Severity {
+    /// Stable string name
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// What a finding is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum LintCheck {
+    /// The name did not resolve, or its records did not validate
+    Unresolvable,
+    /// No TXT record is a `bitcoin:` URI
+    NoBitcoinRecord,
+    /// More than one `bitcoin:` URI, which BIP-353 forbids
+    MultipleBitcoinRecords,
+    /// TXT records that are not `bitcoin:` URIs
+    NonBitcoinTxt,
+    /// Upper case in the scheme or parameter names, which clients that
+    /// only match lower case miss
+    NotLowercase,
+    /// A `bitcoin:` URI that does not parse
+    InvalidUri,
+    /// A `req-` parameter, which clients that do not understand it must
+    /// refuse to pay (BIP-21)
+    UnknownRequiredParameter,
+    /// A BOLT11 invoice or BOLT12 offer that does not decode
+    InvalidInvoice,
+    /// A BOLT11 invoice, which is single-use, in a record every payer sees
+    SingleUseInvoice,
+    /// The RRSIG over the records expires soon or has expired
+    ExpiringSignature,
+    /// Records not signed with DNSSEC
+    Unsigned,
+    /// The zone is provably insecure, signed or not: its parent has no DS for it
+    MissingDs,
+    /// Signatures are present but do not validate
+    Bogus,
+    /// TTL so short that every payment needs a fresh lookup
+    ShortTtl,
+    /// TTL so long that changes take too long to reach payers
+    LongTtl,
+    /// The answer was expanded from a wildcard, so every user resolves
+    Wildcard,
+}
+
+impl LintCheck {
+    /// Stable string name, e.g. for filtering findings in a UI
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LintCheck::Unresolvable => "unresolvable",
+            LintCheck::NoBitcoinRecord => "no-bitcoin-record",
+            LintCheck::MultipleBitcoinRecords => "multiple-bitcoin-records",
+            LintCheck::NonBitcoinTxt => "non-bitcoin-txt",
+            LintCheck::NotLowercase => "not-lowercase",
+            LintCheck::InvalidUri => "invalid-uri",
+            LintCheck::UnknownRequiredParameter => "unknown-required-parameter",
+            LintCheck::InvalidInvoice => "invalid-invoice",
+            LintCheck::SingleUseInvoice => "single-use-invoice",
+            LintCheck::ExpiringSignature => "expiring-signature",
+            LintCheck::Unsigned => "unsigned",
+            LintCheck::MissingDs => "missing-ds",
+            LintCheck::Bogus => "bogus",
+            LintCheck::ShortTtl => "short-ttl",
+            LintCheck::LongTtl => "long-ttl",
+            LintCheck::Wildcard => "wildcard",
+        }
+    }
+}
+
+impl fmt::Display for LintCheck {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// One problem found by the linter
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Finding {
+    pub check: LintCheck,
+    pub severity: Severity,
+    /// Human-readable details
+    pub message: String,
+}
+
+impl Finding {
+    fn new(check: LintCheck, severity: Severity, message: impl Into<String>) -> Self {
+        Self { check, severity, message: message.into() }
+    }
+
+    pub(crate) fn unresolvable(message: impl Into<String>) -> Self {
+        Self::new(LintCheck::Unresolvable, Severity::Error, message)
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} [{}]: {}", self.severity, self.check, self.message)
+    }
+}
+
+/// Linter thresholds
+#[derive(Debug, Clone)]
+pub struct LintConfig {
+    /// TTLs below this are reported
+    pub min_ttl: Duration,
+    /// TTLs above this are reported
+    pub max_ttl: Duration,
+    /// Signatures expiring within this are reported
+    pub signature_expiry_warning: Duration,
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        Self {
+            min_ttl: Duration::from_secs(300),
+            max_ttl: Duration::from_secs(7 * 86400),
+            signature_expiry_warning: Duration::from_secs(7 * 86400),
+        }
+    }
+}
+
+/// Lint the records at `name` in a zone snippet, e.g. `SignedZone::records`
+///
+/// Only the TXT records at `name` and their RRSIGs are looked at. The
+/// signatures are not verified and the parent's DS cannot be checked; use
+/// `Resolver::lint` for that.
+pub fn lint_records(name: &Name, records: &[Record], config: &LintConfig) -> Vec<Finding> {
+    let txt: Vec<Record> = records.iter()
+        .filter(|r| r.name() == name && r.record_type() == RecordType::TXT)
+        .cloned()
+        .collect();
+    let expiration = records.iter()
+        .filter(|r| r.name() == name)
+        .filter_map(|r| match r.data() {
+            Some(RData::DNSSEC(DNSSECRData::SIG(sig))) if sig.type_covered() == RecordType::TXT => Some(sig.sig_expiration()),
+            _ => None,
+        })
+        .max();
+
+    let mut findings = lint_txt(&txt, config);
+    match expiration {
+        Some(expiration) => findings.extend(lint_expiration(expiration, config)),
+        None if !txt.is_empty() => {
+            findings.push(Finding::new(LintCheck::Unsigned, Severity::Error, format!("No RRSIG over the TXT records at {}", name)));
+        }
+        None => {}
+    }
+    findings
+}
+
+/// Lint a resolved answer: its TXT records and how they validated
+pub(crate) fn lint_answer(records: &[Record], dnssec: &DnssecInfo, config: &LintConfig) -> Vec<Finding> {
+    let mut findings = lint_txt(records, config);
+    match dnssec.status {
+        DnssecStatus::Secure => findings.extend(dnssec.expiration.and_then(|expiration| lint_expiration(expiration, config))),
+        DnssecStatus::Insecure => findings.push(Finding::new(
+            LintCheck::MissingDs,
+            Severity::Error,
+            "The parent zone has no DS record for the zone, so the records are not DNSSEC-secured",
+        )),
+        DnssecStatus::Bogus => findings.push(Finding::new(LintCheck::Bogus, Severity::Error, "DNSSEC validation failed")),
+        DnssecStatus::Indeterminate => findings.push(Finding::new(
+            LintCheck::Unsigned,
+            Severity::Warning,
+            "DNSSEC was not validated",
+        )),
+    }
+    if let Some(wildcard) = &dnssec.wildcard {
+        findings.push(Finding::new(
+            LintCheck::Wildcard,
+            Severity::Info,
+            format!("Expanded from {}, so every user name resolves", wildcard),
+        ));
+    }
+    findings
+}
+
+/// Checks on the TXT RRset itself
+fn lint_txt(records: &[Record], config: &LintConfig) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let txt: Vec<(u32, String)> = records.iter()
+        .filter_map(|r| match r.data() {
+            Some(RData::TXT(txt)) => Some((r.ttl(), concat_txt(txt.txt_data()))),
+            _ => None,
+        })
+        .collect();
+    let strings: Vec<&String> = txt.iter().map(|(_, s)| s).collect();
+    let (uris, others): (Vec<&String>, Vec<&String>) = strings.into_iter()
+        .partition(|s| s.to_lowercase().starts_with("bitcoin:"));
+
+    match uris.len() {
+        0 => findings.push(Finding::new(LintCheck::NoBitcoinRecord, Severity::Error, "No TXT record is a bitcoin: URI")),
+        1 => {}
+        n => findings.push(Finding::new(
+            LintCheck::MultipleBitcoinRecords,
+            Severity::Error,
+            format!("{} bitcoin: URIs; BIP-353 requires exactly one, so clients refuse to pay", n),
+        )),
+    }
+    if !others.is_empty() {
+        findings.push(Finding::new(
+            LintCheck::NonBitcoinTxt,
+            Severity::Warning,
+            format!("{} TXT records are not bitcoin: URIs and only make responses bigger", others.len()),
+        ));
+    }
+
+    if let Some(ttl) = txt.iter().map(|(ttl, _)| *ttl).min() {
+        if u64::from(ttl) < config.min_ttl.as_secs() {
+            findings.push(Finding::new(LintCheck::ShortTtl, Severity::Warning, format!("TTL of {}s means a lookup for nearly every payment", ttl)));
+        } else if u64::from(ttl) > config.max_ttl.as_secs() {
+            findings.push(Finding::new(LintCheck::LongTtl, Severity::Warning, format!("TTL of {}s delays changes reaching payers", ttl)));
+        }
+    }
+
+    for uri in uris {
+        findings.extend(lint_uri(uri));
+    }
+    findings
+}
+
+/// Checks on one `bitcoin:` URI
+fn lint_uri(uri: &str) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let instruction = match PaymentInstruction::from_uri(uri) {
+        Ok(instruction) => instruction,
+        Err(e) => return vec![Finding::new(LintCheck::InvalidUri, Severity::Error, e.to_string())],
+    };
+
+    if !uri.starts_with("bitcoin:") {
+        findings.push(Finding::new(LintCheck::NotLowercase, Severity::Warning, format!("Scheme {} is not lower case", &uri[..8])));
+    }
+    for (key, _) in instruction.sorted_parameters() {
+        if key.chars().any(|c| c.is_ascii_uppercase()) {
+            findings.push(Finding::new(LintCheck::NotLowercase, Severity::Warning, format!("Parameter {} is not lower case", key)));
+        }
+        if key.to_lowercase().starts_with("req-") {
+            findings.push(Finding::new(
+                LintCheck::UnknownRequiredParameter,
+                Severity::Error,
+                format!("Clients that do not understand {} must refuse to pay", key),
+            ));
+        }
+    }
+
+    if let Some(invoice) = instruction.lightning_invoice() {
+        findings.push(lint_bolt11(invoice, dnssec::now().into()));
+    }
+    if let Some(offer) = instruction.lightning_offer() {
+        if !is_offer(offer) {
+            findings.push(Finding::new(LintCheck::InvalidInvoice, Severity::Error, "lno is not a BOLT12 offer"));
+        }
+    }
+    findings
+}
+
+/// A BOLT11 invoice never belongs in a static record; say how bad it is
+fn lint_bolt11(invoice: &str, now: u64) -> Finding {
+    let Some((hrp, data)) = bech32_decode(invoice) else {
+        return Finding::new(LintCheck::InvalidInvoice, Severity::Error, "lightning is not a valid bech32 BOLT11 invoice");
+    };
+    if !hrp.starts_with("ln") || data.len() < 7 + BOLT11_SIGNATURE_LEN {
+        return Finding::new(LintCheck::InvalidInvoice, Severity::Error, "lightning is not a BOLT11 invoice");
+    }
+
+    let timestamp = data[..7].iter().fold(0u64, |acc, &d| acc << 5 | u64::from(d));
+    let mut expiry = BOLT11_DEFAULT_EXPIRY;
+    let fields = &data[7..data.len() - BOLT11_SIGNATURE_LEN];
+    let mut i = 0;
+    while i + 3 <= fields.len() {
+        let len = usize::from(fields[i + 1]) * 32 + usize::from(fields[i + 2]);
+        let Some(value) = fields.get(i + 3..i + 3 + len) else {
+            return Finding::new(LintCheck::InvalidInvoice, Severity::Error, "lightning invoice has a truncated field");
+        };
+        // `x`: expiry in seconds
+        if fields[i] == 6 {
+            expiry = value.iter().fold(0u64, |acc, &d| acc << 5 | u64::from(d));
+        }
+        i += 3 + len;
+    }
+
+    if timestamp.saturating_add(expiry) <= now {
+        Finding::new(LintCheck::SingleUseInvoice, Severity::Error, "The BOLT11 invoice has expired; use a BOLT12 offer instead")
+    } else {
+        Finding::new(
+            LintCheck::SingleUseInvoice,
+            Severity::Warning,
+            "BOLT11 invoices are single-use and expire, but every payer gets this one; use a BOLT12 offer instead",
+        )
+    }
+}
+
+/// Does `offer` look like a BOLT12 offer? Offers have no checksum, so
+/// only the prefix and alphabet are checked
+fn is_offer(offer: &str) -> bool {
+    // Long offers may be split with `+` and whitespace
+    let offer: String = offer.chars().filter(|c| *c != '+' && !c.is_whitespace()).collect();
+    if offer.chars().any(|c| c.is_ascii_uppercase()) && offer.chars().any(|c| c.is_ascii_lowercase()) {
+        return false;
+    }
+    let offer = offer.to_lowercase();
+    offer.strip_prefix("lno1").is_some_and(|data| !data.is_empty() && data.bytes().all(|b| BECH32_CHARSET.contains(&b)))
+}
+
+fn lint_expiration(expiration: u32, config: &LintConfig) -> Option<Finding> {
+    let now = dnssec::now();
+    if expiration <= now {
+        return Some(Finding::new(LintCheck::ExpiringSignature, Severity::Error, "The RRSIG over the records has expired"));
+    }
+    let left = u64::from(expiration - now);
+    (left < config.signature_expiry_warning.as_secs()).then(|| Finding::new(
+        LintCheck::ExpiringSignature,
+        Severity::Warning,
+        format!("The RRSIG over the records expires in {} hours; re-sign the zone", left / 3600),
+    ))
+}
+
+/// Split a bech32 string into its human-readable part and 5-bit data,
+/// without the checksum, if the checksum is valid (BIP-173)
+fn bech32_decode(s: &str) -> Option<(String, Vec<u8>)> {
+    if s.chars().any(|c| c.is_ascii_uppercase()) && s.chars().any(|c| c.is_ascii_lowercase()) {
+        return None;
+    }
+    let s = s.to_lowercase();
+    let (hrp, data) = s.rsplit_once('1')?;
+    if hrp.is_empty() || data.len() < 6 {
+        return None;
+    }
+    let data: Vec<u8> = data.bytes()
+        .map(|b| BECH32_CHARSET.iter().position(|&c| c == b).map(|v| v as u8))
+        .collect::<Option<_>>()?;
+
+    let mut values: Vec<u8> = hrp.bytes().map(|b| b >> 5).collect();
+    values.push(0);
+    values.extend(hrp.bytes().map(|b| b & 31));
+    values.extend(&data);
+    (bech32_polymod(&values) == 1).then(|| (hrp.to_string(), data[..data.len() - 6].to_vec()))
+}
+
+fn bech32_polymod(values: &[u8]) -> u32 {
+    const GENERATOR: [u32; 5] = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+    let mut checksum: u32 = 1;
+    for &value in values {
+        let top = checksum >> 25;
+        checksum = (checksum & 0x1ffffff) << 5 ^ u32::from(value);
+        for (i, generator) in GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                checksum ^= generator;
+            }
+        }
+    }
+    checksum
+}
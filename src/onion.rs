@@ -0,0 +1,96 @@
+//! Resolution over Lightning onion messages (bLIP-32)
+//!
+//! Instead of talking to DNS, the payer asks a Lightning node to resolve the
+//! name and return an RFC 9102 proof: the TXT RRset plus every DNSKEY, DS
+//! and RRSIG record up to the root. The proof is validated offline, so the
+//! resolving node learns neither who is paying nor can it forge an answer.
+
+use async_trait::async_trait;
+use trust_dns_proto::rr::{Name, Record};
+use trust_dns_proto::serialize::binary::{BinDecodable, BinDecoder, BinEncodable, BinEncoder, EncodeMode};
+
+use crate::Bip353Error;
+
+/// Onion message TLV type of `dnssec_query`
+pub const DNSSEC_QUERY_TYPE: u64 = 65536;
+/// Onion message TLV type of `dnssec_proof`
+pub const DNSSEC_PROOF_TYPE: u64 = 65538;
+
+/// A way to reach a bLIP-32 DNS resolver over onion messages
+///
+/// Implementations pick the resolving node, wrap `payload` in a
+/// `dnssec_query` onion message with a blinded reply path, and return the
+/// payload of the matching `dnssec_proof` reply.
+#[async_trait]
+pub trait OnionMessageTransport: Send + Sync {
+    async fn send_dnssec_query(&self, payload: &[u8]) -> Result<Vec<u8>, Bip353Error>;
+}
+
+/// Encode a `dnssec_query` payload asking for the TXT records at `name`
+pub fn encode_dnssec_query(name: &Name) -> Result<Vec<u8>, Bip353Error> {
+    let name = fqdn_string(name);
+    if name.len() > 255 {
+        return Err(Bip353Error::InvalidAddress("Name too long".into()));
+    }
+
+    let mut payload = vec![name.len() as u8];
+    payload.extend_from_slice(name.as_bytes());
+    Ok(payload)
+}
+
+/// Decode a `dnssec_proof` payload into the name it answers and its proof
+pub fn decode_dnssec_proof(payload: &[u8]) -> Result<(Name, Vec<u8>), Bip353Error> {
+    let invalid = || Bip353Error::DnssecError("Malformed dnssec_proof message".into());
+
+    let (&name_len, rest) = payload.split_first().ok_or_else(invalid)?;
+    let name = rest.get(..name_len as usize).ok_or_else(invalid)?;
+    let rest = &rest[name_len as usize..];
+    let proof_len = rest.get(..2).ok_or_else(invalid)?;
+    let proof_len = u16::from_be_bytes([proof_len[0], proof_len[1]]) as usize;
+    let proof = rest.get(2..2 + proof_len).ok_or_else(invalid)?;
+
+    let name = std::str::from_utf8(name).map_err(|_| invalid())?;
+    let name = Name::from_ascii(name).map_err(|_| invalid())?;
+    Ok((name, proof.to_vec()))
+}
+
+/// Parse an RFC 9102 proof: a concatenation of uncompressed wire-format records
+pub(crate) fn parse_proof(proof: &[u8]) -> Result<Vec<Record>, Bip353Error> {
+    let mut decoder = BinDecoder::new(proof);
+    let mut records = Vec::new();
+    while !decoder.is_empty() {
+        let record = Record::read(&mut decoder)
+            .map_err(|e| Bip353Error::DnssecError(format!("Malformed proof: {}", e)))?;
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// Encode `records` as an RFC 9102 proof, the inverse of `parse_proof`
+pub(crate) fn encode_proof(records: &[Record]) -> Result<Vec<u8>, Bip353Error> {
+    let mut proof = Vec::new();
+    let mut encoder = BinEncoder::with_mode(&mut proof, EncodeMode::Signing);
+    for record in records {
+        record.emit(&mut encoder)
+            .map_err(|e| Bip353Error::DnssecError(format!("Cannot encode proof: {}", e)))?;
+    }
+    Ok(proof)
+}
+
+/// Ask `transport` for a proof of the TXT records at `name`
+pub(crate) async fn fetch_proof(transport: &dyn OnionMessageTransport, name: &Name) -> Result<Vec<Record>, Bip353Error> {
+    let reply = transport.send_dnssec_query(&encode_dnssec_query(name)?).await?;
+    let (proof_name, proof) = decode_dnssec_proof(&reply)?;
+    if &proof_name != name {
+        return Err(Bip353Error::DnssecError(format!("Proof is for {}, not {}", proof_name, name)));
+    }
+    parse_proof(&proof)
+}
+
+fn fqdn_string(name: &Name) -> String {
+    let mut name = name.to_ascii();
+    if !name.ends_with('.') {
+        name.push('.');
+    }
+    name
+}
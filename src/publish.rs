@@ -0,0 +1,165 @@
+//! Publishing payment instructions with dynamic updates (RFC 2136)
+//!
+//! `Publisher` sends TSIG-signed UPDATE messages over TCP to the zone's
+//! primary server, then resolves the name until the change shows up, so a
+//! successful call means resolvers can see the new record.
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use trust_dns_proto::op::{Message, MessageType, OpCode, Query, ResponseCode};
+use trust_dns_proto::rr::{DNSClass, Name, Record, RecordType};
+
+use crate::tcp::{TcpConfig, TcpTransport};
+use crate::transport::DnsTransport;
+use crate::tsig::TsigKey;
+use crate::{payment_record, Bip353Error, PaymentInstruction, Resolver};
+
+/// Dynamic update settings
+#[derive(Debug, Clone)]
+pub struct UpdateConfig {
+    /// The zone's primary server, which accepts updates
+    pub server: TcpConfig,
+    /// The zone the records are published in, e.g. `example.com.`
+    pub zone: Name,
+    pub key: TsigKey,
+    /// TTL of published records
+    pub ttl: u32,
+    /// How many times to resolve the name before the change counts as failed
+    pub verify_attempts: u32,
+    /// Wait between those attempts, for secondaries to pick up the change
+    pub verify_interval: Duration,
+}
+
+impl UpdateConfig {
+    /// Create a config updating `zone` on the server at `server`
+    pub fn new(server: SocketAddr, zone: Name, key: TsigKey) -> Self {
+        Self {
+            server: TcpConfig::new(server),
+            zone,
+            key,
+            ttl: 3600,
+            verify_attempts: 5,
+            verify_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Adds, replaces and deletes users' payment instructions in one zone
+pub struct Publisher {
+    config: UpdateConfig,
+    transport: TcpTransport,
+    resolver: Resolver,
+}
+
+impl Publisher {
+    /// Create a publisher; `resolver` checks every change after it is made
+    pub fn new(config: UpdateConfig, resolver: Resolver) -> Self {
+        let transport = TcpTransport::new(config.server.clone());
+        Self { config, transport, resolver }
+    }
+
+    /// Publish `uri` for `user`, who must not have TXT records yet
+    pub async fn add(&self, user: &str, uri: &str) -> Result<PaymentInstruction, Bip353Error> {
+        let record = self.record(user, uri)?;
+        // Prerequisite (RFC 2136 2.4.3): no TXT RRset at the name
+        let absent = Self::rrset_marker(record.name(), DNSClass::NONE);
+        self.update(vec![absent], vec![record]).await?;
+        self.verify_published(user, uri).await
+    }
+
+    /// Publish `uri` for `user`, replacing any TXT records they have
+    pub async fn replace(&self, user: &str, uri: &str) -> Result<PaymentInstruction, Bip353Error> {
+        let record = self.record(user, uri)?;
+        let delete = Self::rrset_marker(record.name(), DNSClass::ANY);
+        self.update(Vec::new(), vec![delete, record]).await?;
+        self.verify_published(user, uri).await
+    }
+
+    /// Remove `user`'s TXT records
+    pub async fn delete(&self, user: &str) -> Result<(), Bip353Error> {
+        let name = self.record(user, "bitcoin:")?.name().clone();
+        self.update(Vec::new(), vec![Self::rrset_marker(&name, DNSClass::ANY)]).await?;
+        self.verify_deleted(user).await
+    }
+
+    fn record(&self, user: &str, uri: &str) -> Result<Record, Bip353Error> {
+        payment_record(user, &self.config.zone.to_string(), uri, self.config.ttl)
+    }
+
+    /// An RDATA-less TXT record: with class ANY it deletes the RRset, with
+    /// class NONE as a prerequisite it requires the RRset not to exist
+    fn rrset_marker(name: &Name, class: DNSClass) -> Record {
+        let mut record = Record::with(name.clone(), RecordType::TXT, 0);
+        record.set_dns_class(class);
+        record
+    }
+
+    /// Send one signed UPDATE and check the server applied it
+    async fn update(&self, prerequisites: Vec<Record>, updates: Vec<Record>) -> Result<(), Bip353Error> {
+        let mut zone = Query::query(self.config.zone.clone(), RecordType::SOA);
+        zone.set_query_class(DNSClass::IN);
+        let mut message = Message::new();
+        message
+            .set_id(rand::random())
+            .set_message_type(MessageType::Query)
+            .set_op_code(OpCode::Update)
+            .add_query(zone)
+            .add_answers(prerequisites)
+            .add_name_servers(updates);
+        let request_mac = self.config.key.sign(&mut message, None)?;
+        let request = message.to_vec().map_err(|e| Bip353Error::DnsError(e.to_string()))?;
+
+        let response = self.transport.exchange(&request).await?;
+        let reply = Message::from_vec(&response)
+            .map_err(|e| Bip353Error::TransportError(format!("Malformed response: {}", e)))?;
+        if reply.id() != message.id() || reply.message_type() != MessageType::Response || reply.op_code() != OpCode::Update {
+            return Err(Bip353Error::TransportError("Response does not match update".into()));
+        }
+        // Servers do not sign rejections of a bad key or signature
+        if reply.signature().is_empty() && reply.response_code() != ResponseCode::NoError {
+            return Err(Bip353Error::UpdateError(format!("Server returned {}", reply.response_code())));
+        }
+        self.config.key.verify(&response, Some(&request_mac))?;
+        match reply.response_code() {
+            ResponseCode::NoError => Ok(()),
+            code => Err(Bip353Error::UpdateError(format!("Server returned {}", code))),
+        }
+    }
+
+    fn domain(&self) -> String {
+        self.config.zone.to_string()
+    }
+
+    async fn verify_published(&self, user: &str, uri: &str) -> Result<PaymentInstruction, Bip353Error> {
+        let mut last = String::new();
+        for attempt in 0..self.config.verify_attempts {
+            if attempt > 0 {
+                tokio::time::sleep(self.config.verify_interval).await;
+            }
+            self.resolver.flush(user, &self.domain());
+            match self.resolver.resolve(user, &self.domain()).await {
+                Ok(instruction) if instruction.uri == uri => return Ok(instruction),
+                Ok(instruction) => last = format!("resolves to {}", instruction.uri),
+                Err(e) => last = e.to_string(),
+            }
+        }
+        Err(Bip353Error::UpdateError(format!("Update accepted, but {}@{} {}", user, self.domain(), last)))
+    }
+
+    async fn verify_deleted(&self, user: &str) -> Result<(), Bip353Error> {
+        let mut last = String::new();
+        for attempt in 0..self.config.verify_attempts {
+            if attempt > 0 {
+                tokio::time::sleep(self.config.verify_interval).await;
+            }
+            self.resolver.flush(user, &self.domain());
+            match self.resolver.resolve(user, &self.domain()).await {
+                Err(Bip353Error::NotFound(_) | Bip353Error::UnverifiedNotFound(_)) => return Ok(()),
+                Ok(instruction) => last = format!("still resolves to {}", instruction.uri),
+                Err(e) => last = e.to_string(),
+            }
+        }
+        Err(Bip353Error::UpdateError(format!("Update accepted, but {}@{} {}", user, self.domain(), last)))
+    }
+}
@@ -1,60 +1,309 @@
 //! Minimal Python bindings for BIP-353
 //!
 //! These bindings provide a simple Python API for HWI integration.
+//!
+//! `PyResolver` blocks the calling thread (with the GIL released) while
+//! `PyAsyncResolver` returns asyncio awaitables driven by the runtime of a
+//! `BlockingResolver`, so async applications keep their event loop running.
 
 use pyo3::prelude::*;
-use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::exceptions::PyValueError;
+use pyo3::types::PyDict;
 use pyo3::wrap_pyfunction;
-use tokio::runtime::Runtime;
+#[cfg(feature = "test-support")]
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::net::SocketAddr;
+use trust_dns_resolver::config::{NameServerConfig, NameServerConfigGroup, Protocol, ResolverConfig, ResolverOpts};
+
+use crate::{Bip353Config, Bip353Error, BlockingResolver, PaymentInstruction, PaymentType, Resolver};
+
+/// Python exception hierarchy, all rooted at `bip353.Bip353Error`
+///
+/// Malformed input errors also derive from `ValueError`, which the
+/// bindings raised for them before the hierarchy existed.
+/// `create_exception!` takes a single base, so those two are built with
+/// Python's `type()` on first use.
+mod exceptions {
+    use pyo3::create_exception;
+    use pyo3::exceptions::{PyException, PyValueError};
+    use pyo3::prelude::*;
+    use pyo3::sync::GILOnceCell;
+    use pyo3::types::{PyDict, PyType};
+    
+    create_exception!(bip353, Bip353Error, PyException, "Base class for all BIP-353 errors");
+    create_exception!(bip353, DnsError, Bip353Error, "DNS lookup failed");
+    create_exception!(bip353, DnssecError, Bip353Error, "DNSSEC validation failed");
+    create_exception!(bip353, NoRecordError, Bip353Error, "No Bitcoin URI published for the name");
+    create_exception!(bip353, MultipleRecordsError, Bip353Error, "More than one Bitcoin URI published for the name");
+    create_exception!(bip353, NotFoundError, DnsError, "DNSSEC proves the name has no payment instruction");
+    create_exception!(bip353, UnverifiedNotFoundError, DnsError, "The name appears to have no records, but that is not proven");
+    create_exception!(bip353, TransportError, DnsError, "The DNS server could not be reached or gave no usable answer");
+
+    static INVALID_ADDRESS_ERROR: GILOnceCell<Py<PyType>> = GILOnceCell::new();
+    static INVALID_URI_ERROR: GILOnceCell<Py<PyType>> = GILOnceCell::new();
+
+    /// `bip353.InvalidAddressError(Bip353Error, ValueError)`
+    pub fn invalid_address_error(py: Python) -> PyResult<Bound<PyType>> {
+        value_error(py, &INVALID_ADDRESS_ERROR, "InvalidAddressError", "Malformed human-readable address")
+    }
 
-use crate::{Bip353Error, PaymentInstruction, Resolver};
+    /// `bip353.InvalidUriError(Bip353Error, ValueError)`
+    pub fn invalid_uri_error(py: Python) -> PyResult<Bound<PyType>> {
+        value_error(py, &INVALID_URI_ERROR, "InvalidUriError", "Malformed Bitcoin URI")
+    }
+
+    fn value_error<'py>(
+        py: Python<'py>,
+        cell: &GILOnceCell<Py<PyType>>,
+        name: &str,
+        doc: &str,
+    ) -> PyResult<Bound<'py, PyType>> {
+        let class = cell.get_or_try_init(py, || {
+            let namespace = PyDict::new(py);
+            namespace.set_item("__module__", "bip353")?;
+            namespace.set_item("__doc__", doc)?;
+            let bases = (py.get_type::<Bip353Error>(), py.get_type::<PyValueError>());
+            py.get_type::<PyType>().call1((name, bases, namespace))?.downcast_into::<PyType>()
+                .map(Bound::unbind)
+                .map_err(PyErr::from)
+        })?;
+        Ok(class.bind(py).clone())
+    }
+}
 
 /// Convert a BIP-353 error to a Python exception
 fn to_py_err(err: Bip353Error) -> PyErr {
+    let msg = err.to_string();
     match err {
-        Bip353Error::InvalidAddress(_) => PyValueError::new_err(err.to_string()),
-        _ => PyRuntimeError::new_err(err.to_string()),
+        Bip353Error::DnsError(_) => exceptions::DnsError::new_err(msg),
+        Bip353Error::DnssecError(_) => exceptions::DnssecError::new_err(msg),
+        Bip353Error::InvalidAddress(_) => value_error(exceptions::invalid_address_error, msg),
+        Bip353Error::InvalidRecord(_) => value_error(exceptions::invalid_uri_error, msg),
+        Bip353Error::NoRecord(_) => exceptions::NoRecordError::new_err(msg),
+        Bip353Error::MultipleRecords(_) => exceptions::MultipleRecordsError::new_err(msg),
+        Bip353Error::NotFound(_) => exceptions::NotFoundError::new_err(msg),
+        Bip353Error::UnverifiedNotFound(_) => exceptions::UnverifiedNotFoundError::new_err(msg),
+        Bip353Error::TransportError(_) => exceptions::TransportError::new_err(msg),
+        // The bindings do not publish records
+        Bip353Error::UpdateError(_) => exceptions::DnsError::new_err(msg),
+        Bip353Error::StorageError(_) => exceptions::Bip353Error::new_err(msg),
+    }
+}
+
+/// Raise `class` with `msg`, or whatever went wrong creating the class
+fn value_error(class: fn(Python) -> PyResult<Bound<pyo3::types::PyType>>, msg: String) -> PyErr {
+    Python::with_gil(|py| match class(py) {
+        Ok(class) => PyErr::from_type(class, msg),
+        Err(e) => e,
+    })
+}
+
+/// Python enum for payment types
+///
+/// `str()` gives the same names the bindings used before the enum existed.
+#[pyclass(name = "PaymentType", eq, eq_int)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum PyPaymentType {
+    OnChain,
+    Lightning,
+    LightningOffer,
+    Unknown,
+}
+
+impl From<&PaymentType> for PyPaymentType {
+    fn from(payment_type: &PaymentType) -> Self {
+        match payment_type {
+            PaymentType::OnChain => PyPaymentType::OnChain,
+            PaymentType::Lightning => PyPaymentType::Lightning,
+            PaymentType::LightningOffer => PyPaymentType::LightningOffer,
+            PaymentType::Unknown => PyPaymentType::Unknown,
+        }
+    }
+}
+
+impl From<PyPaymentType> for PaymentType {
+    fn from(payment_type: PyPaymentType) -> Self {
+        match payment_type {
+            PyPaymentType::OnChain => PaymentType::OnChain,
+            PyPaymentType::Lightning => PaymentType::Lightning,
+            PyPaymentType::LightningOffer => PaymentType::LightningOffer,
+            PyPaymentType::Unknown => PaymentType::Unknown,
+        }
+    }
+}
+
+#[pymethods]
+impl PyPaymentType {
+    fn __str__(&self) -> &'static str {
+        PaymentType::from(*self).as_str()
     }
 }
 
+/// Build a resolver for the Python classes
+///
+/// With `nameservers` ("ip:port" strings) the system configuration is
+/// bypassed. Answers are always validated against the root anchors.
+fn build_resolver(nameservers: Option<Vec<String>>) -> PyResult<BlockingResolver> {
+    let nameservers = match nameservers {
+        None => return BlockingResolver::new().map_err(to_py_err),
+        Some(nameservers) => nameservers,
+    };
+    
+    let mut group = NameServerConfigGroup::new();
+    for nameserver in nameservers {
+        let socket_addr: SocketAddr = nameserver.parse()
+            .map_err(|_| PyValueError::new_err(format!("Invalid nameserver address: {}", nameserver)))?;
+        group.push(NameServerConfig {
+            socket_addr,
+            protocol: Protocol::Udp,
+            tls_dns_name: None,
+            trust_nx_responses: true,
+            bind_addr: None,
+        });
+    }
+    let config = ResolverConfig::from_parts(None, vec![], group);
+    
+    let resolver = Resolver::system(&config, &ResolverOpts::default(), Bip353Config::default());
+    BlockingResolver::from_resolver(resolver).map_err(to_py_err)
+}
+
 /// Python wrapper for the resolver
 #[pyclass]
 struct PyResolver {
-    resolver: Resolver,
-    rt: Runtime,
+    resolver: BlockingResolver,
 }
 
 #[pymethods]
 impl PyResolver {
     /// Create a new resolver
     #[new]
-    fn new() -> PyResult<Self> {
-        let resolver = Resolver::new().map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
-        let rt = Runtime::new().map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
-        
-        Ok(Self { resolver, rt })
+    #[pyo3(signature = (nameservers=None))]
+    fn new(nameservers: Option<Vec<String>>) -> PyResult<Self> {
+        Ok(Self { resolver: build_resolver(nameservers)? })
     }
     
     /// Resolve a human-readable Bitcoin address
-    fn resolve_address(&self, address: &str) -> PyResult<PyPaymentInstruction> {
-        let instruction = self.rt.block_on(self.resolver.resolve_address(address))
-            .map_err(to_py_err)?;
+    ///
+    /// The GIL is released while waiting on DNS.
+    fn resolve_address(&self, py: Python, address: &str) -> PyResult<PyPaymentInstruction> {
+        let instruction = py.allow_threads(|| self.resolver.resolve_address(address)).map_err(to_py_err)?;
         
         Ok(PyPaymentInstruction { instruction })
     }
     
     /// Parse a human-readable Bitcoin address
     fn parse_address(&self, address: &str) -> PyResult<(String, String)> {
-        Resolver::parse_address(address).map_err(to_py_err)
+        parse_address(address)
     }
 }
 
+/// asyncio-native Python wrapper for the resolver
+///
+/// Resolutions run on the resolver's own Tokio runtime; each call returns an
+/// asyncio future bound to the caller's running event loop, so many names
+/// can be resolved concurrently with `asyncio.gather`.
+#[pyclass]
+struct PyAsyncResolver {
+    resolver: BlockingResolver,
+}
+
+#[pymethods]
+impl PyAsyncResolver {
+    /// Create a new resolver
+    #[new]
+    #[pyo3(signature = (nameservers=None))]
+    fn new(nameservers: Option<Vec<String>>) -> PyResult<Self> {
+        Ok(Self { resolver: build_resolver(nameservers)? })
+    }
+    
+    /// Resolve a human-readable Bitcoin address, returning an awaitable
+    ///
+    /// Must be called from a coroutine running on an asyncio event loop.
+    fn resolve_address<'py>(&self, py: Python<'py>, address: String) -> PyResult<Bound<'py, PyAny>> {
+        let event_loop = py.import("asyncio")?.call_method0("get_running_loop")?;
+        let future = event_loop.call_method0("create_future")?;
+        
+        let event_loop = event_loop.unbind();
+        let py_future = future.clone().unbind();
+        
+        self.resolver.spawn(|resolver| async move {
+            let result = resolver.resolve_address(&address).await;
+            
+            Python::with_gil(|py| {
+                let result = result
+                    .map_err(to_py_err)
+                    .and_then(|instruction| Py::new(py, PyPaymentInstruction { instruction }))
+                    .map(Py::into_any);
+                let setter = FutureResultSetter { future: py_future, result: Some(result) };
+                // The event loop may already be closed if the caller went away
+                let _ = event_loop.call_method1(py, "call_soon_threadsafe", (setter,));
+            });
+        });
+        
+        Ok(future)
+    }
+    
+    /// Parse a human-readable Bitcoin address
+    fn parse_address(&self, address: &str) -> PyResult<(String, String)> {
+        parse_address(address)
+    }
+}
+
+/// Completes an asyncio future from the event loop thread
+///
+/// Scheduled with `call_soon_threadsafe` since asyncio futures are not
+/// thread-safe. Skips futures that were cancelled while the lookup ran.
+#[pyclass]
+struct FutureResultSetter {
+    future: PyObject,
+    result: Option<PyResult<PyObject>>,
+}
+
+#[pymethods]
+impl FutureResultSetter {
+    fn __call__(&mut self, py: Python) -> PyResult<()> {
+        if self.future.call_method0(py, "done")?.is_truthy(py)? {
+            return Ok(());
+        }
+        
+        match self.result.take() {
+            Some(Ok(value)) => self.future.call_method1(py, "set_result", (value,))?,
+            Some(Err(err)) => self.future.call_method1(py, "set_exception", (err.into_value(py),))?,
+            None => return Ok(()),
+        };
+        
+        Ok(())
+    }
+}
+
+/// Parse a human-readable Bitcoin address into (user, domain) without any DNS lookup
+#[pyfunction]
+fn parse_address(address: &str) -> PyResult<(String, String)> {
+    Resolver::parse_address(address).map_err(to_py_err)
+}
+
+/// Parse a Bitcoin URI into a payment instruction without any DNS lookup
+#[pyfunction]
+fn parse_uri(uri: &str) -> PyResult<PyPaymentInstruction> {
+    let instruction = PaymentInstruction::from_uri(uri).map_err(to_py_err)?;
+    Ok(PyPaymentInstruction { instruction })
+}
+
 /// Python wrapper for payment instructions
 #[pyclass]
 struct PyPaymentInstruction {
     instruction: PaymentInstruction,
 }
 
+impl PyPaymentInstruction {
+    /// What equality and hashing look at: where the payment goes, not how
+    /// the record was validated or which proof it came with
+    fn key(&self) -> (&str, &'static str, Vec<(&str, &str)>) {
+        (&self.instruction.uri, self.instruction.payment_type.as_str(), self.instruction.sorted_parameters())
+    }
+}
+
 #[pymethods]
 impl PyPaymentInstruction {
     /// Get the URI
@@ -65,13 +314,8 @@ impl PyPaymentInstruction {
     
     /// Get the payment type
     #[getter]
-    fn payment_type(&self) -> String {
-        match self.instruction.payment_type {
-            crate::PaymentType::OnChain => "on-chain".to_string(),
-            crate::PaymentType::Lightning => "lightning".to_string(),
-            crate::PaymentType::LightningOffer => "lightning-offer".to_string(),
-            crate::PaymentType::Unknown => "unknown".to_string(),
-        }
+    fn payment_type(&self) -> PyPaymentType {
+        PyPaymentType::from(&self.instruction.payment_type)
     }
     
     /// Is the payment instruction reusable?
@@ -91,13 +335,148 @@ impl PyPaymentInstruction {
         
         dict.into()
     }
+
+    /// DNSSEC status ("secure", "insecure", "bogus" or "indeterminate")
+    #[getter]
+    fn dnssec_status(&self) -> &'static str {
+        self.instruction.dnssec.status.as_str()
+    }
+
+    /// Convert to a plain dict (payment type and DNSSEC status as strings)
+    fn to_dict(&self, py: Python) -> PyResult<PyObject> {
+        let dict = PyDict::new(py);
+        dict.set_item("uri", &self.instruction.uri)?;
+        dict.set_item("payment_type", self.instruction.payment_type.as_str())?;
+        dict.set_item("is_reusable", self.instruction.is_reusable)?;
+        dict.set_item("parameters", self.parameters(py))?;
+        dict.set_item("dnssec_status", self.dnssec_status())?;
+        
+        Ok(dict.into())
+    }
+    
+    /// Serialize to a JSON string
+    fn to_json(&self, py: Python) -> PyResult<String> {
+        let dict = self.to_dict(py)?;
+        py.import("json")?
+            .call_method1("dumps", (dict,))?
+            .extract()
+    }
+    
+    /// Equal if the URI, payment type and parameters are, so two
+    /// resolutions of an unchanged record compare equal
+    fn __eq__(&self, other: PyRef<Self>) -> bool {
+        self.key() == other.key()
+    }
+    
+    fn __hash__(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.key().hash(&mut hasher);
+        hasher.finish()
+    }
+    
+    fn __repr__(&self) -> String {
+        format!(
+            "PaymentInstruction(uri={:?}, payment_type={}, is_reusable={})",
+            self.instruction.uri,
+            self.instruction.payment_type.as_str(),
+            if self.instruction.is_reusable { "True" } else { "False" },
+        )
+    }
+}
+
+/// A TXT record: one string, or a list of character-strings
+#[cfg(feature = "test-support")]
+#[derive(FromPyObject)]
+enum PyTxt {
+    Text(String),
+    Strings(Vec<String>),
+}
+
+/// In-process signed DNS server for the Python tests (feature `test-support`)
+///
+/// Serves one `TestZone` on localhost from its own Tokio runtime. The
+/// resolvers it hands out validate against its keys, so tests run the
+/// same path as `PyResolver(nameservers=...)` without the internet.
+#[cfg(feature = "test-support")]
+#[pyclass(name = "TestServer")]
+struct PyTestServer {
+    // Dropped before the runtime its tasks run on
+    server: crate::testing::TestServer,
+    _runtime: tokio::runtime::Runtime,
+}
+
+#[cfg(feature = "test-support")]
+#[pymethods]
+impl PyTestServer {
+    /// Sign `records` (owner name -> TXT records) into `zone` and serve it
+    #[new]
+    #[pyo3(signature = (records, zone="example.com."))]
+    fn new(py: Python, records: HashMap<String, Vec<PyTxt>>, zone: &str) -> PyResult<Self> {
+        let mut test_zone = crate::testing::TestZone::new(zone).map_err(to_py_err)?;
+        for (name, txts) in &records {
+            for txt in txts {
+                match txt {
+                    PyTxt::Text(text) => test_zone.add_txt(name, &[text]),
+                    PyTxt::Strings(strings) => {
+                        test_zone.add_txt(name, &strings.iter().map(String::as_str).collect::<Vec<_>>())
+                    }
+                }.map_err(to_py_err)?;
+            }
+        }
+        
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .thread_name("bip353-test-server")
+            .build()
+            .map_err(|e| to_py_err(Bip353Error::DnsError(format!("Cannot start runtime: {}", e))))?;
+        let server = py.allow_threads(|| runtime.block_on(crate::testing::TestServer::start(vec![test_zone])))
+            .map_err(to_py_err)?;
+        Ok(Self { server, _runtime: runtime })
+    }
+    
+    /// The "ip:port" the server answers on, over UDP and TCP
+    #[getter]
+    fn address(&self) -> String {
+        self.server.udp_addr().to_string()
+    }
+    
+    /// A blocking resolver that trusts this server's keys
+    fn resolver(&self) -> PyResult<PyResolver> {
+        let resolver = BlockingResolver::from_resolver(self.server.system_resolver()).map_err(to_py_err)?;
+        Ok(PyResolver { resolver })
+    }
+    
+    /// An asyncio resolver that trusts this server's keys
+    fn async_resolver(&self) -> PyResult<PyAsyncResolver> {
+        let resolver = BlockingResolver::from_resolver(self.server.system_resolver()).map_err(to_py_err)?;
+        Ok(PyAsyncResolver { resolver })
+    }
 }
 
 /// Python module
 #[pymodule]
-fn bip353(_py: Python, m: &PyModule) -> PyResult<()> {
+fn bip353(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = m.py();
     m.add_class::<PyResolver>()?;
+    m.add_class::<PyAsyncResolver>()?;
     m.add_class::<PyPaymentInstruction>()?;
+    m.add_class::<PyPaymentType>()?;
+    #[cfg(feature = "test-support")]
+    m.add_class::<PyTestServer>()?;
+    
+    m.add("Bip353Error", py.get_type::<exceptions::Bip353Error>())?;
+    m.add("DnsError", py.get_type::<exceptions::DnsError>())?;
+    m.add("DnssecError", py.get_type::<exceptions::DnssecError>())?;
+    m.add("InvalidAddressError", exceptions::invalid_address_error(py)?)?;
+    m.add("InvalidUriError", exceptions::invalid_uri_error(py)?)?;
+    m.add("NoRecordError", py.get_type::<exceptions::NoRecordError>())?;
+    m.add("MultipleRecordsError", py.get_type::<exceptions::MultipleRecordsError>())?;
+    m.add("NotFoundError", py.get_type::<exceptions::NotFoundError>())?;
+    m.add("UnverifiedNotFoundError", py.get_type::<exceptions::UnverifiedNotFoundError>())?;
+    m.add("TransportError", py.get_type::<exceptions::TransportError>())?;
+    
+    m.add_function(wrap_pyfunction!(parse_address, m)?)?;
+    m.add_function(wrap_pyfunction!(parse_uri, m)?)?;
     
     Ok(())
 }
\ No newline at end of file
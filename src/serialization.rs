@@ -0,0 +1,82 @@
+//! `serde` helpers for the trust-dns types in public structs
+//!
+//! Names are written as their presentation form with the trailing dot
+//! (`example.com.`) and algorithms as their IANA number (15 for Ed25519),
+//! so unknown algorithms round-trip too. Proofs are lowercase hex.
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use trust_dns_proto::rr::dnssec::Algorithm;
+use trust_dns_proto::rr::Name;
+
+pub(crate) mod name {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(name: &Name, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(name)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Name, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Name::from_ascii(&name).map_err(serde::de::Error::custom)
+    }
+}
+
+pub(crate) mod option_name {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(name: &Option<Name>, serializer: S) -> Result<S::Ok, S::Error> {
+        name.as_ref().map(Name::to_string).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Name>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|name| Name::from_ascii(&name).map_err(serde::de::Error::custom))
+            .transpose()
+    }
+}
+
+pub(crate) mod algorithm {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(algorithm: &Algorithm, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8((*algorithm).into())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Algorithm, D::Error> {
+        u8::deserialize(deserializer).map(Algorithm::from_u8)
+    }
+}
+
+pub(crate) mod option_algorithm {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(algorithm: &Option<Algorithm>, serializer: S) -> Result<S::Ok, S::Error> {
+        algorithm.map(u8::from).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Algorithm>, D::Error> {
+        Ok(Option::<u8>::deserialize(deserializer)?.map(Algorithm::from_u8))
+    }
+}
+
+pub(crate) mod option_hex {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(bytes: &Option<Vec<u8>>, serializer: S) -> Result<S::Ok, S::Error> {
+        bytes.as_deref().map(|bytes| data_encoding::HEXLOWER.encode(bytes)).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Vec<u8>>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|hex| data_encoding::HEXLOWER_PERMISSIVE.decode(hex.as_bytes()).map_err(serde::de::Error::custom))
+            .transpose()
+    }
+}
+
+/// Write a map with its keys sorted, so the output does not depend on
+/// `HashMap` iteration order
+pub(crate) fn sorted<S: Serializer>(map: &HashMap<String, String>, serializer: S) -> Result<S::Ok, S::Error> {
+    map.iter().collect::<BTreeMap<_, _>>().serialize(serializer)
+}
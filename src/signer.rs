@@ -0,0 +1,491 @@
+//! DNSSEC zone signing for small self-run zones (feature `signer`)
+//!
+//! `ZoneSigner` takes a zone's records, e.g. the TXT records from
+//! `payment_record`, and returns them signed: DNSKEYs, RRSIGs and an NSEC
+//! or NSEC3 chain, plus the DS record to publish at the parent. The
+//! output uses the same `trust-dns` record types the resolver validates.
+//!
+//! ```no_run
+//! # fn example() -> Result<(), bip353::Bip353Error> {
+//! use bip353::signer::{SignerConfig, SigningKey, ZoneSigner};
+//! use trust_dns_proto::rr::dnssec::Algorithm;
+//! # let records = Vec::new();
+//!
+//! let apex = "example.com.".parse().unwrap();
+//! let ksk = SigningKey::generate(Algorithm::ED25519, true)?;
+//! let zsk = SigningKey::generate(Algorithm::ED25519, false)?;
+//! let signed = ZoneSigner::new(apex, ksk, zsk, SignerConfig::default()).sign(&records)?;
+//!
+//! std::fs::write("example.com.zone", signed.to_zone_file()).unwrap();
+//! println!("Add to the parent zone: {}", signed.ds_record());
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::time::Duration;
+
+use ring::rand::SystemRandom;
+use ring::signature::{EcdsaKeyPair, Ed25519KeyPair, KeyPair, ECDSA_P256_SHA256_FIXED_SIGNING};
+use trust_dns_proto::rr::dnssec::rdata::{DNSSECRData, DNSKEY, DS, NSEC, NSEC3, NSEC3PARAM, SIG};
+use trust_dns_proto::rr::dnssec::{tbs, Algorithm, DigestType, Nsec3HashAlgorithm};
+use trust_dns_proto::rr::{DNSClass, Name, RData, Record, RecordType};
+
+use crate::{denial, dnssec, Bip353Error};
+
+/// Signatures are valid from this long before signing, to allow for clock skew
+const INCEPTION_SKEW: u32 = 3600;
+
+fn signing_error(e: impl std::fmt::Display) -> Bip353Error {
+    Bip353Error::DnssecError(e.to_string())
+}
+
+enum KeyPairKind {
+    Ed25519(Ed25519KeyPair),
+    EcdsaP256(EcdsaKeyPair),
+}
+
+/// A zone signing key (ZSK) or key signing key (KSK)
+///
+/// Ed25519 and ECDSA P-256/SHA-256 keys are supported. Keys are stored as
+/// PKCS#8 documents, see `to_pkcs8` and `from_pkcs8`.
+pub struct SigningKey {
+    key_pair: KeyPairKind,
+    pkcs8: Vec<u8>,
+    dnskey: DNSKEY,
+    key_tag: u16,
+    rng: SystemRandom,
+}
+
+impl SigningKey {
+    /// Generate a new key; a KSK has the secure entry point flag set
+    pub fn generate(algorithm: Algorithm, ksk: bool) -> Result<Self, Bip353Error> {
+        let rng = SystemRandom::new();
+        let pkcs8 = match algorithm {
+            Algorithm::ED25519 => Ed25519KeyPair::generate_pkcs8(&rng),
+            Algorithm::ECDSAP256SHA256 => EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng),
+            _ => return Err(signing_error(format!("Cannot sign with {}", algorithm))),
+        }
+        .map_err(|_| signing_error("Cannot generate a signing key"))?;
+        Self::from_pkcs8(algorithm, pkcs8.as_ref(), ksk)
+    }
+
+    /// Load a key from a PKCS#8 document
+    pub fn from_pkcs8(algorithm: Algorithm, pkcs8: &[u8], ksk: bool) -> Result<Self, Bip353Error> {
+        let invalid = |_| signing_error(format!("Invalid {} PKCS#8 key", algorithm));
+        let (key_pair, public_key) = match algorithm {
+            Algorithm::ED25519 => {
+                let key_pair = Ed25519KeyPair::from_pkcs8_maybe_unchecked(pkcs8).map_err(invalid)?;
+                let public_key = key_pair.public_key().as_ref().to_vec();
+                (KeyPairKind::Ed25519(key_pair), public_key)
+            }
+            Algorithm::ECDSAP256SHA256 => {
+                let key_pair = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8).map_err(invalid)?;
+                // DNSKEY carries the bare point, without the uncompressed-form 0x04 prefix
+                let public_key = key_pair.public_key().as_ref()[1..].to_vec();
+                (KeyPairKind::EcdsaP256(key_pair), public_key)
+            }
+            _ => return Err(signing_error(format!("Cannot sign with {}", algorithm))),
+        };
+        let dnskey = DNSKEY::new(true, ksk, false, algorithm, public_key);
+        let key_tag = dnskey.calculate_key_tag().map_err(signing_error)?;
+        Ok(Self { key_pair, pkcs8: pkcs8.to_vec(), dnskey, key_tag, rng: SystemRandom::new() })
+    }
+
+    /// The PKCS#8 document to store the key in
+    pub fn to_pkcs8(&self) -> &[u8] {
+        &self.pkcs8
+    }
+
+    /// The public key
+    pub fn dnskey(&self) -> &DNSKEY {
+        &self.dnskey
+    }
+
+    pub fn key_tag(&self) -> u16 {
+        self.key_tag
+    }
+
+    /// Is this a key signing key?
+    pub fn is_ksk(&self) -> bool {
+        self.dnskey.secure_entry_point()
+    }
+
+    /// The SHA-256 DS record data for this key as a key of `zone`
+    pub fn ds(&self, zone: &Name) -> Result<DS, Bip353Error> {
+        let digest = self.dnskey.to_digest(zone, DigestType::SHA256).map_err(signing_error)?;
+        Ok(DS::new(self.key_tag, self.dnskey.algorithm(), DigestType::SHA256, digest.as_ref().to_vec()))
+    }
+
+    /// The RRSIG over `rrset`, all of one name and type, made by this key
+    /// as a key of `zone`
+    fn sign(&self, zone: &Name, rrset: &[Record], inception: u32, expiration: u32) -> Result<Record, Bip353Error> {
+        let first = &rrset[0];
+        let sig = SIG::new(
+            first.record_type(),
+            self.dnskey.algorithm(),
+            first.name().num_labels(),
+            first.ttl(),
+            expiration,
+            inception,
+            self.key_tag,
+            zone.clone(),
+            Vec::new(),
+        );
+        let tbs = tbs::rrset_tbs_with_sig(first.name(), DNSClass::IN, &sig, rrset).map_err(signing_error)?;
+        let signature = match &self.key_pair {
+            KeyPairKind::Ed25519(key_pair) => key_pair.sign(tbs.as_ref()).as_ref().to_vec(),
+            KeyPairKind::EcdsaP256(key_pair) => key_pair.sign(&self.rng, tbs.as_ref())
+                .map_err(|_| signing_error("ECDSA signing failed"))?
+                .as_ref()
+                .to_vec(),
+        };
+
+        let mut record = Record::with(first.name().clone(), RecordType::RRSIG, first.ttl());
+        record.set_data(Some(RData::DNSSEC(DNSSECRData::SIG(sig.set_sig(signature)))));
+        Ok(record)
+    }
+}
+
+/// How the signed zone proves that names and types do not exist
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DenialConfig {
+    /// NSEC records (RFC 4034), which let anyone list the zone's names
+    Nsec,
+    /// Hashed NSEC3 records (RFC 5155)
+    Nsec3 {
+        /// Extra SHA-1 iterations; RFC 9276 recommends 0, and validators
+        /// ignore more than 150
+        iterations: u16,
+        salt: Vec<u8>,
+        /// Leave delegations without a DS out of the chain
+        opt_out: bool,
+    },
+}
+
+/// Signing settings
+#[derive(Debug, Clone)]
+pub struct SignerConfig {
+    pub denial: DenialConfig,
+    /// How long signatures stay valid; the zone must be re-signed before then
+    pub validity: Duration,
+}
+
+impl Default for SignerConfig {
+    fn default() -> Self {
+        Self {
+            denial: DenialConfig::Nsec3 { iterations: 0, salt: Vec::new(), opt_out: false },
+            validity: Duration::from_secs(30 * 86400),
+        }
+    }
+}
+
+/// Signs a zone's records with a KSK and a ZSK
+///
+/// The KSK signs the DNSKEY RRset and the ZSK everything else. The same
+/// key may not be used for both; use two keys even for a test zone.
+pub struct ZoneSigner {
+    apex: Name,
+    ksk: SigningKey,
+    zsk: SigningKey,
+    config: SignerConfig,
+}
+
+impl ZoneSigner {
+    pub fn new(apex: Name, ksk: SigningKey, zsk: SigningKey, config: SignerConfig) -> Self {
+        Self { apex, ksk, zsk, config }
+    }
+
+    pub fn ksk(&self) -> &SigningKey {
+        &self.ksk
+    }
+
+    pub fn zsk(&self) -> &SigningKey {
+        &self.zsk
+    }
+
+    /// Sign `records` with signatures valid from now
+    ///
+    /// The records must be in the zone and include its SOA. RRSIG, NSEC and
+    /// NSEC3 records in the input are replaced, so a signed zone can be
+    /// signed again.
+    pub fn sign(&self, records: &[Record]) -> Result<SignedZone, Bip353Error> {
+        self.sign_at(records, dnssec::now())
+    }
+
+    /// Sign `records` as of `now`, in seconds since the Unix epoch
+    pub fn sign_at(&self, records: &[Record], now: u32) -> Result<SignedZone, Bip353Error> {
+        if !self.ksk.is_ksk() || self.zsk.is_ksk() {
+            return Err(signing_error("The KSK must have the SEP flag and the ZSK must not"));
+        }
+        let mut zone = Zone::new(&self.apex, records)?;
+        let ttl = zone.soa_ttl;
+        for key in [&self.ksk, &self.zsk] {
+            let dnskey = RData::DNSSEC(DNSSECRData::DNSKEY(key.dnskey.clone()));
+            if !zone.records.iter().any(|r| r.name() == &self.apex && r.data() == Some(&dnskey)) {
+                zone.records.push(Record::from_rdata(self.apex.clone(), ttl, dnskey));
+            }
+        }
+
+        match &self.config.denial {
+            DenialConfig::Nsec => zone.add_nsec_chain(),
+            DenialConfig::Nsec3 { iterations, salt, opt_out } => zone.add_nsec3_chain(*iterations, salt, *opt_out)?,
+        }
+
+        let inception = now.saturating_sub(INCEPTION_SKEW);
+        let expiration = now.saturating_add(self.config.validity.as_secs().min(u32::MAX as u64) as u32);
+        let mut rrsigs = Vec::new();
+        for (name, record_type) in zone.signed_rrsets() {
+            let key = if record_type == RecordType::DNSKEY { &self.ksk } else { &self.zsk };
+            let rrset = dnssec::rrset(&zone.records, &name, record_type);
+            rrsigs.push(key.sign(&self.apex, &rrset, inception, expiration)?);
+        }
+        zone.records.extend(rrsigs);
+
+        Ok(SignedZone {
+            ds: Record::from_rdata(self.apex.clone(), ttl, RData::DNSSEC(DNSSECRData::DS(self.ksk.ds(&self.apex)?))),
+            apex: self.apex.clone(),
+            records: zone.records,
+        })
+    }
+}
+
+/// The records of a zone being signed
+struct Zone<'a> {
+    apex: &'a Name,
+    records: Vec<Record>,
+    /// Names with NS records below the apex
+    cuts: Vec<Name>,
+    soa_ttl: u32,
+    /// TTL of NSEC and NSEC3 records (RFC 9077)
+    negative_ttl: u32,
+}
+
+impl<'a> Zone<'a> {
+    fn new(apex: &'a Name, records: &[Record]) -> Result<Self, Bip353Error> {
+        let mut zone_records: Vec<Record> = Vec::new();
+        for record in records {
+            if !apex.zone_of(record.name()) {
+                return Err(Bip353Error::InvalidRecord(format!("{} is not in zone {}", record.name(), apex)));
+            }
+            if matches!(record.record_type(), RecordType::RRSIG | RecordType::NSEC | RecordType::NSEC3 | RecordType::NSEC3PARAM) {
+                continue;
+            }
+            if !zone_records.iter().any(|r| r.name() == record.name() && r.data() == record.data()) {
+                zone_records.push(record.clone());
+            }
+        }
+
+        let (soa_ttl, minimum) = zone_records.iter()
+            .filter(|r| r.name() == apex)
+            .find_map(|r| match r.data() {
+                Some(RData::SOA(soa)) => Some((r.ttl(), soa.minimum())),
+                _ => None,
+            })
+            .ok_or_else(|| Bip353Error::InvalidRecord(format!("{} has no SOA record", apex)))?;
+        let cuts = zone_records.iter()
+            .filter(|r| r.record_type() == RecordType::NS && r.name() != apex)
+            .map(|r| r.name().clone())
+            .collect();
+        Ok(Self { apex, records: zone_records, cuts, soa_ttl, negative_ttl: soa_ttl.min(minimum) })
+    }
+
+    /// Is `name` below a delegation, i.e. glue rather than zone data?
+    fn occluded(&self, name: &Name) -> bool {
+        self.cuts.iter().any(|cut| cut != name && cut.zone_of(name))
+    }
+
+    /// Names the zone is authoritative for, or delegates, in canonical order
+    fn names(&self) -> BTreeSet<Name> {
+        self.records.iter()
+            .map(|r| r.name().clone())
+            .filter(|name| !self.occluded(name))
+            .collect()
+    }
+
+    fn types_at(&self, name: &Name) -> Vec<RecordType> {
+        self.records.iter().filter(|r| r.name() == name).map(Record::record_type).collect()
+    }
+
+    fn is_insecure_cut(&self, name: &Name) -> bool {
+        self.cuts.contains(name) && !self.types_at(name).contains(&RecordType::DS)
+    }
+
+    fn add_nsec_chain(&mut self) {
+        let names: Vec<Name> = self.names().into_iter().collect();
+        for (i, name) in names.iter().enumerate() {
+            let next = names.get(i + 1).unwrap_or(self.apex);
+            let types = bitmap(self.types_at(name).into_iter().chain([RecordType::RRSIG, RecordType::NSEC]));
+            let nsec = NSEC::new(next.clone(), types);
+            self.records.push(Record::from_rdata(name.clone(), self.negative_ttl, RData::DNSSEC(DNSSECRData::NSEC(nsec))));
+        }
+    }
+
+    fn add_nsec3_chain(&mut self, iterations: u16, salt: &[u8], opt_out: bool) -> Result<(), Bip353Error> {
+        if iterations > denial::MAX_NSEC3_ITERATIONS {
+            return Err(signing_error(format!("{} NSEC3 iterations is more than validators accept", iterations)));
+        }
+        let mut names = BTreeSet::new();
+        for name in self.names() {
+            if opt_out && self.is_insecure_cut(&name) {
+                continue;
+            }
+            // Empty non-terminals between the apex and the name get an NSEC3 too
+            for labels in self.apex.num_labels()..=name.num_labels() {
+                names.insert(name.trim_to(labels as usize));
+            }
+            names.insert(name);
+        }
+
+        let mut hashed = Vec::new();
+        for name in names {
+            let hash = Nsec3HashAlgorithm::SHA1.hash(salt, &name, iterations).map_err(signing_error)?;
+            let mut types = self.types_at(&name);
+            if !types.is_empty() && !self.is_insecure_cut(&name) {
+                types.push(RecordType::RRSIG);
+            }
+            if name == *self.apex {
+                types.push(RecordType::NSEC3PARAM);
+            }
+            hashed.push((hash.as_ref().to_vec(), name, bitmap(types.into_iter())));
+        }
+        hashed.sort_by(|a, b| a.0.cmp(&b.0));
+        if let Some(pair) = hashed.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+            return Err(signing_error(format!("NSEC3 hash collision between {} and {}", pair[0].1, pair[1].1)));
+        }
+
+        for (i, (hash, _, types)) in hashed.iter().enumerate() {
+            let next = &hashed[(i + 1) % hashed.len()].0;
+            let label = data_encoding::BASE32HEX_NOPAD.encode(hash).to_ascii_lowercase();
+            let owner = Name::from_ascii(label).and_then(|label| label.append_domain(self.apex)).map_err(signing_error)?;
+            let nsec3 = NSEC3::new(Nsec3HashAlgorithm::SHA1, opt_out, iterations, salt.to_vec(), next.clone(), types.clone());
+            self.records.push(Record::from_rdata(owner, self.negative_ttl, RData::DNSSEC(DNSSECRData::NSEC3(nsec3))));
+        }
+        let param = NSEC3PARAM::new(Nsec3HashAlgorithm::SHA1, false, iterations, salt.to_vec());
+        self.records.push(Record::from_rdata(self.apex.clone(), 0, RData::DNSSEC(DNSSECRData::NSEC3PARAM(param))));
+        Ok(())
+    }
+
+    /// Every RRset that gets an RRSIG: all but delegation NS records and glue
+    fn signed_rrsets(&self) -> BTreeSet<(Name, RecordType)> {
+        self.records.iter()
+            .filter(|r| !self.occluded(r.name()))
+            .filter(|r| !(r.record_type() == RecordType::NS && self.cuts.contains(r.name())))
+            .map(|r| (r.name().clone(), r.record_type()))
+            .collect()
+    }
+}
+
+/// Sorted, deduplicated types for an NSEC or NSEC3 type bitmap
+fn bitmap(types: impl Iterator<Item = RecordType>) -> Vec<RecordType> {
+    let mut types: Vec<RecordType> = types.collect();
+    types.sort_by_key(|t| u16::from(*t));
+    types.dedup();
+    types
+}
+
+/// A signed zone and the DS record for its parent
+#[derive(Debug, Clone)]
+pub struct SignedZone {
+    apex: Name,
+    records: Vec<Record>,
+    ds: Record,
+}
+
+impl SignedZone {
+    pub fn apex(&self) -> &Name {
+        &self.apex
+    }
+
+    /// All records, signatures and denial records included
+    pub fn records(&self) -> &[Record] {
+        &self.records
+    }
+
+    pub fn into_records(self) -> Vec<Record> {
+        self.records
+    }
+
+    /// The DS record of the KSK, to add to the parent zone
+    pub fn ds_record(&self) -> &Record {
+        &self.ds
+    }
+
+    /// The zone in RFC 1035 master file format, SOA first
+    pub fn to_zone_file(&self) -> String {
+        let mut records: Vec<&Record> = self.records.iter().collect();
+        records.sort_by_key(|r| {
+            let is_soa = r.name() == &self.apex && r.record_type() == RecordType::SOA;
+            (!is_soa, r.name().clone(), u16::from(covered_type(r)), r.record_type() == RecordType::RRSIG)
+        });
+
+        let mut zone_file = format!("$ORIGIN {}\n", self.apex);
+        for record in records {
+            let _ = writeln!(zone_file, "{}", presentation(record));
+        }
+        zone_file
+    }
+}
+
+/// Type an RRSIG covers, or the record's own type
+pub(crate) fn covered_type(record: &Record) -> RecordType {
+    match record.data() {
+        Some(RData::DNSSEC(DNSSECRData::SIG(sig))) => sig.type_covered(),
+        _ => record.record_type(),
+    }
+}
+
+/// A record in master file format
+///
+/// `trust-dns` prints TXT strings unquoted and NSEC3 hashes in the wrong
+/// base32 alphabet, so those two are formatted here.
+fn presentation(record: &Record) -> String {
+    let head = format!("{} {} {} {}", record.name(), record.ttl(), record.dns_class(), record.record_type());
+    match record.data() {
+        Some(RData::TXT(txt)) => {
+            let strings: Vec<String> = txt.txt_data().iter().map(|s| quote(s)).collect();
+            format!("{} {}", head, strings.join(" "))
+        }
+        Some(RData::DNSSEC(DNSSECRData::NSEC3(nsec3))) => {
+            let salt = match nsec3.salt() {
+                [] => "-".to_string(),
+                salt => data_encoding::HEXUPPER.encode(salt),
+            };
+            let mut line = format!(
+                "{} {} {} {} {} {}",
+                head,
+                u8::from(nsec3.hash_algorithm()),
+                u8::from(nsec3.opt_out()),
+                nsec3.iterations(),
+                salt,
+                data_encoding::BASE32HEX_NOPAD.encode(nsec3.next_hashed_owner_name()).to_ascii_lowercase(),
+            );
+            for record_type in nsec3.type_bit_maps() {
+                let _ = write!(line, " {}", record_type);
+            }
+            line
+        }
+        Some(data) => format!("{} {}", head, data),
+        None => head,
+    }
+}
+
+/// A TXT character-string, quoted and escaped
+fn quote(bytes: &[u8]) -> String {
+    let mut quoted = String::from("\"");
+    for &byte in bytes {
+        match byte {
+            b'"' | b'\\' => {
+                quoted.push('\\');
+                quoted.push(byte as char);
+            }
+            0x20..=0x7e => quoted.push(byte as char),
+            _ => {
+                let _ = write!(quoted, "\\{:03}", byte);
+            }
+        }
+    }
+    quoted.push('"');
+    quoted
+}
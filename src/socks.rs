@@ -0,0 +1,144 @@
+//! SOCKS5 client (RFC 1928/1929) for routing DNS traffic through Tor
+//!
+//! Host names are handed to the proxy unresolved, so nothing about the
+//! lookup leaks to the local network.
+
+use std::net::SocketAddr;
+#[cfg(feature = "tokio")]
+use std::net::IpAddr;
+
+#[cfg(feature = "tokio")]
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+#[cfg(feature = "tokio")]
+use tokio::net::TcpStream;
+
+#[cfg(feature = "tokio")]
+use crate::Bip353Error;
+
+/// How SOCKS credentials are chosen
+///
+/// Tor puts streams with different credentials on different circuits, so
+/// unrelated lookups cannot be linked by the exit or the nameserver.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum StreamIsolation {
+    /// No authentication
+    None,
+    /// Fresh random credentials for every `Resolver::resolve` call
+    #[default]
+    PerLookup,
+    /// Always use these credentials
+    Credentials { username: String, password: String },
+}
+
+/// SOCKS5 proxy settings
+#[derive(Debug, Clone)]
+pub struct Socks5Config {
+    /// Proxy address, e.g. `127.0.0.1:9050` for a local Tor daemon
+    pub address: SocketAddr,
+    pub isolation: StreamIsolation,
+}
+
+impl Socks5Config {
+    /// Create a config for the proxy at `address`, isolating each lookup
+    pub fn new(address: SocketAddr) -> Self {
+        Self { address, isolation: StreamIsolation::default() }
+    }
+}
+
+/// Where the proxy should connect to
+#[cfg(feature = "tokio")]
+pub(crate) enum Target<'a> {
+    Addr(SocketAddr),
+    Host(&'a str, u16),
+}
+
+/// Open a TCP stream to `target` through the proxy
+#[cfg(feature = "tokio")]
+pub(crate) async fn connect(config: &Socks5Config, target: Target<'_>, lookup: u64) -> Result<TcpStream, Bip353Error> {
+    let io_error = |e: std::io::Error| Bip353Error::TransportError(format!("SOCKS5 proxy error: {}", e));
+    let proxy_error = |msg: &str| Bip353Error::TransportError(format!("SOCKS5 proxy error: {}", msg));
+
+    let credentials = match &config.isolation {
+        StreamIsolation::None => None,
+        StreamIsolation::PerLookup => {
+            let token = format!("bip353-{:016x}", lookup);
+            Some((token.clone(), token))
+        }
+        StreamIsolation::Credentials { username, password } => Some((username.clone(), password.clone())),
+    };
+
+    let mut stream = TcpStream::connect(config.address).await.map_err(io_error)?;
+
+    // Method negotiation: 0x00 no auth, 0x02 username/password
+    let method = if credentials.is_some() { 0x02 } else { 0x00 };
+    stream.write_all(&[0x05, 0x01, method]).await.map_err(io_error)?;
+    let mut reply = [0u8; 2];
+    stream.read_exact(&mut reply).await.map_err(io_error)?;
+    if reply != [0x05, method] {
+        return Err(proxy_error("authentication method rejected"));
+    }
+
+    if let Some((username, password)) = credentials {
+        if username.len() > 255 || password.len() > 255 {
+            return Err(proxy_error("credentials too long"));
+        }
+        let mut auth = vec![0x01, username.len() as u8];
+        auth.extend_from_slice(username.as_bytes());
+        auth.push(password.len() as u8);
+        auth.extend_from_slice(password.as_bytes());
+        stream.write_all(&auth).await.map_err(io_error)?;
+        stream.read_exact(&mut reply).await.map_err(io_error)?;
+        if reply[1] != 0x00 {
+            return Err(proxy_error("authentication failed"));
+        }
+    }
+
+    let mut request = vec![0x05, 0x01, 0x00];
+    let port = match target {
+        Target::Addr(addr) => {
+            match addr.ip() {
+                IpAddr::V4(ip) => {
+                    request.push(0x01);
+                    request.extend_from_slice(&ip.octets());
+                }
+                IpAddr::V6(ip) => {
+                    request.push(0x04);
+                    request.extend_from_slice(&ip.octets());
+                }
+            }
+            addr.port()
+        }
+        Target::Host(host, port) => {
+            if host.len() > 255 {
+                return Err(proxy_error("host name too long"));
+            }
+            request.push(0x03);
+            request.push(host.len() as u8);
+            request.extend_from_slice(host.as_bytes());
+            port
+        }
+    };
+    request.extend_from_slice(&port.to_be_bytes());
+    stream.write_all(&request).await.map_err(io_error)?;
+
+    let mut head = [0u8; 4];
+    stream.read_exact(&mut head).await.map_err(io_error)?;
+    if head[0] != 0x05 {
+        return Err(proxy_error("invalid reply"));
+    }
+    if head[1] != 0x00 {
+        return Err(proxy_error(&format!("connect failed with code {}", head[1])));
+    }
+
+    // Skip the bound address, which we have no use for
+    let addr_len = match head[3] {
+        0x01 => 4,
+        0x04 => 16,
+        0x03 => stream.read_u8().await.map_err(io_error)? as usize,
+        _ => return Err(proxy_error("invalid reply")),
+    };
+    let mut bound = vec![0u8; addr_len + 2];
+    stream.read_exact(&mut bound).await.map_err(io_error)?;
+
+    Ok(stream)
+}
@@ -0,0 +1,300 @@
+//! Persistent storage for the cache and the address book
+//!
+//! `Storage` is a small key-value interface, so each application can keep
+//! resolution results and pinned contacts wherever it keeps its own data.
+//! `MemoryStorage` and the JSON-file based `FileStorage` are provided.
+//!
+//! Values are JSON documents carrying a schema version, with instructions
+//! and pins in their `serde` shapes. Cache entries of
+//! another version are skipped; address book entries are an error rather
+//! than a silently dropped pin.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::cache::Cached;
+use crate::{Bip353Error, HumanReadableName, PaymentInstruction, Pin};
+
+/// Namespace of cached resolution results
+pub(crate) const CACHE_NAMESPACE: &str = "cache";
+/// Namespace of address book pins
+pub(crate) const PINS_NAMESPACE: &str = "address-book";
+
+/// Version of the stored cache entries and pins
+const SCHEMA_VERSION: u32 = 1;
+/// Version of the `FileStorage` file layout
+const FILE_VERSION: u32 = 1;
+
+/// A key-value store, grouped into namespaces
+///
+/// Implementations must be safe to call from several threads; each call
+/// should be durable once it returns.
+pub trait Storage: Send + Sync {
+    fn get(&self, namespace: &str, key: &str) -> Result<Option<Vec<u8>>, Bip353Error>;
+    fn put(&self, namespace: &str, key: &str, value: &[u8]) -> Result<(), Bip353Error>;
+    fn remove(&self, namespace: &str, key: &str) -> Result<(), Bip353Error>;
+    /// Every key and value in `namespace`
+    fn entries(&self, namespace: &str) -> Result<Vec<(String, Vec<u8>)>, Bip353Error>;
+}
+
+/// Encrypts `FileStorage` files at rest
+///
+/// The whole file is passed through the cipher, so names are protected as
+/// well as the records. Key management is left to the application.
+pub trait Cipher: Send + Sync {
+    fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, Bip353Error>;
+    fn decrypt(&self, ciphertext: &[u8]) -> Result<Vec<u8>, Bip353Error>;
+}
+
+fn storage_error(e: impl std::fmt::Display) -> Bip353Error {
+    Bip353Error::StorageError(e.to_string())
+}
+
+type Namespaces = BTreeMap<String, BTreeMap<String, Vec<u8>>>;
+
+/// Storage that lasts as long as the process; useful for tests and for
+/// sharing one cache between resolvers
+#[derive(Default)]
+pub struct MemoryStorage {
+    namespaces: Mutex<Namespaces>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn get(&self, namespace: &str, key: &str) -> Result<Option<Vec<u8>>, Bip353Error> {
+        Ok(self.namespaces.lock().unwrap().get(namespace).and_then(|entries| entries.get(key)).cloned())
+    }
+
+    fn put(&self, namespace: &str, key: &str, value: &[u8]) -> Result<(), Bip353Error> {
+        self.namespaces.lock().unwrap().entry(namespace.to_string()).or_default().insert(key.to_string(), value.to_vec());
+        Ok(())
+    }
+
+    fn remove(&self, namespace: &str, key: &str) -> Result<(), Bip353Error> {
+        if let Some(entries) = self.namespaces.lock().unwrap().get_mut(namespace) {
+            entries.remove(key);
+        }
+        Ok(())
+    }
+
+    fn entries(&self, namespace: &str) -> Result<Vec<(String, Vec<u8>)>, Bip353Error> {
+        let namespaces = self.namespaces.lock().unwrap();
+        Ok(namespaces.get(namespace).map(|entries| entries.clone().into_iter().collect()).unwrap_or_default())
+    }
+}
+
+/// Layout of a `FileStorage` file; values are base64
+#[derive(Serialize, Deserialize)]
+struct StorageFile {
+    version: u32,
+    namespaces: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+/// Storage in a single JSON file, optionally encrypted
+///
+/// The file is read once when opened and rewritten on every change: the
+/// new contents go to a temporary file next to it, which is synced and
+/// then renamed over the old one, so a crash leaves either the old or the
+/// new file. Only one `FileStorage` should use a file at a time.
+pub struct FileStorage {
+    path: PathBuf,
+    cipher: Option<Arc<dyn Cipher>>,
+    namespaces: Mutex<Namespaces>,
+}
+
+impl FileStorage {
+    /// Open the file at `path`, which is created on the first write
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Bip353Error> {
+        Self::load(path.as_ref().to_path_buf(), None)
+    }
+
+    /// Open the file at `path`, encrypted with `cipher`
+    pub fn open_encrypted(path: impl AsRef<Path>, cipher: Arc<dyn Cipher>) -> Result<Self, Bip353Error> {
+        Self::load(path.as_ref().to_path_buf(), Some(cipher))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn load(path: PathBuf, cipher: Option<Arc<dyn Cipher>>) -> Result<Self, Bip353Error> {
+        let namespaces = match fs::read(&path) {
+            Ok(contents) => {
+                let contents = match &cipher {
+                    Some(cipher) => cipher.decrypt(&contents)?,
+                    None => contents,
+                };
+                let file: StorageFile = serde_json::from_slice(&contents)
+                    .map_err(|e| storage_error(format!("{} is not a storage file: {}", path.display(), e)))?;
+                if file.version > FILE_VERSION {
+                    return Err(storage_error(format!(
+                        "{} has version {}, newer than the supported {}", path.display(), file.version, FILE_VERSION,
+                    )));
+                }
+                Self::decode_values(file.namespaces)?
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => Namespaces::new(),
+            Err(e) => return Err(storage_error(format!("Cannot read {}: {}", path.display(), e))),
+        };
+        Ok(Self { path, cipher, namespaces: Mutex::new(namespaces) })
+    }
+
+    fn decode_values(namespaces: BTreeMap<String, BTreeMap<String, String>>) -> Result<Namespaces, Bip353Error> {
+        namespaces.into_iter()
+            .map(|(namespace, entries)| {
+                let entries = entries.into_iter()
+                    .map(|(key, value)| Ok((key, data_encoding::BASE64.decode(value.as_bytes()).map_err(storage_error)?)))
+                    .collect::<Result<_, Bip353Error>>()?;
+                Ok((namespace, entries))
+            })
+            .collect()
+    }
+
+    /// Write `namespaces` to a temporary file and rename it over the file
+    fn save(&self, namespaces: &Namespaces) -> Result<(), Bip353Error> {
+        let file = StorageFile {
+            version: FILE_VERSION,
+            namespaces: namespaces.iter()
+                .map(|(namespace, entries)| {
+                    let entries = entries.iter().map(|(key, value)| (key.clone(), data_encoding::BASE64.encode(value))).collect();
+                    (namespace.clone(), entries)
+                })
+                .collect(),
+        };
+        let mut contents = serde_json::to_vec_pretty(&file).map_err(storage_error)?;
+        if let Some(cipher) = &self.cipher {
+            contents = cipher.encrypt(&contents)?;
+        }
+
+        let mut temp = self.path.clone().into_os_string();
+        temp.push(".tmp");
+        let temp = PathBuf::from(temp);
+        let write = || -> std::io::Result<()> {
+            let mut file = fs::File::create(&temp)?;
+            file.write_all(&contents)?;
+            file.sync_all()?;
+            fs::rename(&temp, &self.path)
+        };
+        write().map_err(|e| storage_error(format!("Cannot write {}: {}", self.path.display(), e)))
+    }
+
+    /// Apply `change` and save, leaving memory untouched if saving fails
+    fn update(&self, change: impl FnOnce(&mut Namespaces)) -> Result<(), Bip353Error> {
+        let mut namespaces = self.namespaces.lock().unwrap();
+        let mut updated = namespaces.clone();
+        change(&mut updated);
+        self.save(&updated)?;
+        *namespaces = updated;
+        Ok(())
+    }
+}
+
+impl Storage for FileStorage {
+    fn get(&self, namespace: &str, key: &str) -> Result<Option<Vec<u8>>, Bip353Error> {
+        Ok(self.namespaces.lock().unwrap().get(namespace).and_then(|entries| entries.get(key)).cloned())
+    }
+
+    fn put(&self, namespace: &str, key: &str, value: &[u8]) -> Result<(), Bip353Error> {
+        self.update(|namespaces| {
+            namespaces.entry(namespace.to_string()).or_default().insert(key.to_string(), value.to_vec());
+        })
+    }
+
+    fn remove(&self, namespace: &str, key: &str) -> Result<(), Bip353Error> {
+        if self.get(namespace, key)?.is_none() {
+            return Ok(());
+        }
+        self.update(|namespaces| {
+            if let Some(entries) = namespaces.get_mut(namespace) {
+                entries.remove(key);
+            }
+        })
+    }
+
+    fn entries(&self, namespace: &str) -> Result<Vec<(String, Vec<u8>)>, Bip353Error> {
+        let namespaces = self.namespaces.lock().unwrap();
+        Ok(namespaces.get(namespace).map(|entries| entries.clone().into_iter().collect()).unwrap_or_default())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+enum StoredCacheEntry {
+    Instruction { instruction: Box<PaymentInstruction> },
+    /// A name proven to have no records
+    Absent { message: String },
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheRecord {
+    version: u32,
+    /// Seconds since the Unix epoch
+    expires: u64,
+    entry: StoredCacheEntry,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PinRecord {
+    version: u32,
+    name: HumanReadableName,
+    pin: Pin,
+}
+
+/// A cache entry expiring at `expires` (seconds since the Unix epoch)
+pub(crate) fn encode_cached(value: &Cached, expires: u64) -> Result<Vec<u8>, Bip353Error> {
+    let entry = match value {
+        Cached::Instruction(instruction) => StoredCacheEntry::Instruction { instruction: instruction.clone() },
+        Cached::Absent(error) => StoredCacheEntry::Absent { message: absent_message(error) },
+    };
+    serde_json::to_vec(&CacheRecord { version: SCHEMA_VERSION, expires, entry }).map_err(storage_error)
+}
+
+/// Only proven absence is cached, so the message is all there is to keep
+fn absent_message(error: &Bip353Error) -> String {
+    match error {
+        Bip353Error::NotFound(msg) => msg.clone(),
+        error => error.to_string(),
+    }
+}
+
+/// A stored cache entry and when it expires, or `None` if it is from
+/// another schema version or unreadable
+pub(crate) fn decode_cached(value: &[u8]) -> Option<(Cached, u64)> {
+    let record: CacheRecord = serde_json::from_slice(value).ok()?;
+    if record.version != SCHEMA_VERSION {
+        return None;
+    }
+    let cached = match record.entry {
+        StoredCacheEntry::Instruction { instruction } => Cached::Instruction(instruction),
+        StoredCacheEntry::Absent { message } => Cached::Absent(Bip353Error::NotFound(message)),
+    };
+    Some((cached, record.expires))
+}
+
+pub(crate) fn encode_pin(name: &HumanReadableName, pin: &Pin) -> Result<Vec<u8>, Bip353Error> {
+    let record = PinRecord { version: SCHEMA_VERSION, name: name.clone(), pin: pin.clone() };
+    serde_json::to_vec(&record).map_err(storage_error)
+}
+
+pub(crate) fn decode_pin(value: &[u8]) -> Result<(HumanReadableName, Pin), Bip353Error> {
+    #[derive(Deserialize)]
+    struct Version {
+        version: u32,
+    }
+    let Version { version } = serde_json::from_slice(value).map_err(storage_error)?;
+    if version != SCHEMA_VERSION {
+        return Err(storage_error(format!("Unsupported address book entry version {}", version)));
+    }
+    let record: PinRecord = serde_json::from_slice(value).map_err(storage_error)?;
+    Ok((record.name, record.pin))
+}
@@ -0,0 +1,76 @@
+//! Plain DNS to the nameservers of a `trust-dns-resolver` configuration
+//!
+//! `trust-dns-resolver` validates DNSSEC itself but drops the RRSIGs and
+//! denials it checked. This transport hands back whole responses instead,
+//! so the system backend validates locally like every other transport.
+
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tokio::net::{TcpStream, UdpSocket};
+use trust_dns_resolver::config::{ResolverConfig, ResolverOpts};
+
+use crate::transport::{self, DnsTransport};
+use crate::Bip353Error;
+
+/// Largest UDP response accepted; the query advertises less
+const MAX_UDP_RESPONSE: usize = 65535;
+
+/// DNS over UDP, retried over TCP when the answer is truncated
+///
+/// The nameservers are tried in order until one answers.
+pub(crate) struct SystemTransport {
+    nameservers: Vec<SocketAddr>,
+    timeout: Duration,
+}
+
+impl SystemTransport {
+    /// Query the nameservers of `config`, waiting `opts.timeout` for each
+    pub fn new(config: &ResolverConfig, opts: &ResolverOpts) -> Self {
+        let mut nameservers = Vec::new();
+        for nameserver in config.name_servers().iter() {
+            if !nameservers.contains(&nameserver.socket_addr) {
+                nameservers.push(nameserver.socket_addr);
+            }
+        }
+        Self { nameservers, timeout: opts.timeout }
+    }
+
+    async fn send(&self, address: SocketAddr, query: &[u8]) -> Result<Vec<u8>, Bip353Error> {
+        let io_error = |e: std::io::Error| Bip353Error::TransportError(format!("DNS I/O error with {}: {}", address, e));
+        let local: SocketAddr = match address {
+            SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+            SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+        };
+        // Connected, so only datagrams from the nameserver are received
+        let socket = UdpSocket::bind(local).await.map_err(io_error)?;
+        socket.connect(address).await.map_err(io_error)?;
+        socket.send(query).await.map_err(io_error)?;
+        let mut response = vec![0u8; MAX_UDP_RESPONSE];
+        let len = socket.recv(&mut response).await.map_err(io_error)?;
+        response.truncate(len);
+
+        // TC flag: the answer did not fit, so ask again over TCP
+        if response.get(2).is_some_and(|flags| flags & 0x02 != 0) {
+            let mut stream = TcpStream::connect(address).await.map_err(io_error)?;
+            return transport::stream_exchange(&mut stream, query).await.map_err(io_error);
+        }
+        Ok(response)
+    }
+}
+
+#[async_trait]
+impl DnsTransport for SystemTransport {
+    async fn exchange(&self, query: &[u8]) -> Result<Vec<u8>, Bip353Error> {
+        let mut error = Bip353Error::TransportError("No nameservers configured".into());
+        for &address in &self.nameservers {
+            match tokio::time::timeout(self.timeout, self.send(address, query)).await {
+                Ok(Ok(response)) => return Ok(response),
+                Ok(Err(e)) => error = e,
+                Err(_) => error = Bip353Error::TransportError(format!("DNS request to {} timed out", address)),
+            }
+        }
+        Err(error)
+    }
+}
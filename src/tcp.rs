@@ -0,0 +1,77 @@
+//! Plain DNS over TCP
+//!
+//! Opens a fresh connection per query so that, behind a SOCKS5 proxy, each
+//! lookup can be given its own Tor circuit.
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+#[cfg(feature = "tokio")]
+use async_trait::async_trait;
+#[cfg(feature = "tokio")]
+use tokio::net::TcpStream;
+
+use crate::socks::Socks5Config;
+#[cfg(feature = "tokio")]
+use crate::socks::{self, Target};
+#[cfg(feature = "tokio")]
+use crate::transport::{self, DnsTransport};
+#[cfg(feature = "tokio")]
+use crate::Bip353Error;
+
+/// DNS-over-TCP settings
+#[derive(Debug, Clone)]
+pub struct TcpConfig {
+    /// Address of the recursive server
+    pub address: SocketAddr,
+    /// Connect through this SOCKS5 proxy (e.g. Tor)
+    pub proxy: Option<Socks5Config>,
+    pub timeout: Duration,
+}
+
+impl TcpConfig {
+    /// Create a config for the server at `address`
+    pub fn new(address: SocketAddr) -> Self {
+        Self { address, proxy: None, timeout: Duration::from_secs(5) }
+    }
+}
+
+/// DNS-over-TCP transport on Tokio
+#[cfg(feature = "tokio")]
+pub struct TcpTransport {
+    config: TcpConfig,
+}
+
+#[cfg(feature = "tokio")]
+impl TcpTransport {
+    /// Create a transport from `config`
+    pub fn new(config: TcpConfig) -> Self {
+        Self { config }
+    }
+
+    async fn send(&self, query: &[u8], lookup: u64) -> Result<Vec<u8>, Bip353Error> {
+        let mut stream = match &self.config.proxy {
+            Some(proxy) => socks::connect(proxy, Target::Addr(self.config.address), lookup).await?,
+            None => TcpStream::connect(self.config.address)
+                .await
+                .map_err(|e| Bip353Error::TransportError(format!("Cannot connect to {}: {}", self.config.address, e)))?,
+        };
+        transport::stream_exchange(&mut stream, query)
+            .await
+            .map_err(|e| Bip353Error::TransportError(format!("TCP I/O error: {}", e)))
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[async_trait]
+impl DnsTransport for TcpTransport {
+    async fn exchange(&self, query: &[u8]) -> Result<Vec<u8>, Bip353Error> {
+        self.exchange_isolated(query, rand::random()).await
+    }
+
+    async fn exchange_isolated(&self, query: &[u8], lookup: u64) -> Result<Vec<u8>, Bip353Error> {
+        tokio::time::timeout(self.config.timeout, self.send(query, lookup))
+            .await
+            .map_err(|_| Bip353Error::TransportError("TCP request timed out".into()))?
+    }
+}
@@ -0,0 +1,1055 @@
+//! Local authoritative DNS stand-in for tests (feature `test-support`)
+//!
+//! `TestServer` serves one or more `TestZone`s over UDP and TCP on one
+//! localhost port, over DoT and DoH, and in process. Each zone is signed
+//! by `signer` with freshly generated Ed25519 keys and an NSEC chain, or
+//! NSEC3 with `TestZone::set_denial`. Hosted zones
+//! are linked with DS records, unless the child is left unsigned or
+//! without one, and the topmost ones become the trust anchors, so a
+//! `Resolver` from `TestServer::resolver` runs the whole resolve path,
+//! validation included, without the internet.
+//!
+//! ```no_run
+//! # async fn example() -> Result<(), bip353::Bip353Error> {
+//! use bip353::testing::{TestServer, TestZone};
+//!
+//! let mut zone = TestZone::new("example.com.")?;
+//! zone.add_payment_instruction("alice", "bitcoin:?lno=lno1qsgqmqvgm96frzdg8m0gc6n")?;
+//! let server = TestServer::start(vec![zone]).await?;
+//!
+//! let instruction = server.resolver()?.resolve("alice", "example.com").await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::task::JoinHandle;
+use tokio_rustls::TlsAcceptor;
+use trust_dns_proto::op::{Edns, Message, MessageType, OpCode, ResponseCode};
+use trust_dns_proto::rr::dnssec::rdata::DNSSECRData;
+use trust_dns_proto::rr::dnssec::{Algorithm, Nsec3HashAlgorithm};
+use trust_dns_proto::rr::rdata::{NULL, SOA, TXT};
+use trust_dns_proto::rr::{DNSClass, Name, RData, Record, RecordType};
+use trust_dns_proto::serialize::binary::{BinEncodable, BinEncoder};
+use trust_dns_resolver::config::{NameServerConfigGroup, ResolverConfig, ResolverOpts};
+
+use crate::{alias, dnssec, onion, transport};
+use crate::signer::{covered_type, DenialConfig, SignerConfig, SigningKey, ZoneSigner};
+use crate::{
+    AlgorithmPolicy, Bip353Config, Bip353Error, DnsTransport, DohConfig, DohMethod, DotConfig, Resolver, TcpConfig,
+    TransportConfig, TrustAnchors, TsigKey,
+};
+
+/// TTL of records added through the `TestZone` helpers
+pub const TTL: u32 = 3600;
+
+/// SOA minimum of generated SOA records, i.e. the negative caching TTL
+pub const NEGATIVE_TTL: u32 = 300;
+
+/// Largest UDP response without EDNS (RFC 1035 4.2.1)
+const UDP_PAYLOAD: usize = 512;
+
+/// Host name the DoT and DoH listeners' self-signed certificate is issued for
+pub const TLS_HOST: &str = "dns.test";
+
+fn invalid_name(e: impl std::fmt::Display) -> Bip353Error {
+    Bip353Error::InvalidAddress(e.to_string())
+}
+
+fn parse_name(name: &str) -> Result<Name, Bip353Error> {
+    let mut name = Name::from_utf8(name).map_err(invalid_name)?;
+    name.set_fqdn(true);
+    Ok(name)
+}
+
+/// A zone to be signed: an apex and its records
+///
+/// A SOA and NS record are added at the apex unless present.
+#[derive(Debug, Clone)]
+pub struct TestZone {
+    apex: Name,
+    records: Vec<Record>,
+    update_key: Option<TsigKey>,
+    denial: DenialConfig,
+    denial_proofs: bool,
+    signed: bool,
+    ds: bool,
+    /// Algorithm and PKCS#8 document of the KSK and the ZSK, if not generated
+    keys: Option<[(Algorithm, Vec<u8>); 2]>,
+    validity: Duration,
+}
+
+impl TestZone {
+    /// Create an empty zone at `apex`, e.g. `example.com.`
+    pub fn new(apex: &str) -> Result<Self, Bip353Error> {
+        Ok(Self {
+            apex: parse_name(apex)?,
+            records: Vec::new(),
+            update_key: None,
+            denial: DenialConfig::Nsec,
+            denial_proofs: true,
+            signed: true,
+            ds: true,
+            keys: None,
+            validity: SignerConfig::default().validity,
+        })
+    }
+
+    /// Prove denials with `denial` rather than an NSEC chain
+    ///
+    /// With NSEC3 opt-out, unsigned child zones get no NSEC3 record and
+    /// are proven insecure by the opt-out span covering them instead.
+    pub fn set_denial(&mut self, denial: DenialConfig) -> &mut Self {
+        self.denial = denial;
+        self
+    }
+
+    /// Answer without NSEC or NSEC3 records, so denials and wildcard
+    /// expansions come unproven
+    pub fn set_without_denial_proofs(&mut self) -> &mut Self {
+        self.denial_proofs = false;
+        self
+    }
+
+    /// Serve the zone without DNSSEC, and delegate it from a hosted parent
+    /// without a DS record
+    pub fn set_unsigned(&mut self) -> &mut Self {
+        self.signed = false;
+        self.ds = false;
+        self
+    }
+
+    /// Sign the zone, but delegate it from a hosted parent without a DS
+    /// record, as if its DS was never uploaded
+    pub fn set_without_ds(&mut self) -> &mut Self {
+        self.ds = false;
+        self
+    }
+
+    /// Sign with `ksk` and `zsk` rather than freshly generated keys, e.g. to
+    /// serve the zone again after rolling one of them
+    pub fn set_keys(&mut self, ksk: &SigningKey, zsk: &SigningKey) -> &mut Self {
+        let key = |key: &SigningKey| (key.dnskey().algorithm(), key.to_pkcs8().to_vec());
+        self.keys = Some([key(ksk), key(zsk)]);
+        self
+    }
+
+    /// Make signatures expire `validity` after signing rather than in 30 days
+    pub fn set_signature_validity(&mut self, validity: Duration) -> &mut Self {
+        self.validity = validity;
+        self
+    }
+
+    /// Accept RFC 2136 updates to this zone signed with `key`
+    ///
+    /// The zone is signed again after every update.
+    pub fn allow_updates(&mut self, key: TsigKey) -> &mut Self {
+        self.update_key = Some(key);
+        self
+    }
+
+    /// The zone apex
+    pub fn apex(&self) -> &Name {
+        &self.apex
+    }
+
+    /// Add any record, which must be at or below the apex
+    pub fn add_record(&mut self, record: Record) -> Result<&mut Self, Bip353Error> {
+        if !self.apex.zone_of(record.name()) {
+            return Err(invalid_name(format!("{} is not in zone {}", record.name(), self.apex)));
+        }
+        self.records.push(record);
+        Ok(self)
+    }
+
+    /// Add a TXT record made of `strings` at `name`
+    pub fn add_txt(&mut self, name: &str, strings: &[&str]) -> Result<&mut Self, Bip353Error> {
+        let txt = TXT::new(strings.iter().map(|s| s.to_string()).collect());
+        self.add_record(Record::from_rdata(parse_name(name)?, TTL, RData::TXT(txt)))
+    }
+
+    /// Publish `uri` for `user`, split into 255-byte character-strings
+    pub fn add_payment_instruction(&mut self, user: &str, uri: &str) -> Result<&mut Self, Bip353Error> {
+        self.add_record(crate::payment_record(user, &self.apex.to_string(), uri, TTL)?)
+    }
+
+    /// Add a CNAME at `name` pointing at `target`
+    pub fn add_cname(&mut self, name: &str, target: &str) -> Result<&mut Self, Bip353Error> {
+        self.add_record(Record::from_rdata(parse_name(name)?, TTL, RData::CNAME(parse_name(target)?)))
+    }
+
+    /// Add a DNAME at `owner` (RFC 6672), redirecting every name below it
+    /// to the same name below `target`
+    pub fn add_dname(&mut self, owner: &str, target: &str) -> Result<&mut Self, Bip353Error> {
+        let mut rdata = Vec::new();
+        parse_name(target)?.to_lowercase().emit(&mut BinEncoder::new(&mut rdata)).map_err(invalid_name)?;
+        let dname = RData::Unknown { code: u16::from(alias::DNAME), rdata: NULL::with(rdata) };
+        self.add_record(Record::from_rdata(parse_name(owner)?, TTL, dname))
+    }
+}
+
+/// A hosted zone and its keys; `records` are signed once all zones are linked
+struct HostedZone {
+    apex: Name,
+    signer: ZoneSigner,
+    denial: DenialConfig,
+    denial_proofs: bool,
+    signed: bool,
+    /// Delegated from the hosted parent with a DS record
+    ds: bool,
+    update_key: Option<TsigKey>,
+    /// The zone as last updated, before signing
+    unsigned: Vec<Record>,
+    records: Vec<Record>,
+}
+
+impl HostedZone {
+    /// Records at `name` of `record_type`, with their RRSIGs
+    fn rrset<'a>(&'a self, name: &'a Name, record_type: RecordType) -> impl Iterator<Item = &'a Record> + 'a {
+        self.records.iter().filter(move |r| r.name() == name && covered_type(r) == record_type)
+    }
+
+    fn exists(&self, name: &Name) -> bool {
+        self.records.iter().any(|r| name.zone_of(r.name()))
+    }
+
+    /// The NSEC (and its RRSIG) whose owner is `name` or, failing that,
+    /// covers it
+    fn nsec_for(&self, name: &Name) -> Vec<Record> {
+        let owner = self.records.iter()
+            .filter(|r| r.record_type() == RecordType::NSEC && r.name() <= name)
+            .map(Record::name)
+            .max()
+            .unwrap_or(&self.apex);
+        self.rrset(owner, RecordType::NSEC).cloned().collect()
+    }
+
+    /// The NSEC3 (and its RRSIG) whose owner is the hash of `name` or,
+    /// failing that, covers it
+    fn nsec3_for(&self, name: &Name) -> Vec<Record> {
+        let DenialConfig::Nsec3 { iterations, salt, .. } = &self.denial else { return Vec::new() };
+        let Ok(hash) = Nsec3HashAlgorithm::SHA1.hash(salt, name, *iterations) else { return Vec::new() };
+        let label = data_encoding::BASE32HEX_NOPAD.encode(hash.as_ref()).to_ascii_lowercase();
+
+        // Base32hex sorts like the hashes; the first NSEC3 covers the wrap-around
+        let owners: Vec<(String, &Name)> = self.records.iter()
+            .filter(|r| r.record_type() == RecordType::NSEC3)
+            .filter_map(|r| Some((String::from_utf8_lossy(r.name().iter().next()?).to_ascii_lowercase(), r.name())))
+            .collect();
+        let owner = owners.iter()
+            .filter(|(owner, _)| *owner <= label)
+            .max()
+            .or_else(|| owners.iter().max())
+            .map(|(_, owner)| *owner);
+        owner.map_or_else(Vec::new, |owner| self.rrset(owner, RecordType::NSEC3).cloned().collect())
+    }
+
+    /// Does `name` have an NSEC3 record of its own?
+    fn nsec3_matches(&self, name: &Name) -> bool {
+        let DenialConfig::Nsec3 { iterations, salt, .. } = &self.denial else { return false };
+        let Ok(hash) = Nsec3HashAlgorithm::SHA1.hash(salt, name, *iterations) else { return false };
+        let label = data_encoding::BASE32HEX_NOPAD.encode(hash.as_ref());
+        self.records.iter().any(|r| {
+            r.record_type() == RecordType::NSEC3
+                && r.name().iter().next().is_some_and(|owner| owner.eq_ignore_ascii_case(label.as_bytes()))
+        })
+    }
+
+    /// NSEC3 closest encloser proof for `name` (RFC 5155 7.2.1): the NSEC3
+    /// of its deepest ancestor that has one, and the NSEC3 covering the
+    /// next closer name
+    fn nsec3_closest_encloser(&self, name: &Name) -> Vec<Record> {
+        let closest_encloser = (self.apex.num_labels()..name.num_labels())
+            .rev()
+            .map(|labels| name.trim_to(labels as usize))
+            .find(|ancestor| self.nsec3_matches(ancestor));
+        let Some(closest_encloser) = closest_encloser else { return Vec::new() };
+        let next_closer = name.trim_to(closest_encloser.num_labels() as usize + 1);
+        let mut proof = self.nsec3_for(&closest_encloser);
+        extend_unique(&mut proof, self.nsec3_for(&next_closer));
+        proof
+    }
+
+    /// Proof that `name`, which exists, has no RRset of the queried type
+    ///
+    /// An NSEC3 opt-out delegation has no NSEC3 of its own, so it gets the
+    /// closest encloser proof instead (RFC 5155 7.2.4).
+    fn nodata_proof(&self, name: &Name) -> Vec<Record> {
+        match self.denial {
+            DenialConfig::Nsec => self.nsec_for(name),
+            DenialConfig::Nsec3 { .. } if self.nsec3_matches(name) => self.nsec3_for(name),
+            DenialConfig::Nsec3 { .. } => self.nsec3_closest_encloser(name),
+        }
+    }
+
+    /// Proof that `name` does not exist, nor the wildcard that would match it
+    fn nxdomain_proof(&self, name: &Name, wildcard: Option<&Name>) -> Vec<Record> {
+        let mut proof = match self.denial {
+            DenialConfig::Nsec => self.nsec_for(name),
+            DenialConfig::Nsec3 { .. } => self.nsec3_closest_encloser(name),
+        };
+        if let Some(wildcard) = wildcard {
+            match self.denial {
+                DenialConfig::Nsec => extend_unique(&mut proof, self.nsec_for(wildcard)),
+                DenialConfig::Nsec3 { .. } => extend_unique(&mut proof, self.nsec3_for(wildcard)),
+            }
+        }
+        proof
+    }
+
+    /// Proof that no name closer to `name` than `closest_encloser` exists,
+    /// for a wildcard expansion
+    fn no_closer_proof(&self, name: &Name, closest_encloser: &Name) -> Vec<Record> {
+        match self.denial {
+            DenialConfig::Nsec => self.nsec_for(name),
+            DenialConfig::Nsec3 { .. } => self.nsec3_for(&name.trim_to(closest_encloser.num_labels() as usize + 1)),
+        }
+    }
+
+    /// Answer `name`/`record_type` from this zone
+    fn answer(&self, name: &Name, record_type: RecordType, response: &mut Message) {
+        let answers: Vec<Record> = self.rrset(name, record_type).cloned().collect();
+        if !answers.is_empty() {
+            response.add_answers(answers);
+            return;
+        }
+        // Below a DNAME: the DNAME and the CNAME synthesized from it (RFC 6672 3.1)
+        if let Some(step) = alias::step(&self.records, name, record_type).filter(|step| step.record_type == alias::DNAME) {
+            let dname: Vec<Record> = self.rrset(&step.owner, alias::DNAME).cloned().collect();
+            response.add_answers(dname);
+            if let Some(target) = step.target {
+                response.add_answer(Record::from_rdata(name.clone(), TTL, RData::CNAME(target)));
+            }
+            return;
+        }
+        let cname: Vec<Record> = self.rrset(name, RecordType::CNAME).cloned().collect();
+        if !cname.is_empty() {
+            response.add_answers(cname);
+            return;
+        }
+
+        let closest_encloser = (0..=name.num_labels())
+            .rev()
+            .map(|labels| name.trim_to(labels as usize))
+            .find(|ancestor| self.exists(ancestor))
+            .unwrap_or_else(|| self.apex.clone());
+        let wildcard = Name::from_ascii("*").and_then(|star| star.append_domain(&closest_encloser)).ok();
+        let mut authority: Vec<Record> = self.rrset(&self.apex, RecordType::SOA).cloned().collect();
+
+        if self.exists(name) {
+            // NODATA
+            authority.extend(self.nodata_proof(name));
+        } else if let Some(wildcard) = wildcard.as_ref().filter(|w| self.rrset(w, record_type).next().is_some()) {
+            // Wildcard expansion, with the proof that `name` itself does not exist
+            for record in self.rrset(wildcard, record_type) {
+                let mut record = record.clone();
+                record.set_name(name.clone());
+                response.add_answer(record);
+            }
+            response.add_name_servers(self.no_closer_proof(name, &closest_encloser));
+            return;
+        } else {
+            // NXDOMAIN: neither `name` nor the wildcard that would match it exists
+            response.set_response_code(ResponseCode::NXDomain);
+            authority.extend(self.nxdomain_proof(name, wildcard.as_ref()));
+        }
+        response.add_name_servers(authority);
+    }
+}
+
+/// Append the `records` not already in `to`
+fn extend_unique(to: &mut Vec<Record>, records: Vec<Record>) {
+    for record in records {
+        if !to.contains(&record) {
+            to.push(record);
+        }
+    }
+}
+
+/// Signed zones, ready to answer queries
+struct Zones(Vec<HostedZone>);
+
+impl Zones {
+    fn sign(zones: Vec<TestZone>) -> Result<Self, Bip353Error> {
+        let now = dnssec::now();
+        let mut signed = Vec::new();
+        for zone in zones {
+            let (ksk, zsk) = match &zone.keys {
+                Some([(ksk_algorithm, ksk), (zsk_algorithm, zsk)]) => (
+                    SigningKey::from_pkcs8(*ksk_algorithm, ksk, true)?,
+                    SigningKey::from_pkcs8(*zsk_algorithm, zsk, false)?,
+                ),
+                None => (SigningKey::generate(Algorithm::ED25519, true)?, SigningKey::generate(Algorithm::ED25519, false)?),
+            };
+            let config = SignerConfig { denial: zone.denial.clone(), validity: zone.validity };
+            let signer = ZoneSigner::new(zone.apex.clone(), ksk, zsk, config);
+            let mut records = zone.records;
+            let ns = Name::from_ascii("ns").and_then(|ns| ns.append_domain(&zone.apex)).map_err(invalid_name)?;
+            if !records.iter().any(|r| r.name() == &zone.apex && r.record_type() == RecordType::SOA) {
+                let hostmaster = Name::from_ascii("hostmaster").and_then(|h| h.append_domain(&zone.apex))
+                    .map_err(invalid_name)?;
+                let soa = SOA::new(ns.clone(), hostmaster, 1, 3600, 600, 86400, NEGATIVE_TTL);
+                records.push(Record::from_rdata(zone.apex.clone(), TTL, RData::SOA(soa)));
+            }
+            if !records.iter().any(|r| r.name() == &zone.apex && r.record_type() == RecordType::NS) {
+                records.push(Record::from_rdata(zone.apex.clone(), TTL, RData::NS(ns)));
+            }
+            signed.push(HostedZone {
+                apex: zone.apex,
+                signer,
+                denial: zone.denial,
+                denial_proofs: zone.denial_proofs,
+                signed: zone.signed,
+                ds: zone.ds,
+                update_key: zone.update_key,
+                unsigned: Vec::new(),
+                records,
+            });
+        }
+
+        // Delegate each zone from its closest hosted ancestor
+        for i in 0..signed.len() {
+            let apex = signed[i].apex.clone();
+            let ds = signed[i].signer.ksk().ds(&apex)?;
+            let ns = signed[i].records.iter()
+                .filter(|r| r.name() == &apex && r.record_type() == RecordType::NS)
+                .cloned()
+                .collect::<Vec<_>>();
+            if let Some(parent) = Self::parent_index(&signed, &apex) {
+                signed[parent].records.extend(ns);
+                if signed[i].ds {
+                    signed[parent].records.push(Record::from_rdata(apex, TTL, RData::DNSSEC(DNSSECRData::DS(ds))));
+                }
+            }
+        }
+        for zone in &mut signed {
+            zone.unsigned = std::mem::take(&mut zone.records);
+            zone.records = if zone.signed {
+                zone.signer.sign_at(&zone.unsigned, now)?.into_records()
+            } else {
+                zone.unsigned.clone()
+            };
+        }
+        Ok(Self(signed))
+    }
+
+    /// The deepest other zone above `apex`
+    fn parent_index(zones: &[HostedZone], apex: &Name) -> Option<usize> {
+        zones.iter()
+            .enumerate()
+            .filter(|(_, zone)| zone.apex != *apex && zone.apex.zone_of(apex))
+            .max_by_key(|(_, zone)| zone.apex.num_labels())
+            .map(|(i, _)| i)
+    }
+
+    /// The zone answering for `name`; a DS lives on the parent side of a cut
+    fn zone_for(&self, name: &Name, record_type: RecordType) -> Option<&HostedZone> {
+        let deepest = self.0.iter()
+            .filter(|zone| zone.apex.zone_of(name))
+            .max_by_key(|zone| zone.apex.num_labels())?;
+        if record_type == RecordType::DS && deepest.apex == *name {
+            if let Some(parent) = Self::parent_index(&self.0, name) {
+                return Some(&self.0[parent]);
+            }
+        }
+        Some(deepest)
+    }
+
+    /// Answer `name`/`record_type` from the zone responsible for it
+    fn answer(&self, name: &Name, record_type: RecordType, response: &mut Message) {
+        let Some(zone) = self.zone_for(name, record_type) else {
+            response.set_response_code(ResponseCode::Refused);
+            return;
+        };
+        zone.answer(name, record_type, response);
+        if !zone.denial_proofs {
+            let authority: Vec<Record> = response.take_name_servers()
+                .into_iter()
+                .filter(|r| !matches!(covered_type(r), RecordType::NSEC | RecordType::NSEC3))
+                .collect();
+            response.add_name_servers(authority);
+        }
+    }
+
+    /// The answer and authority records for `name`/`record_type`
+    fn lookup(&self, name: &Name, record_type: RecordType) -> Vec<Record> {
+        let mut response = Message::new();
+        self.answer(name, record_type, &mut response);
+        let mut records = response.take_answers();
+        records.extend(response.take_name_servers());
+        records
+    }
+
+    /// Answer a wire-format query or update; UDP responses are truncated
+    /// to fit
+    fn respond(&mut self, query: &[u8], udp: bool) -> Option<Vec<u8>> {
+        let request = Message::from_vec(query).ok()?;
+        if request.op_code() == OpCode::Update {
+            return self.update(query, &request);
+        }
+        let mut response = Message::new();
+        response
+            .set_id(request.id())
+            .set_message_type(MessageType::Response)
+            .set_op_code(request.op_code())
+            .set_authoritative(true)
+            .set_recursion_desired(request.recursion_desired())
+            .set_recursion_available(true)
+            .set_checking_disabled(request.checking_disabled());
+        let max_size = request.extensions().as_ref().map_or(UDP_PAYLOAD, |edns| usize::from(edns.max_payload()));
+        if let Some(edns) = request.extensions() {
+            let mut reply = Edns::new();
+            reply.set_dnssec_ok(edns.dnssec_ok());
+            reply.set_max_payload(edns.max_payload());
+            response.set_edns(reply);
+        }
+
+        match request.queries() {
+            [question] => {
+                response.add_query(question.clone());
+                self.answer(question.name(), question.query_type(), &mut response);
+            }
+            _ => {
+                response.set_response_code(ResponseCode::FormErr);
+            }
+        }
+
+        let bytes = response.to_vec().ok()?;
+        if udp && bytes.len() > max_size.max(UDP_PAYLOAD) {
+            // Too big: send just the header and question, the client retries over TCP
+            let mut truncated = response.truncate();
+            truncated.set_truncated(true);
+            return truncated.to_vec().ok();
+        }
+        Some(bytes)
+    }
+}
+
+impl Zones {
+    /// Apply a signed RFC 2136 update to a zone that allows them
+    fn update(&mut self, raw: &[u8], request: &Message) -> Option<Vec<u8>> {
+        let mut response = Message::new();
+        response
+            .set_id(request.id())
+            .set_message_type(MessageType::Response)
+            .set_op_code(OpCode::Update)
+            .add_queries(request.queries().to_vec());
+
+        let zone = match request.queries() {
+            [zone] if zone.query_type() == RecordType::SOA => self.0.iter_mut().find(|z| z.apex == *zone.name()),
+            _ => {
+                response.set_response_code(ResponseCode::FormErr);
+                return response.to_vec().ok();
+            }
+        };
+        let Some(zone) = zone else {
+            response.set_response_code(ResponseCode::NotAuth);
+            return response.to_vec().ok();
+        };
+        let Some(key) = zone.update_key.clone() else {
+            response.set_response_code(ResponseCode::Refused);
+            return response.to_vec().ok();
+        };
+        // Bad or missing signatures get an unsigned NOTAUTH (RFC 8945 5.3.2)
+        let Ok(request_mac) = key.verify(raw, None) else {
+            response.set_response_code(ResponseCode::NotAuth);
+            return response.to_vec().ok();
+        };
+
+        let code = zone.apply(request.answers(), request.name_servers());
+        response.set_response_code(code);
+        key.sign(&mut response, Some(&request_mac)).ok()?;
+        response.to_vec().ok()
+    }
+}
+
+impl HostedZone {
+    /// Check `prerequisites`, apply `updates` and sign the zone again
+    /// (RFC 2136 3.2 to 3.4)
+    fn apply(&mut self, prerequisites: &[Record], updates: &[Record]) -> ResponseCode {
+        for record in prerequisites.iter().chain(updates) {
+            if !self.apex.zone_of(record.name()) {
+                return ResponseCode::NotZone;
+            }
+        }
+        for prerequisite in prerequisites {
+            let name = prerequisite.name();
+            let in_use = self.unsigned.iter().any(|r| r.name() == name);
+            let rrset = self.unsigned.iter().any(|r| r.name() == name && r.record_type() == prerequisite.record_type());
+            let (holds, failure) = match (prerequisite.dns_class(), prerequisite.record_type()) {
+                (DNSClass::ANY, RecordType::ANY) => (in_use, ResponseCode::NXDomain),
+                (DNSClass::ANY, _) => (rrset, ResponseCode::NXRRSet),
+                (DNSClass::NONE, RecordType::ANY) => (!in_use, ResponseCode::YXDomain),
+                (DNSClass::NONE, _) => (!rrset, ResponseCode::YXRRSet),
+                // Value-dependent prerequisites are not supported
+                _ => return ResponseCode::FormErr,
+            };
+            if !holds {
+                return failure;
+            }
+        }
+
+        let apex = self.apex.clone();
+        for update in updates {
+            let name = update.name();
+            let record_type = update.record_type();
+            // The apex SOA and NS records are not removable this way
+            let protected = |r: &Record| r.name() == &apex && matches!(r.record_type(), RecordType::SOA | RecordType::NS);
+            match update.dns_class() {
+                DNSClass::IN => {
+                    if !self.unsigned.iter().any(|r| r.name() == name && r.data() == update.data()) {
+                        self.unsigned.push(update.clone());
+                    }
+                }
+                DNSClass::ANY => self.unsigned.retain(|r| {
+                    r.name() != name || protected(r) || (record_type != RecordType::ANY && r.record_type() != record_type)
+                }),
+                DNSClass::NONE => self.unsigned.retain(|r| r.name() != name || r.data() != update.data() || protected(r)),
+                _ => return ResponseCode::FormErr,
+            }
+        }
+
+        for record in &mut self.unsigned {
+            if let Some(RData::SOA(soa)) = record.data_mut() {
+                soa.increment_serial();
+            }
+        }
+        if !self.signed {
+            self.records = self.unsigned.clone();
+            return ResponseCode::NoError;
+        }
+        match self.signer.sign(&self.unsigned) {
+            Ok(signed) => {
+                self.records = signed.into_records();
+                ResponseCode::NoError
+            }
+            Err(_) => ResponseCode::ServFail,
+        }
+    }
+}
+
+/// An in-process authoritative server for signed test zones
+///
+/// Besides UDP and TCP, the server answers DoT and DoH with a self-signed
+/// certificate for `TLS_HOST`, and in process through `transport`. It
+/// stops when dropped.
+pub struct TestServer {
+    udp_addr: SocketAddr,
+    tcp_addr: SocketAddr,
+    dot_addr: SocketAddr,
+    doh_addr: SocketAddr,
+    certificate: Vec<u8>,
+    trust_anchors: TrustAnchors,
+    dnskey_anchors: String,
+    zones: Arc<Mutex<Zones>>,
+    tls: Arc<TlsListeners>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+/// A DoH request received by a `TestServer`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DohRequest {
+    pub method: DohMethod,
+    /// ID of the DNS query, 0 as RFC 8484 4.1 recommends
+    pub id: u16,
+}
+
+/// Counters and settings shared with the DoT and DoH listeners
+struct TlsListeners {
+    dot_connections: AtomicUsize,
+    dot_queries_per_connection: AtomicUsize,
+    doh_padding: AtomicUsize,
+    doh_requests: Mutex<Vec<DohRequest>>,
+}
+
+impl TestServer {
+    /// Sign `zones` and serve them on random localhost ports
+    pub async fn start(zones: Vec<TestZone>) -> Result<Self, Bip353Error> {
+        let zones = Zones::sign(zones)?;
+        let mut trust_anchors = TrustAnchors::new();
+        let mut dnskey_anchors = String::new();
+        for zone in &zones.0 {
+            if zone.signed && Zones::parent_index(&zones.0, &zone.apex).is_none() {
+                let ksk = zone.signer.ksk().dnskey();
+                trust_anchors.add_ds(zone.apex.clone(), zone.signer.ksk().ds(&zone.apex)?);
+                dnskey_anchors.push_str(&format!(
+                    "{} {} IN DNSKEY 257 3 {} {}\n",
+                    zone.apex,
+                    TTL,
+                    u8::from(ksk.algorithm()),
+                    data_encoding::BASE64.encode(ksk.public_key()),
+                ));
+            }
+        }
+        let zones = Arc::new(Mutex::new(zones));
+
+        let io_error = |e: std::io::Error| Bip353Error::TransportError(format!("Test server I/O error: {}", e));
+        // UDP and TCP share a port, so clients can retry truncated answers
+        // over TCP as they would with a real server
+        let mut attempts = 0;
+        let (udp, tcp) = loop {
+            let udp = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.map_err(io_error)?;
+            let port = udp.local_addr().map_err(io_error)?.port();
+            match TcpListener::bind((Ipv4Addr::LOCALHOST, port)).await {
+                Ok(tcp) => break (udp, tcp),
+                Err(e) if attempts == 10 => return Err(io_error(e)),
+                Err(_) => attempts += 1,
+            }
+        };
+        let udp_addr = udp.local_addr().map_err(io_error)?;
+        let tcp_addr = tcp.local_addr().map_err(io_error)?;
+        let dot = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.map_err(io_error)?;
+        let doh = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.map_err(io_error)?;
+        let dot_addr = dot.local_addr().map_err(io_error)?;
+        let doh_addr = doh.local_addr().map_err(io_error)?;
+
+        let cert = rcgen::generate_simple_self_signed(vec![TLS_HOST.to_string()]).map_err(tls_error)?;
+        let certificate = cert.serialize_der().map_err(tls_error)?;
+        let key = cert.serialize_private_key_der();
+        let dot_config = tls_config(&certificate, &key, b"dot")?;
+        let doh_config = tls_config(&certificate, &key, b"http/1.1")?;
+        let tls = Arc::new(TlsListeners {
+            dot_connections: AtomicUsize::new(0),
+            dot_queries_per_connection: AtomicUsize::new(usize::MAX),
+            doh_padding: AtomicUsize::new(0),
+            doh_requests: Mutex::new(Vec::new()),
+        });
+
+        let udp_zones = zones.clone();
+        let udp_task = tokio::spawn(async move {
+            let mut buf = vec![0u8; 65535];
+            while let Ok((len, peer)) = udp.recv_from(&mut buf).await {
+                let response = udp_zones.lock().unwrap().respond(&buf[..len], true);
+                if let Some(response) = response {
+                    let _ = udp.send_to(&response, peer).await;
+                }
+            }
+        });
+        let tcp_zones = zones.clone();
+        let tcp_task = tokio::spawn(async move {
+            while let Ok((mut stream, _)) = tcp.accept().await {
+                let zones = tcp_zones.clone();
+                tokio::spawn(async move { serve_stream(&mut stream, &zones, usize::MAX).await });
+            }
+        });
+        let (dot_zones, dot_tls, acceptor) = (zones.clone(), tls.clone(), TlsAcceptor::from(dot_config));
+        let dot_task = tokio::spawn(async move {
+            while let Ok((stream, _)) = dot.accept().await {
+                dot_tls.dot_connections.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(serve_dot(stream, acceptor.clone(), dot_zones.clone(), dot_tls.clone()));
+            }
+        });
+        let (doh_zones, doh_tls, acceptor) = (zones.clone(), tls.clone(), TlsAcceptor::from(doh_config));
+        let doh_task = tokio::spawn(async move {
+            while let Ok((stream, _)) = doh.accept().await {
+                tokio::spawn(serve_doh(stream, acceptor.clone(), doh_zones.clone(), doh_tls.clone()));
+            }
+        });
+
+        Ok(Self {
+            udp_addr,
+            tcp_addr,
+            dot_addr,
+            doh_addr,
+            certificate,
+            trust_anchors,
+            dnskey_anchors,
+            zones,
+            tls,
+            tasks: vec![udp_task, tcp_task, dot_task, doh_task],
+        })
+    }
+
+    /// Address of the UDP listener
+    pub fn udp_addr(&self) -> SocketAddr {
+        self.udp_addr
+    }
+
+    /// Address of the TCP listener
+    pub fn tcp_addr(&self) -> SocketAddr {
+        self.tcp_addr
+    }
+
+    /// Address of the DoT listener
+    pub fn dot_addr(&self) -> SocketAddr {
+        self.dot_addr
+    }
+
+    /// Address of the DoH listener
+    pub fn doh_addr(&self) -> SocketAddr {
+        self.doh_addr
+    }
+
+    /// The DER-encoded self-signed certificate of the DoT and DoH listeners
+    pub fn certificate(&self) -> &[u8] {
+        &self.certificate
+    }
+
+    /// DS anchors for the signed zones that have no hosted parent
+    pub fn trust_anchors(&self) -> TrustAnchors {
+        self.trust_anchors.clone()
+    }
+
+    /// The same anchors as `trust_anchors`, as DNSKEY records in
+    /// presentation format for `TrustAnchors::parse`
+    pub fn dnskey_anchors(&self) -> &str {
+        &self.dnskey_anchors
+    }
+
+    /// A config querying this server over TCP and trusting its anchors
+    pub fn config(&self) -> Bip353Config {
+        Bip353Config {
+            transport: TransportConfig::Tcp(TcpConfig::new(self.tcp_addr)),
+            trust_anchors: self.trust_anchors(),
+            ..Default::default()
+        }
+    }
+
+    /// A resolver built from `config`
+    pub fn resolver(&self) -> Result<Resolver, Bip353Error> {
+        Resolver::from_config(self.config())
+    }
+
+    /// A resolver on the system backend, querying this server over UDP
+    /// (TCP for truncated answers) as its only nameserver
+    pub fn system_resolver(&self) -> Resolver {
+        self.system_resolver_with_policy(AlgorithmPolicy::default())
+    }
+
+    /// Like `system_resolver`, applying `algorithm_policy`
+    pub fn system_resolver_with_policy(&self, algorithm_policy: AlgorithmPolicy) -> Resolver {
+        let nameservers = NameServerConfigGroup::from_ips_clear(&[self.udp_addr.ip()], self.udp_addr.port(), true);
+        Resolver::system(
+            &ResolverConfig::from_parts(None, Vec::new(), nameservers),
+            &ResolverOpts::default(),
+            Bip353Config { trust_anchors: self.trust_anchors(), algorithm_policy, ..Default::default() },
+        )
+    }
+
+    /// A transport answering from this server's zones in process, without
+    /// sockets or a Tokio runtime
+    ///
+    /// Each transport counts the queries it was sent.
+    pub fn transport(&self) -> Arc<TestTransport> {
+        Arc::new(TestTransport { zones: self.zones.clone(), queries: AtomicUsize::new(0) })
+    }
+
+    /// A config querying this server over DoH with `method`, trusting its
+    /// certificate
+    pub fn doh_config(&self, method: DohMethod) -> DohConfig {
+        let mut config = DohConfig::new(format!("https://{}:{}/dns-query", TLS_HOST, self.doh_addr.port()));
+        config.bootstrap_ips = vec![self.doh_addr.ip()];
+        config.root_certificates = vec![self.certificate.clone()];
+        config.method = method;
+        config
+    }
+
+    /// The DoH requests received so far, in order
+    pub fn doh_requests(&self) -> Vec<DohRequest> {
+        self.tls.doh_requests.lock().unwrap().clone()
+    }
+
+    /// Append `bytes` zero bytes to every DoH response body
+    pub fn set_doh_padding(&self, bytes: usize) {
+        self.tls.doh_padding.store(bytes, Ordering::SeqCst);
+    }
+
+    /// A config querying this server over DoT, trusting its certificate
+    pub fn dot_config(&self) -> DotConfig {
+        let mut config = DotConfig::new(self.dot_addr, TLS_HOST);
+        config.root_certificates = vec![self.certificate.clone()];
+        config
+    }
+
+    /// The number of DoT connections accepted so far
+    pub fn dot_connections(&self) -> usize {
+        self.tls.dot_connections.load(Ordering::SeqCst)
+    }
+
+    /// Close each DoT connection after answering `queries` queries on it
+    pub fn set_dot_queries_per_connection(&self, queries: usize) {
+        self.tls.dot_queries_per_connection.store(queries, Ordering::SeqCst);
+    }
+
+    /// RFC 9102 proof for the TXT records at `name`, as a Lightning node
+    /// answering `dnssec_query` would send it: the answer or its denial,
+    /// and the DNSKEY and DS RRsets of every hosted zone above it
+    pub fn proof(&self, name: &str) -> Result<Vec<u8>, Bip353Error> {
+        let name = parse_name(name)?;
+        let zones = self.zones.lock().unwrap();
+        let mut records = zones.lookup(&name, RecordType::TXT);
+        for zone in zones.0.iter().filter(|zone| zone.apex.zone_of(&name)) {
+            extend_unique(&mut records, zones.lookup(&zone.apex, RecordType::DNSKEY));
+            if Zones::parent_index(&zones.0, &zone.apex).is_some() {
+                extend_unique(&mut records, zones.lookup(&zone.apex, RecordType::DS));
+            }
+        }
+        onion::encode_proof(&records)
+    }
+
+    /// Replace the data of the TXT records at `name` without signing them
+    /// again, as an attacker on the path would
+    pub fn tamper_txt(&self, name: &str, strings: &[&str]) -> Result<(), Bip353Error> {
+        let name = parse_name(name)?;
+        let txt = RData::TXT(TXT::new(strings.iter().map(|s| s.to_string()).collect()));
+        for zone in &mut self.zones.lock().unwrap().0 {
+            for record in zone.records.iter_mut().filter(|r| r.name() == &name && r.record_type() == RecordType::TXT) {
+                record.set_data(Some(txt.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Drop every RRSIG at `name`, as an attacker stripping signatures would
+    pub fn strip_rrsigs(&self, name: &str) -> Result<(), Bip353Error> {
+        let name = parse_name(name)?;
+        for zone in &mut self.zones.lock().unwrap().0 {
+            zone.records.retain(|r| r.name() != &name || r.record_type() != RecordType::RRSIG);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+/// A `DnsTransport` answering from a `TestServer`'s zones in process
+pub struct TestTransport {
+    zones: Arc<Mutex<Zones>>,
+    queries: AtomicUsize,
+}
+
+impl TestTransport {
+    /// The number of queries sent through this transport so far
+    pub fn queries(&self) -> usize {
+        self.queries.load(Ordering::SeqCst)
+    }
+}
+
+#[async_trait]
+impl DnsTransport for TestTransport {
+    async fn exchange(&self, query: &[u8]) -> Result<Vec<u8>, Bip353Error> {
+        self.queries.fetch_add(1, Ordering::SeqCst);
+        self.zones.lock().unwrap()
+            .respond(query, false)
+            .ok_or_else(|| Bip353Error::TransportError("Test server could not answer the query".to_string()))
+    }
+}
+
+fn tls_error(e: impl std::fmt::Display) -> Bip353Error {
+    Bip353Error::TransportError(format!("Test server TLS error: {}", e))
+}
+
+/// A TLS server config presenting the DER-encoded `certificate` and `key`
+/// and offering `alpn`
+fn tls_config(certificate: &[u8], key: &[u8], alpn: &[u8]) -> Result<Arc<rustls::ServerConfig>, Bip353Error> {
+    let mut config = rustls::ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(vec![rustls::Certificate(certificate.to_vec())], rustls::PrivateKey(key.to_vec()))
+        .map_err(tls_error)?;
+    config.alpn_protocols = vec![alpn.to_vec()];
+    Ok(Arc::new(config))
+}
+
+/// Answer up to `max_queries` length-prefixed queries on one connection,
+/// or until it closes
+async fn serve_stream<S>(stream: &mut S, zones: &Mutex<Zones>, max_queries: usize)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    for _ in 0..max_queries {
+        let mut prefix = [0u8; 2];
+        if stream.read_exact(&mut prefix).await.is_err() {
+            return;
+        }
+        let mut query = transport::frame_buffer(prefix);
+        if stream.read_exact(&mut query).await.is_err() {
+            return;
+        }
+        let Some(response) = zones.lock().unwrap().respond(&query, false) else { return };
+        let Ok(framed) = transport::frame(&response) else { return };
+        if stream.write_all(&framed).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Answer DoT queries on one connection (RFC 7858), closing it after the
+/// configured number of queries
+async fn serve_dot(stream: TcpStream, acceptor: TlsAcceptor, zones: Arc<Mutex<Zones>>, tls: Arc<TlsListeners>) {
+    // Clients that reject the certificate abort the handshake
+    let Ok(mut stream) = acceptor.accept(stream).await else { return };
+    serve_stream(&mut stream, &zones, tls.dot_queries_per_connection.load(Ordering::SeqCst)).await;
+    let _ = stream.shutdown().await;
+}
+
+/// Answer one DoH request (RFC 8484) and close the connection
+async fn serve_doh(stream: TcpStream, acceptor: TlsAcceptor, zones: Arc<Mutex<Zones>>, tls: Arc<TlsListeners>) {
+    let Ok(mut stream) = acceptor.accept(stream).await else { return };
+    let Some((method, query)) = read_doh_request(&mut stream).await else { return };
+    let Some(mut body) = zones.lock().unwrap().respond(&query, false) else { return };
+    tls.doh_requests.lock().unwrap().push(DohRequest { method, id: u16::from_be_bytes([query[0], query[1]]) });
+
+    body.resize(body.len() + tls.doh_padding.load(Ordering::SeqCst), 0);
+    let head = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/dns-message\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len(),
+    );
+    // Clients stop reading oversized responses part way
+    let _ = stream.write_all(head.as_bytes()).await;
+    let _ = stream.write_all(&body).await;
+    let _ = stream.shutdown().await;
+}
+
+/// Read one HTTP/1.1 DoH request and return its method and DNS query
+async fn read_doh_request<S: AsyncRead + Unpin>(stream: &mut S) -> Option<(DohMethod, Vec<u8>)> {
+    let mut raw = Vec::new();
+    let mut buf = [0u8; 1024];
+    let header_end = loop {
+        if let Some(end) = raw.windows(4).position(|w| w == b"\r\n\r\n") {
+            break end;
+        }
+        let len = stream.read(&mut buf).await.ok().filter(|len| *len > 0)?;
+        raw.extend_from_slice(&buf[..len]);
+    };
+    let head = std::str::from_utf8(&raw[..header_end]).ok()?;
+    let header = |name: &str| {
+        head.lines()
+            .filter_map(|line| line.split_once(':'))
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.trim())
+    };
+    let mut request_line = head.lines().next()?.split(' ');
+    let (method, path) = (request_line.next()?, request_line.next()?);
+
+    match method {
+        "GET" => {
+            let dns = path.split_once("dns=")?.1.split('&').next()?;
+            Some((DohMethod::Get, data_encoding::BASE64URL_NOPAD.decode(dns.as_bytes()).ok()?))
+        }
+        "POST" if header("Content-Type") == Some("application/dns-message") => {
+            let len: usize = header("Content-Length")?.parse().ok()?;
+            let mut body = raw[header_end + 4..].to_vec();
+            while body.len() < len {
+                let read = stream.read(&mut buf).await.ok().filter(|read| *read > 0)?;
+                body.extend_from_slice(&buf[..read]);
+            }
+            body.truncate(len);
+            Some((DohMethod::Post, body))
+        }
+        _ => None,
+    }
+}
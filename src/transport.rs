@@ -0,0 +1,321 @@
+//! Pluggable DNS transports
+//!
+//! A transport only moves wire-format DNS messages. Everything it returns is
+//! checked locally by the DNSSEC validator, so an untrusted transport (a
+//! public DoH server, a proxy, ...) can at worst deny service.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use futures_util::lock::Mutex as AsyncMutex;
+use trust_dns_proto::op::{Edns, Message, MessageType, OpCode, Query, ResponseCode};
+use trust_dns_proto::rr::{Name, Record, RecordType};
+
+use crate::{alias, denial};
+use crate::dnssec::{self, rrsigs, AlgorithmPolicy, TrustAnchors, ZoneKeys};
+use crate::Bip353Error;
+
+/// Maximum number of zone cuts walked when collecting a proof
+const MAX_CHAIN_LENGTH: usize = 16;
+
+/// A way to exchange DNS messages with a (recursive) server
+#[async_trait]
+pub trait DnsTransport: Send + Sync {
+    /// Send a wire-format query and return the wire-format response
+    async fn exchange(&self, query: &[u8]) -> Result<Vec<u8>, Bip353Error>;
+
+    /// Like `exchange`, for a query that is part of lookup `lookup`
+    ///
+    /// All queries of one `Resolver::resolve` call share a random lookup ID.
+    /// Transports that can keep lookups apart (e.g. Tor stream isolation)
+    /// override this; the default ignores it.
+    async fn exchange_isolated(&self, query: &[u8], lookup: u64) -> Result<Vec<u8>, Bip353Error> {
+        let _ = lookup;
+        self.exchange(query).await
+    }
+}
+
+/// Build a recursive query asking for DNSSEC records
+///
+/// CD is set so the upstream server hands back records even if it cannot
+/// validate them itself; validation happens locally.
+pub(crate) fn build_query(name: &Name, record_type: RecordType, id: u16) -> Result<Vec<u8>, Bip353Error> {
+    let mut edns = Edns::new();
+    edns.set_dnssec_ok(true);
+    edns.set_max_payload(4096);
+
+    let mut message = Message::new();
+    message
+        .set_id(id)
+        .set_message_type(MessageType::Query)
+        .set_op_code(OpCode::Query)
+        .set_recursion_desired(true)
+        .set_checking_disabled(true)
+        .add_query(Query::query(name.clone(), record_type))
+        .set_edns(edns);
+
+    message.to_vec().map_err(|e| Bip353Error::DnsError(e.to_string()))
+}
+
+/// Run a single query over `transport` and sanity check the response
+///
+/// Any response code is returned; see `query_ok` for the common case.
+pub(crate) async fn query(
+    transport: &dyn DnsTransport,
+    name: &Name,
+    record_type: RecordType,
+    lookup: u64,
+) -> Result<Message, Bip353Error> {
+    let id = rand::random();
+    let request = build_query(name, record_type, id)?;
+    let response = transport.exchange_isolated(&request, lookup).await?;
+    let message = Message::from_vec(&response)
+        .map_err(|e| Bip353Error::TransportError(format!("Malformed response: {}", e)))?;
+
+    if message.id() != id || message.message_type() != MessageType::Response {
+        return Err(Bip353Error::TransportError("Response does not match query".into()));
+    }
+    match message.queries() {
+        [q] if q.name() == name && q.query_type() == record_type => Ok(message),
+        _ => Err(Bip353Error::TransportError("Response does not match query".into())),
+    }
+}
+
+/// Like `query`, but fail unless the server answered NOERROR
+pub(crate) async fn query_ok(
+    transport: &dyn DnsTransport,
+    name: &Name,
+    record_type: RecordType,
+    lookup: u64,
+) -> Result<Message, Bip353Error> {
+    let message = query(transport, name, record_type, lookup).await?;
+    match message.response_code() {
+        ResponseCode::NoError => Ok(message),
+        ResponseCode::NXDomain => Err(Bip353Error::DnsError(format!("{} does not exist", name))),
+        code => Err(Bip353Error::TransportError(format!("Server returned {}", code))),
+    }
+}
+
+/// A query's answers, filled in by whichever lookup asks first
+///
+/// An async mutex rather than a runtime's once-cell, so lookups work under
+/// any executor; others asking meanwhile wait for the first answer.
+type SharedAnswer = Arc<AsyncMutex<Option<Vec<Record>>>>;
+
+/// DNSKEY/DS answers and validated zone keys shared between lookups
+///
+/// Names under the same domain have the same chain of trust, so a batch of
+/// lookups only fetches and checks it once.
+#[derive(Default)]
+pub(crate) struct ChainMemo {
+    answers: Mutex<HashMap<(Name, RecordType), SharedAnswer>>,
+    zone_keys: Mutex<HashMap<Name, Arc<ZoneKeys>>>,
+}
+
+impl ChainMemo {
+    /// Answers for a DNSKEY/DS query, asking `transport` only the first time
+    async fn chain_query(
+        &self,
+        transport: &dyn DnsTransport,
+        name: &Name,
+        record_type: RecordType,
+        lookup: u64,
+    ) -> Result<Vec<Record>, Bip353Error> {
+        let cell = self.answers.lock().unwrap()
+            .entry((name.clone(), record_type))
+            .or_default()
+            .clone();
+        let mut answer = cell.lock().await;
+        if let Some(records) = &*answer {
+            return Ok(records.clone());
+        }
+        // Keep the authority section: a DS denial lives there
+        let mut message = query_ok(transport, name, record_type, lookup).await?;
+        let mut records = message.take_answers();
+        records.extend(message.take_name_servers());
+        *answer = Some(records.clone());
+        Ok(records)
+    }
+
+    /// Validated zone keys for RRsets signed by `signer`
+    ///
+    /// The first proof seen for a signer is validated from the trust
+    /// anchors; later lookups under that signer reuse the result.
+    pub fn zone_keys(
+        &self,
+        signer: &Name,
+        records: &[Record],
+        anchors: &TrustAnchors,
+        policy: &AlgorithmPolicy,
+        now: u32,
+    ) -> Arc<ZoneKeys> {
+        if let Some(keys) = self.zone_keys.lock().unwrap().get(signer) {
+            return keys.clone();
+        }
+        let keys = Arc::new(dnssec::validate_zone_keys(records, anchors, policy, now));
+        self.zone_keys.lock().unwrap().insert(signer.clone(), keys.clone());
+        keys
+    }
+}
+
+/// Collect `name`/`record_type` plus every DNSKEY and DS record needed to
+/// validate it, walking from the signing zone up to the root
+///
+/// For an unsigned answer the walk starts at its enclosing zone, so the
+/// records include the parent's NSEC denying a DS if there is one. For an
+/// NXDOMAIN or NODATA response it starts at the zone whose SOA came with
+/// the denial.
+pub(crate) async fn fetch_proof(
+    transport: &dyn DnsTransport,
+    name: &Name,
+    record_type: RecordType,
+    lookup: u64,
+    memo: &ChainMemo,
+) -> Result<Vec<Record>, Bip353Error> {
+    let mut response = query(transport, name, record_type, lookup).await?;
+    match response.response_code() {
+        ResponseCode::NoError | ResponseCode::NXDomain => {}
+        code => return Err(Bip353Error::TransportError(format!("Server returned {}", code))),
+    }
+    // The authority section holds the NSEC/NSEC3 proof of a wildcard
+    // answer or of a denial
+    let mut records = response.take_answers();
+    records.extend(response.take_name_servers());
+
+    // The answer may be an alias, whose own zone's chain is needed
+    let Some(step) = alias::step(&records, name, record_type) else {
+        let Some(zone) = denial::zone(&records, name) else {
+            return Ok(records);
+        };
+        return walk_chain(transport, records, zone, lookup, memo).await;
+    };
+    let signer = rrsigs(&records, &step.owner, step.record_type).next().map(|sig| sig.signer_name().clone());
+    let zone = match signer {
+        Some(signer) => signer,
+        // Unsigned: walk up from the enclosing zone, whose parent may prove
+        // that it is not meant to be signed
+        None => match enclosing_zone(transport, &step.owner, lookup).await? {
+            Some(zone) => zone,
+            None => return Ok(records),
+        },
+    };
+    walk_chain(transport, records, zone, lookup, memo).await
+}
+
+/// Add the DNSKEY and DS records from `zone` up to the root to `records`
+async fn walk_chain(
+    transport: &dyn DnsTransport,
+    mut records: Vec<Record>,
+    mut zone: Name,
+    lookup: u64,
+    memo: &ChainMemo,
+) -> Result<Vec<Record>, Bip353Error> {
+    for _ in 0..MAX_CHAIN_LENGTH {
+        records.extend(memo.chain_query(transport, &zone, RecordType::DNSKEY, lookup).await?);
+        if zone.is_root() {
+            return Ok(records);
+        }
+
+        let ds = memo.chain_query(transport, &zone, RecordType::DS, lookup).await?;
+        // Signed by the parent: the DS itself, or the NSEC/NSEC3 denying it
+        let parent = rrsigs(&ds, &zone, RecordType::DS)
+            .chain(dnssec::denial_sigs(&ds, &zone))
+            .next()
+            .map(|sig| sig.signer_name().clone());
+        records.extend(ds);
+
+        zone = match parent {
+            // The parent must be a strict ancestor, or we could loop forever
+            Some(parent) if parent.zone_of(&zone) && parent != zone => parent,
+            // The chain is broken here; validation decides what that means
+            _ => return Ok(records),
+        };
+    }
+
+    Err(Bip353Error::DnssecError("Chain of trust too long".into()))
+}
+
+/// The apex of the zone holding `name`, from the SOA record a server
+/// returns in the answer or, for a non-apex name, the authority section
+async fn enclosing_zone(transport: &dyn DnsTransport, name: &Name, lookup: u64) -> Result<Option<Name>, Bip353Error> {
+    let message = query_ok(transport, name, RecordType::SOA, lookup).await?;
+    Ok(message.answers().iter()
+        .chain(message.name_servers())
+        .find(|record| record.record_type() == RecordType::SOA && record.name().zone_of(name))
+        .map(|record| record.name().clone()))
+}
+
+/// Build a TLS connector trusting `root_certificates` (DER), or the Mozilla
+/// root store if none are given
+#[cfg(feature = "tokio")]
+pub(crate) fn tls_connector(
+    root_certificates: &[Vec<u8>],
+    alpn: &[&[u8]],
+) -> Result<tokio_rustls::TlsConnector, Bip353Error> {
+    let mut roots = rustls::RootCertStore::empty();
+    if root_certificates.is_empty() {
+        roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
+            rustls::OwnedTrustAnchor::from_subject_spki_name_constraints(
+                ta.subject,
+                ta.spki,
+                ta.name_constraints,
+            )
+        }));
+    } else {
+        for der in root_certificates {
+            roots.add(&rustls::Certificate(der.clone()))
+                .map_err(|e| Bip353Error::DnsError(format!("Invalid root certificate: {}", e)))?;
+        }
+    }
+
+    let mut config = rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    config.alpn_protocols = alpn.iter().map(|p| p.to_vec()).collect();
+
+    Ok(tokio_rustls::TlsConnector::from(Arc::new(config)))
+}
+
+/// Parse a TLS server name (host name or IP literal) for SNI and verification
+#[cfg(feature = "tokio")]
+pub(crate) fn server_name(host: &str) -> Result<rustls::ServerName, Bip353Error> {
+    rustls::ServerName::try_from(host.trim_start_matches('[').trim_end_matches(']'))
+        .map_err(|_| Bip353Error::DnsError(format!("Invalid TLS server name: {}", host)))
+}
+
+/// Prefix `message` with its length for a stream transport (RFC 1035 4.2.2)
+#[cfg(any(feature = "tokio", feature = "smol"))]
+pub(crate) fn frame(message: &[u8]) -> std::io::Result<Vec<u8>> {
+    let len = u16::try_from(message.len())
+        .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidInput, "message too long"))?;
+    let mut framed = Vec::with_capacity(message.len() + 2);
+    framed.extend_from_slice(&len.to_be_bytes());
+    framed.extend_from_slice(message);
+    Ok(framed)
+}
+
+/// A buffer for the message announced by a 2-byte length prefix
+#[cfg(any(feature = "tokio", feature = "smol"))]
+pub(crate) fn frame_buffer(prefix: [u8; 2]) -> Vec<u8> {
+    vec![0u8; u16::from_be_bytes(prefix) as usize]
+}
+
+/// Exchange a length-prefixed message over a Tokio stream
+#[cfg(feature = "tokio")]
+pub(crate) async fn stream_exchange<S>(stream: &mut S, query: &[u8]) -> std::io::Result<Vec<u8>>
+where
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+{
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    stream.write_all(&frame(query)?).await?;
+    stream.flush().await?;
+
+    let mut prefix = [0u8; 2];
+    stream.read_exact(&mut prefix).await?;
+    let mut response = frame_buffer(prefix);
+    stream.read_exact(&mut response).await?;
+    Ok(response)
+}
@@ -0,0 +1,105 @@
+//! TSIG message authentication (RFC 8945)
+//!
+//! Dynamic updates are authenticated with a secret shared with the
+//! primary server. The request MAC is chained into the response MAC, so a
+//! signed response also proves it answers this request.
+
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use trust_dns_proto::op::Message;
+use trust_dns_proto::rr::dnssec::rdata::tsig::{self, TsigAlgorithm, TSIG};
+use trust_dns_proto::rr::dnssec::rdata::DNSSECRData;
+use trust_dns_proto::rr::{Name, RData};
+
+use crate::Bip353Error;
+
+/// Allowed clock difference between signer and verifier, in seconds
+const FUDGE: u16 = 300;
+
+fn tsig_error(e: impl fmt::Display) -> Bip353Error {
+    Bip353Error::UpdateError(format!("TSIG: {}", e))
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// A TSIG key shared with a DNS server
+///
+/// `Debug` output leaves out the secret.
+#[derive(Clone, PartialEq, Eq)]
+pub struct TsigKey {
+    name: Name,
+    algorithm: TsigAlgorithm,
+    secret: Vec<u8>,
+}
+
+impl fmt::Debug for TsigKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TsigKey").field("name", &self.name).field("algorithm", &self.algorithm).finish_non_exhaustive()
+    }
+}
+
+impl TsigKey {
+    /// A key named `name`; HMAC-SHA256, -SHA384 and -SHA512 are supported
+    pub fn new(name: Name, algorithm: TsigAlgorithm, secret: Vec<u8>) -> Result<Self, Bip353Error> {
+        if !algorithm.supported() {
+            return Err(tsig_error(format!("{} is not supported", algorithm)));
+        }
+        Ok(Self { name, algorithm, secret })
+    }
+
+    /// Parse `[algorithm:]name:base64-secret`, the form `nsupdate -y` takes
+    ///
+    /// The algorithm defaults to `hmac-sha256`.
+    pub fn parse(text: &str) -> Result<Self, Bip353Error> {
+        let parts: Vec<&str> = text.trim().split(':').collect();
+        let (algorithm, name, secret) = match parts[..] {
+            [algorithm, name, secret] => (algorithm, name, secret),
+            [name, secret] => ("hmac-sha256", name, secret),
+            _ => return Err(tsig_error("Key must be in format [algorithm:]name:secret")),
+        };
+        let algorithm = Name::from_ascii(algorithm).map(TsigAlgorithm::from_name).map_err(tsig_error)?;
+        let mut name = Name::from_ascii(name).map_err(tsig_error)?;
+        name.set_fqdn(true);
+        let secret = data_encoding::BASE64.decode(secret.as_bytes()).map_err(tsig_error)?;
+        Self::new(name, algorithm, secret)
+    }
+
+    pub fn name(&self) -> &Name {
+        &self.name
+    }
+
+    pub fn algorithm(&self) -> &TsigAlgorithm {
+        &self.algorithm
+    }
+
+    /// Append a TSIG record to `message` and return its MAC
+    ///
+    /// `request_mac` is the MAC of the request when signing a response.
+    pub(crate) fn sign(&self, message: &mut Message, request_mac: Option<&[u8]>) -> Result<Vec<u8>, Bip353Error> {
+        let unsigned = TSIG::new(self.algorithm.clone(), unix_time(), FUDGE, Vec::new(), message.id(), 0, Vec::new());
+        let tbs = tsig::message_tbs(request_mac, message, &unsigned, &self.name).map_err(tsig_error)?;
+        let mac = self.algorithm.mac_data(&self.secret, &tbs).map_err(tsig_error)?;
+        message.add_tsig(tsig::make_tsig_record(self.name.clone(), unsigned.set_mac(mac.clone())));
+        Ok(mac)
+    }
+
+    /// Check the TSIG record at the end of the wire-format `message` and
+    /// return its MAC
+    pub(crate) fn verify(&self, message: &[u8], request_mac: Option<&[u8]>) -> Result<Vec<u8>, Bip353Error> {
+        let (tbs, record) = tsig::signed_bitmessage_to_buf(request_mac, message, true).map_err(tsig_error)?;
+        let Some(RData::DNSSEC(DNSSECRData::TSIG(tsig))) = record.data() else {
+            return Err(tsig_error("Message is not signed"));
+        };
+        if record.name() != &self.name || tsig.algorithm() != &self.algorithm {
+            return Err(tsig_error(format!("Signed with unknown key {}", record.name())));
+        }
+        self.algorithm.verify_mac(&self.secret, &tbs, tsig.mac()).map_err(|_| tsig_error("Bad signature"))?;
+        if unix_time().abs_diff(tsig.time()) > u64::from(tsig.fudge()) {
+            return Err(tsig_error("Signature time is outside the allowed clock skew"));
+        }
+        Ok(tsig.mac().to_vec())
+    }
+}
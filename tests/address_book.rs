@@ -0,0 +1,171 @@
+use std::time::Duration;
+
+use bip353::signer::SigningKey;
+use bip353::testing::{TestServer, TestZone};
+use bip353::{
+    AddressBook, Bip353Config, DnssecStatus, HumanReadableName, KeyId, PaymentInstruction, Publisher, Resolver, TsigKey,
+    UpdateConfig,
+};
+use trust_dns_proto::rr::dnssec::Algorithm;
+use trust_dns_proto::rr::Name;
+
+const KEY: &str = "update-key.:c2VjcmV0LXNoYXJlZC13aXRoLXRoZS1wcmltYXJ5LXNlcnZlcg==";
+const ONCHAIN: &str = "bitcoin:bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+const OFFER: &str = "bitcoin:bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4?lno=lno1qsgqmqvgm96frzdg8m0gc6n";
+
+fn alice() -> HumanReadableName {
+    HumanReadableName::new("alice", "example.com").unwrap()
+}
+
+async fn server() -> TestServer {
+    let mut zone = TestZone::new("example.com.").unwrap();
+    zone.add_payment_instruction("alice", ONCHAIN).unwrap();
+    zone.allow_updates(TsigKey::parse(KEY).unwrap());
+    TestServer::start(vec![zone]).await.unwrap()
+}
+
+async fn server_with_keys(ksk: &SigningKey, zsk: &SigningKey) -> TestServer {
+    let mut zone = TestZone::new("example.com.").unwrap();
+    zone.add_payment_instruction("alice", ONCHAIN).unwrap();
+    zone.set_keys(ksk, zsk);
+    TestServer::start(vec![zone]).await.unwrap()
+}
+
+fn publisher(server: &TestServer) -> Publisher {
+    let mut config = UpdateConfig::new(server.tcp_addr(), Name::from_ascii("example.com.").unwrap(), TsigKey::parse(KEY).unwrap());
+    config.verify_interval = Duration::from_millis(10);
+    Publisher::new(config, server.resolver().unwrap())
+}
+
+#[tokio::test]
+async fn test_first_use_pins() {
+    let server = server().await;
+    let resolver = server.resolver().unwrap();
+    let book = AddressBook::new();
+
+    let first = resolver.resolve_pinned(&alice(), &book).await.unwrap();
+    assert!(first.is_first_use());
+    assert!(!first.is_changed());
+    let pin = book.get(&alice()).unwrap();
+    assert_eq!(pin.uri, ONCHAIN);
+    assert_eq!(pin.key, KeyId::of(&first.instruction.dnssec));
+    assert!(pin.key.is_some());
+
+    let second = resolver.resolve_pinned(&alice(), &book).await.unwrap();
+    assert!(!second.is_first_use());
+    assert!(!second.is_changed());
+    assert_eq!(second.last_seen(), Some(pin.last_seen));
+    assert_eq!(book.names(), vec![alice()]);
+}
+
+#[tokio::test]
+async fn test_changed_methods_are_reported_until_pinned() {
+    let server = server().await;
+    let resolver = server.resolver().unwrap();
+    let book = AddressBook::new();
+    resolver.resolve_pinned(&alice(), &book).await.unwrap();
+
+    // A new label is not a new destination
+    publisher(&server).replace("alice", &format!("{}?label=Alice", ONCHAIN)).await.unwrap();
+    let check = resolver.resolve_pinned(&alice(), &book).await.unwrap();
+    assert!(!check.is_changed());
+
+    publisher(&server).replace("alice", OFFER).await.unwrap();
+    let check = resolver.resolve_pinned(&alice(), &book).await.unwrap();
+    assert_eq!(check.changed_methods, vec!["lno"]);
+    assert!(!check.key_rotated);
+    assert_eq!(check.previous.as_ref().unwrap().uri, ONCHAIN);
+
+    // Still reported, since the change was not accepted
+    let check = resolver.resolve_pinned(&alice(), &book).await.unwrap();
+    assert!(check.is_changed());
+    assert_eq!(book.get(&alice()).unwrap().uri, ONCHAIN);
+
+    book.pin(&alice(), &check.instruction).unwrap();
+    assert!(!resolver.resolve_pinned(&alice(), &book).await.unwrap().is_changed());
+}
+
+#[tokio::test]
+async fn test_key_rotation() {
+    let book = AddressBook::new();
+    server().await.resolver().unwrap().resolve_pinned(&alice(), &book).await.unwrap();
+
+    // The same record, in a zone signed with new keys
+    let check = server().await.resolver().unwrap().resolve_pinned(&alice(), &book).await.unwrap();
+    assert!(check.key_rotated);
+    assert!(check.changed_methods.is_empty());
+    assert!(check.is_changed());
+}
+
+#[tokio::test]
+async fn test_zsk_rollover_is_not_a_rotation() {
+    let ksk = SigningKey::generate(Algorithm::ED25519, true).unwrap();
+    let zsk = || SigningKey::generate(Algorithm::ED25519, false).unwrap();
+    let book = AddressBook::new();
+    let pinned = server_with_keys(&ksk, &zsk()).await.resolver().unwrap().resolve_pinned(&alice(), &book).await.unwrap();
+    assert_eq!(pinned.instruction.dnssec.anchor_key_tag, Some(ksk.key_tag()));
+
+    // The same KSK over a new ZSK: the record's signing key changed, its anchor did not
+    let check = server_with_keys(&ksk, &zsk()).await.resolver().unwrap().resolve_pinned(&alice(), &book).await.unwrap();
+    assert_ne!(check.instruction.dnssec.key_tag, pinned.instruction.dnssec.key_tag);
+    assert!(!check.key_rotated);
+    assert!(!check.is_changed());
+
+    // A new KSK is
+    let other = SigningKey::generate(Algorithm::ED25519, true).unwrap();
+    let check = server_with_keys(&other, &zsk()).await.resolver().unwrap().resolve_pinned(&alice(), &book).await.unwrap();
+    assert!(check.key_rotated);
+}
+
+#[tokio::test]
+async fn test_status_downgrade() {
+    let book = AddressBook::new();
+    let pinned = server().await.resolver().unwrap().resolve_pinned(&alice(), &book).await.unwrap();
+    assert!(!pinned.key_unverified);
+
+    // Same URI, but nothing vouches for it or its key any more
+    let check = book.check(&alice(), PaymentInstruction::from_uri(ONCHAIN).unwrap()).unwrap();
+    assert!(check.changed_methods.is_empty());
+    assert!(check.status_downgraded);
+    assert!(check.key_unverified);
+    assert!(!check.key_rotated);
+    assert!(check.is_changed());
+    assert_eq!(book.get(&alice()).unwrap().status, DnssecStatus::Secure);
+}
+
+#[test]
+fn test_unvalidated_pin_flags_unverified_key() {
+    let book = AddressBook::new();
+    let instruction = PaymentInstruction::from_uri(ONCHAIN).unwrap();
+    assert!(book.check(&alice(), instruction.clone()).unwrap().key_unverified);
+    assert_eq!(book.get(&alice()).unwrap().key, None);
+
+    // Flagged every time, but the pin never had a key to lose
+    let check = book.check(&alice(), instruction).unwrap();
+    assert!(check.key_unverified);
+    assert!(!check.status_downgraded);
+    assert!(!check.is_changed());
+}
+
+#[tokio::test]
+async fn test_refused_results_are_not_pinned() {
+    let server = server().await;
+    let other = TestServer::start(vec![TestZone::new("example.com.").unwrap()]).await.unwrap();
+    let resolver = Resolver::from_config(Bip353Config { trust_anchors: other.trust_anchors(), ..server.config() }).unwrap();
+    let book = AddressBook::new();
+
+    assert!(resolver.resolve_pinned(&alice(), &book).await.is_err());
+    assert_eq!(book.get(&alice()), None);
+}
+
+#[test]
+fn test_human_readable_name() {
+    let name: HumanReadableName = "₿Alice@Example.COM.".parse().unwrap();
+    assert_eq!(name, alice());
+    assert_eq!(name.user(), "alice");
+    assert_eq!(name.domain(), "example.com");
+    assert_eq!(name.to_string(), "₿alice@example.com");
+
+    assert!(HumanReadableName::parse("alice").is_err());
+    assert!(HumanReadableName::new("", "example.com").is_err());
+}
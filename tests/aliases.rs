@@ -0,0 +1,106 @@
+use bip353::testing::{TestServer, TestZone};
+use bip353::{AliasKind, Bip353Config, Bip353Error, DnssecStatus, PaymentType, Resolver, TransportConfig};
+use trust_dns_proto::rr::Name;
+
+const URI: &str = "bitcoin:bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+
+/// The root and `example.`, then `zones`
+fn zones(zones: Vec<TestZone>) -> Vec<TestZone> {
+    let mut all = vec![TestZone::new(".").unwrap(), TestZone::new("example.").unwrap()];
+    all.extend(zones);
+    all
+}
+
+fn resolver(server: &TestServer) -> Resolver {
+    Resolver::from_config(Bip353Config {
+        transport: TransportConfig::Custom(server.transport()),
+        trust_anchors: server.trust_anchors(),
+        ..Default::default()
+    }).unwrap()
+}
+
+fn name(name: &str) -> Name {
+    Name::from_ascii(name).unwrap()
+}
+
+#[tokio::test]
+async fn test_dname_delegates_every_user() {
+    let mut zones = zones(vec![TestZone::new("provider.").unwrap()]);
+    // Every user at example is handed to the provider
+    zones[1].add_dname("user._bitcoin-payment.example.", "user._bitcoin-payment.provider.").unwrap();
+    zones[2].add_txt("alice.user._bitcoin-payment.provider.", &[URI]).unwrap();
+    zones[2].add_txt("bob.user._bitcoin-payment.provider.", &["bitcoin:?lno=lno1qcp4256ypq"]).unwrap();
+    let server = TestServer::start(zones).await.unwrap();
+    let resolver = resolver(&server);
+
+    let alice = resolver.resolve("alice", "example").await.unwrap();
+    assert_eq!(alice.payment_type, PaymentType::OnChain);
+    assert_eq!(alice.dnssec.status, DnssecStatus::Secure);
+    assert_eq!(alice.dnssec.signer, Some(name("provider.")));
+
+    let [alias] = alice.aliases.as_slice() else { panic!("expected one alias: {:?}", alice.aliases) };
+    assert_eq!(alias.kind, AliasKind::Dname);
+    assert_eq!(alias.owner, name("user._bitcoin-payment.example."));
+    assert_eq!(alias.name, name("alice.user._bitcoin-payment.example."));
+    assert_eq!(alias.target, name("alice.user._bitcoin-payment.provider."));
+    assert_eq!(alias.dnssec.status, DnssecStatus::Secure);
+    assert_eq!(alias.dnssec.signer, Some(name("example.")));
+
+    let bob = resolver.resolve("bob", "example").await.unwrap();
+    assert_eq!(bob.payment_type, PaymentType::LightningOffer);
+}
+
+#[tokio::test]
+async fn test_cname_chain() {
+    let mut zones = zones(vec![TestZone::new("provider.").unwrap()]);
+    zones[1].add_cname("alice.user._bitcoin-payment.example.", "alice.pay.example.").unwrap();
+    zones[1].add_cname("alice.pay.example.", "alice.provider.").unwrap();
+    zones[2].add_txt("alice.provider.", &[URI]).unwrap();
+    let server = TestServer::start(zones).await.unwrap();
+
+    let instruction = resolver(&server).resolve("alice", "example").await.unwrap();
+    assert_eq!(instruction.uri, URI);
+    let hops: Vec<_> = instruction.aliases.iter().map(|a| (a.kind, a.target.clone())).collect();
+    assert_eq!(hops, vec![
+        (AliasKind::Cname, name("alice.pay.example.")),
+        (AliasKind::Cname, name("alice.provider.")),
+    ]);
+}
+
+#[tokio::test]
+async fn test_unsigned_hop_is_refused() {
+    let mut zones = zones(Vec::new());
+    zones[1].add_cname("alice.user._bitcoin-payment.example.", "alice.pay.example.").unwrap();
+    zones[1].add_txt("alice.pay.example.", &[URI]).unwrap();
+    let server = TestServer::start(zones).await.unwrap();
+    // The target is signed, but nothing vouches for the alias itself
+    server.strip_rrsigs("alice.user._bitcoin-payment.example.").unwrap();
+
+    let result = resolver(&server).resolve("alice", "example").await;
+    assert!(matches!(result, Err(Bip353Error::DnssecError(msg)) if msg.contains("bogus")));
+}
+
+#[tokio::test]
+async fn test_alias_hop_limit() {
+    let mut zones = zones(Vec::new());
+    zones[1].add_cname("alice.user._bitcoin-payment.example.", "hop0.example.").unwrap();
+    for i in 0..9 {
+        zones[1].add_cname(&format!("hop{}.example.", i), &format!("hop{}.example.", i + 1)).unwrap();
+    }
+    zones[1].add_txt("hop9.example.", &[URI]).unwrap();
+    let server = TestServer::start(zones).await.unwrap();
+
+    let result = resolver(&server).resolve("alice", "example").await;
+    assert!(matches!(result, Err(Bip353Error::DnsError(msg)) if msg.contains("aliases")));
+}
+
+#[tokio::test]
+async fn test_alias_loop() {
+    let mut zones = zones(Vec::new());
+    zones[1].add_cname("alice.user._bitcoin-payment.example.", "loop.example.").unwrap();
+    zones[1].add_cname("loop.example.", "alice.user._bitcoin-payment.example.").unwrap();
+    let server = TestServer::start(zones).await.unwrap();
+
+    let result = resolver(&server).resolve("alice", "example").await;
+    assert!(matches!(result, Err(Bip353Error::DnsError(msg)) if msg.contains("loop")));
+}
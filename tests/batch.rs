@@ -0,0 +1,52 @@
+use std::sync::Arc;
+
+use bip353::testing::{TestServer, TestTransport, TestZone};
+use bip353::{Bip353Config, Bip353Error, PaymentType, Resolver, TransportConfig};
+
+async fn batch_resolver() -> (Resolver, Arc<TestTransport>) {
+    let mut example = TestZone::new("example.").unwrap();
+    for user in ["alice", "bob", "carol"] {
+        example.add_payment_instruction(user, &format!("bitcoin:?lno=lno1{}", user)).unwrap();
+    }
+    let server = TestServer::start(vec![TestZone::new(".").unwrap(), example]).await.unwrap();
+    let transport = server.transport();
+    let resolver = Resolver::from_config(Bip353Config {
+        transport: TransportConfig::Custom(transport.clone()),
+        trust_anchors: server.trust_anchors(),
+        ..Default::default()
+    }).unwrap();
+    (resolver, transport)
+}
+
+#[tokio::test]
+async fn test_resolve_many_preserves_order() {
+    let (resolver, _) = batch_resolver().await;
+    let names = ["₿carol@example", "not an address", "₿alice@example", "₿dave@example", "₿bob@example"];
+
+    let results = resolver.resolve_many(&names, 2, |_, _| {}).await;
+
+    assert_eq!(results.len(), names.len());
+    assert_eq!(results[0].as_ref().unwrap().lightning_offer(), Some("lno1carol"));
+    assert!(matches!(results[1], Err(Bip353Error::InvalidAddress(_))));
+    assert_eq!(results[2].as_ref().unwrap().lightning_offer(), Some("lno1alice"));
+    assert!(matches!(results[3], Err(Bip353Error::NotFound(_))));
+    assert_eq!(results[4].as_ref().unwrap().payment_type, PaymentType::LightningOffer);
+}
+
+#[tokio::test]
+async fn test_resolve_many_deduplicates_and_shares_chain() {
+    let (resolver, transport) = batch_resolver().await;
+    let names = ["₿alice@example", "₿ALICE@example", "₿bob@example", "₿alice@example", "₿carol@example"];
+
+    let mut progress = Vec::new();
+    let results = resolver.resolve_many(&names, 4, |done, total| progress.push((done, total))).await;
+
+    assert!(results.iter().all(Result::is_ok));
+    assert_eq!(results[0].as_ref().unwrap(), results[1].as_ref().unwrap());
+    assert_eq!(results[0].as_ref().unwrap(), results[3].as_ref().unwrap());
+    assert_eq!(progress, vec![(1, 3), (2, 3), (3, 3)]);
+
+    // One TXT query per unique name, plus DNSKEY/DS for example. and the
+    // root DNSKEY fetched once for the whole batch
+    assert_eq!(transport.queries(), 3 + 3);
+}
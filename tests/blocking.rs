@@ -0,0 +1,53 @@
+use bip353::testing::{TestServer, TestZone};
+use bip353::{AddressBook, Bip353Error, BlockingResolver, HumanReadableName, LintConfig};
+
+const URI: &str = "bitcoin:bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+
+async fn server() -> TestServer {
+    let mut zone = TestZone::new("example.com.").unwrap();
+    zone.add_payment_instruction("alice", URI).unwrap();
+    TestServer::start(vec![zone]).await.unwrap()
+}
+
+#[test]
+fn test_outside_a_runtime() {
+    // The server needs a runtime of its own; the test thread stays outside it
+    let server_runtime = tokio::runtime::Runtime::new().unwrap();
+    let server = server_runtime.block_on(server());
+    let resolver = BlockingResolver::from_config(server.config()).unwrap();
+
+    assert_eq!(resolver.resolve("alice", "example.com").unwrap().uri, URI);
+    assert_eq!(resolver.resolve_address("₿alice@example.com").unwrap().uri, URI);
+    assert!(matches!(resolver.resolve("carol", "example.com"), Err(Bip353Error::NotFound(_))));
+
+    let results = resolver.resolve_many(&["alice@example.com", "carol@example.com", "invalid"], 4);
+    assert_eq!(results[0].as_ref().unwrap().uri, URI);
+    assert!(matches!(results[1], Err(Bip353Error::NotFound(_))));
+    assert!(matches!(results[2], Err(Bip353Error::InvalidAddress(_))));
+
+    assert_eq!(resolver.lint("alice", "example.com", &LintConfig::default()).unwrap(), vec![]);
+    let book = AddressBook::new();
+    let name = HumanReadableName::new("alice", "example.com").unwrap();
+    assert!(resolver.resolve_pinned(&name, &book).unwrap().is_first_use());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_inside_a_runtime() {
+    let server = server().await;
+    let resolver = BlockingResolver::from_config(server.config()).unwrap();
+    assert_eq!(resolver.resolve("alice", "example.com").unwrap().uri, URI);
+    // Dropping the resolver here must not panic either
+    drop(resolver);
+}
+
+#[tokio::test]
+async fn test_inside_a_current_thread_runtime() {
+    // This runtime's only thread is blocked during the lookup, so the server
+    // runs elsewhere
+    let server_runtime = tokio::runtime::Runtime::new().unwrap();
+    let server = server_runtime.spawn(server()).await.unwrap();
+    let resolver = BlockingResolver::from_config(server.config()).unwrap();
+    assert_eq!(resolver.resolve("alice", "example.com").unwrap().uri, URI);
+    drop(resolver);
+    server_runtime.shutdown_background();
+}
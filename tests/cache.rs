@@ -0,0 +1,112 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use bip353::testing::{TestServer, TestTransport, TestZone};
+use bip353::{Bip353Config, Bip353Error, CacheConfig, CacheStats, Resolver, TransportConfig};
+
+const URI: &str = "bitcoin:bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+
+fn cached_resolver(server: &TestServer) -> (Resolver, Arc<TestTransport>) {
+    let transport = server.transport();
+    let resolver = Resolver::from_config(Bip353Config {
+        transport: TransportConfig::Custom(transport.clone()),
+        trust_anchors: server.trust_anchors(),
+        cache: Some(CacheConfig::default()),
+        ..Default::default()
+    }).unwrap();
+    (resolver, transport)
+}
+
+/// The root and `example.`, publishing a URI for alice
+fn test_zones() -> Vec<TestZone> {
+    let mut example = TestZone::new("example.").unwrap();
+    example.add_payment_instruction("alice", URI).unwrap();
+    vec![TestZone::new(".").unwrap(), example]
+}
+
+#[tokio::test]
+async fn test_cache_hit() {
+    let server = TestServer::start(test_zones()).await.unwrap();
+    let (resolver, transport) = cached_resolver(&server);
+
+    let first = resolver.resolve("alice", "example").await.unwrap();
+    let queries = transport.queries();
+    // Names are case-insensitive
+    let second = resolver.resolve("Alice", "EXAMPLE.").await.unwrap();
+
+    assert_eq!(first, second);
+    assert_eq!(transport.queries(), queries);
+    assert_eq!(resolver.cache_stats(), Some(CacheStats { hits: 1, misses: 1, entries: 1 }));
+}
+
+#[tokio::test]
+async fn test_cache_flush() {
+    let mut zones = test_zones();
+    zones[1].add_payment_instruction("bob", "bitcoin:bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq").unwrap();
+    let server = TestServer::start(zones).await.unwrap();
+    let (resolver, transport) = cached_resolver(&server);
+
+    resolver.resolve("alice", "example").await.unwrap();
+    resolver.resolve("bob", "example").await.unwrap();
+    resolver.flush("alice", "example");
+    assert_eq!(resolver.cache_stats().unwrap().entries, 1);
+
+    let queries = transport.queries();
+    resolver.resolve("bob", "example").await.unwrap();
+    assert_eq!(transport.queries(), queries);
+    resolver.resolve("alice", "example").await.unwrap();
+    assert!(transport.queries() > queries);
+
+    resolver.flush_all();
+    assert_eq!(resolver.cache_stats().unwrap().entries, 0);
+}
+
+#[tokio::test]
+async fn test_cache_nxdomain() {
+    let server = TestServer::start(test_zones()).await.unwrap();
+    let (resolver, transport) = cached_resolver(&server);
+
+    let result = resolver.resolve("carol", "example").await;
+    assert!(matches!(result, Err(Bip353Error::NotFound(_))));
+    // The proven denial is served from the cache
+    let queries = transport.queries();
+    let result = resolver.resolve("carol", "example").await;
+    assert!(matches!(result, Err(Bip353Error::NotFound(_))));
+    assert_eq!(transport.queries(), queries);
+    assert_eq!(resolver.cache_stats(), Some(CacheStats { hits: 1, misses: 1, entries: 1 }));
+}
+
+#[tokio::test]
+async fn test_cache_bounded_by_rrsig_expiration() {
+    let mut zones = test_zones();
+    // The TTL is an hour, but the signatures expire in two seconds
+    zones[1].set_signature_validity(Duration::from_secs(2));
+    let server = TestServer::start(zones).await.unwrap();
+    let (resolver, transport) = cached_resolver(&server);
+
+    resolver.resolve("alice", "example").await.unwrap();
+    let queries = transport.queries();
+    tokio::time::sleep(Duration::from_millis(2100)).await;
+
+    // Expired from the cache, so the name is queried again
+    let _ = resolver.resolve("alice", "example").await;
+    assert!(transport.queries() > queries);
+    assert_eq!(resolver.cache_stats().unwrap().hits, 0);
+}
+
+#[tokio::test]
+async fn test_no_cache_by_default() {
+    let server = TestServer::start(test_zones()).await.unwrap();
+    let transport = server.transport();
+    let resolver = Resolver::from_config(Bip353Config {
+        transport: TransportConfig::Custom(transport.clone()),
+        trust_anchors: server.trust_anchors(),
+        ..Default::default()
+    }).unwrap();
+
+    resolver.resolve("alice", "example").await.unwrap();
+    let queries = transport.queries();
+    resolver.resolve("alice", "example").await.unwrap();
+    assert_eq!(transport.queries(), 2 * queries);
+    assert_eq!(resolver.cache_stats(), None);
+}
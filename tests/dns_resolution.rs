@@ -1,173 +1,262 @@
 //! Tests for BIP-353 DNS resolution
 //!
-//! These are sample tests for resolving DNS TXT records to
-//! Bitcoin payment instructions. It uses mocked DNS responses
-//! to test the resolution process without actual DNS queries.
-
-use std::str::FromStr;
-use bip353::{Resolver, PaymentInstruction, PaymentType, Bip353Error};
-
-// requires a running DNS server or mock to work properly
-// For integration testing,mark it as ignored by default
-// Run with: cargo test -- --ignored dns_resolution
-#[test]
-#[ignore]
-fn test_successful_resolution() {
-    // Create a tokio runtime for async tests
-    let rt = tokio::runtime::Runtime::new().expect("Failed to create runtime");
-    
-    // Run the async test inside the runtime
-    rt.block_on(async {
-        // Create a resolver
-        let resolver = Resolver::new().expect("Failed to create resolver");
-        
-        // test requires a properly configured DNS server
-        // with a valid DNSSEC-signed TXT record for this name
-        // Replace with a domain you control for actual testing
-        let user = "test";
-        let domain = "example.com";
-        
-        let result = resolver.resolve(user, domain).await;
-        
-        // If the domain has a properly configured BIP-353 record, this will succeed
-        // Otherwise, it will fail with a DNS error, which is expected
-        if result.is_ok() {
-            let instruction = result.unwrap();
-            assert!(instruction.uri.starts_with("bitcoin:"));
-        } else {
-            // This is expected this to fail on domains without BIP-353 records
-            let err = result.unwrap_err();
-            assert!(matches!(err, Bip353Error::DnsError(_)) || 
-                    matches!(err, Bip353Error::InvalidRecord(_)));
-        }
-    });
-}
-
-// For effective unit testing, ideally use a mock DNS resolver
-// Here's how to maybe structure such tests with a mock
-
-#[test]
-#[ignore]
-fn test_with_mock_resolver() {
-    // These tests are still work in progress
-    //They need a mock implementation of the DNS resolver
-    // For a minimal example, here's a structure
-    
-    /* 
-    // Example of what mock setup might look like:
-    let mut mock_resolver = MockDnsResolver::new();
-    
-    // Mock a successful response
-    mock_resolver.expect_resolve()
-        .with(eq("alice.user._bitcoin-payment.example.com"), eq(RecordType::TXT))
-        .returning(|_, _| {
-            Ok(MockDnsResponse::new_with_txt("bitcoin:bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"))
-        });
-    
-    // Create resolver with mock
-    let resolver = Resolver::with_mock(mock_resolver);
-    
-    // Create a tokio runtime for async tests
-    let rt = tokio::runtime::Runtime::new().expect("Failed to create runtime");
-    
-    // Run the async test inside the runtime
-    rt.block_on(async {
-        // Test resolution
-        let result = resolver.resolve("alice", "example.com").await;
-        assert!(result.is_ok());
-        let instruction = result.unwrap();
-        assert!(matches!(instruction.payment_type, PaymentType::OnChain));
-    });
-    */
-}
-
-// Tests resolving a human-readable address string
-#[test]
-#[ignore]
-fn test_resolve_address() {
-    // Create a tokio runtime for async tests
-    let rt = tokio::runtime::Runtime::new().expect("Failed to create runtime");
-    
-    // Run the async test inside the runtime
-    rt.block_on(async {
-        // Create a resolver
-        let resolver = Resolver::new().expect("Failed to create resolver");
-        
-        // This test requires a properly configured DNS server
-        // with a valid DNSSEC-signed TXT record for this name
-        // Replace with an address you control for actual testing
-        let address = "₿test@example.com";
-        
-        let result = resolver.resolve_address(address).await;
-        
-        // If the domain has a properly configured BIP-353 record, this will succeed
-        // Otherwise, it will fail with a DNS error, which is expected
-        if result.is_ok() {
-            let instruction = result.unwrap();
-            assert!(instruction.uri.starts_with("bitcoin:"));
-        } else {
-            // also expected to fail on domains without BIP-353 records
-            let err = result.unwrap_err();
-            assert!(matches!(err, Bip353Error::DnsError(_)) || 
-                    matches!(err, Bip353Error::InvalidRecord(_)));
-        }
-    });
-}
-
-// Test how the resolver handles invalid DNS names
-#[test]
-fn test_invalid_dns_names() {
-    // Create a tokio runtime for async tests
-    let rt = tokio::runtime::Runtime::new().expect("Failed to create runtime");
-    
-    // Run the async test inside the runtime
-    rt.block_on(async {
-        // Create a resolver
-        let resolver = Resolver::new().expect("Failed to create resolver");
-        
-        // Invalid domain (RFC 1035 violation)
-        let result = resolver.resolve("user", "invalid-domain-").await;
-        assert!(result.is_err());
-        
-        // Non-existent domain
-        let result = resolver.resolve("user", "this-domain-definitely-does-not-exist-12345.com").await;
-        assert!(result.is_err());
-        assert!(matches!(result.unwrap_err(), Bip353Error::DnsError(_)));
-        
-        // Empty domain
-        let result = resolver.resolve("user", "").await;
-        assert!(result.is_err());
-    });
-}
-
-// Test DNSSEC validation behavior
-// Note: This requires a properly configured environment with DNSSEC
-#[test]
-#[ignore]
-fn test_dnssec_validation() {
-    // Create a tokio runtime for async tests
-    let rt = tokio::runtime::Runtime::new().expect("Failed to create runtime");
-    
-    // Run the async test inside the runtime
-    rt.block_on(async {
-        // Create a resolver
-        let resolver = Resolver::new().expect("Failed to create resolver");
-        
-        // This test should use a domain known to have valid DNSSEC
-        // For actual testing, replace with appropriate domain
-        let result = resolver.resolve("test", "example.com").await;
-        
-        // For a domain with proper DNSSEC but no BIP-353 record,
-        // a "record not found" type of error is expected, not a DNSSEC error
-        if result.is_err() {
-            let err = result.unwrap_err();
-            assert!(!matches!(err, Bip353Error::DnssecError(_)));
-        }
-        
-    });
-}
-
-// For real integration testing(production implementation), set up a test DNS server with:
-// 1. A properly configured DNSSEC-signed zone
-// 2. BIP-353 TXT records for different payment types
-// 3. Various invalid configurations to test error handling
+//! These resolve against the in-process signed authoritative server from
+//! `bip353::testing`, so the whole path, DNSSEC validation included, runs
+//! without network access.
+
+use bip353::signer::DenialConfig;
+use bip353::testing::{TestServer, TestZone};
+use bip353::{
+    verify_proof, AlgorithmPolicy, Bip353Config, Bip353Error, DnssecStatus, PaymentType, Resolver, ValidationMode,
+};
+use tokio::net::UdpSocket;
+use trust_dns_proto::op::{Message, Query};
+use trust_dns_proto::rr::{Name, RecordType};
+
+const ONCHAIN: &str = "bitcoin:bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+
+async fn example_server() -> TestServer {
+    let mut zone = TestZone::new("example.com.").unwrap();
+    zone.add_payment_instruction("alice", ONCHAIN).unwrap();
+    zone.add_payment_instruction("bob", "bitcoin:?lno=lno1qsgqmqvgm96frzdg8m0gc6n").unwrap();
+    TestServer::start(vec![zone]).await.unwrap()
+}
+
+/// Send a TXT query without EDNS over UDP
+async fn udp_query(server: &TestServer, user: &str) -> Message {
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let name = Name::from_ascii(format!("{}.user._bitcoin-payment.example.com.", user)).unwrap();
+    let mut query = Message::new();
+    query.set_id(7).add_query(Query::query(name, RecordType::TXT));
+    socket.send_to(&query.to_vec().unwrap(), server.udp_addr()).await.unwrap();
+
+    let mut buf = vec![0u8; 65535];
+    let len = socket.recv(&mut buf).await.unwrap();
+    Message::from_vec(&buf[..len]).unwrap()
+}
+
+#[tokio::test]
+async fn test_successful_resolution() {
+    let server = example_server().await;
+    let instruction = server.resolver().unwrap().resolve("alice", "example.com").await.unwrap();
+    assert_eq!(instruction.uri, ONCHAIN);
+    assert_eq!(instruction.payment_type, PaymentType::OnChain);
+}
+
+#[tokio::test]
+async fn test_resolve_address() {
+    let server = example_server().await;
+    let instruction = server.resolver().unwrap().resolve_address("₿bob@example.com").await.unwrap();
+    assert_eq!(instruction.payment_type, PaymentType::LightningOffer);
+}
+
+#[tokio::test]
+async fn test_long_record_is_chunked() {
+    let offer = format!("bitcoin:?lno=lno1{}", "q".repeat(600));
+    let mut zone = TestZone::new("example.com.").unwrap();
+    zone.add_payment_instruction("carol", &offer).unwrap();
+    let server = TestServer::start(vec![zone]).await.unwrap();
+
+    let instruction = server.resolver().unwrap().resolve("carol", "example.com").await.unwrap();
+    assert_eq!(instruction.uri, offer);
+}
+
+#[tokio::test]
+async fn test_chain_through_parent_zone() {
+    // Only the parent is anchored; the child is reached through its DS
+    let com = TestZone::new("com.").unwrap();
+    let mut example = TestZone::new("example.com.").unwrap();
+    example.add_payment_instruction("alice", ONCHAIN).unwrap();
+    let server = TestServer::start(vec![com, example]).await.unwrap();
+
+    let instruction = server.resolver().unwrap().resolve("alice", "example.com").await.unwrap();
+    assert_eq!(instruction.uri, ONCHAIN);
+    assert_eq!(instruction.dnssec.signer, Some(Name::from_ascii("example.com.").unwrap()));
+}
+
+#[tokio::test]
+async fn test_result_carries_proof() {
+    let com = TestZone::new("com.").unwrap();
+    let mut example = TestZone::new("example.com.").unwrap();
+    example.add_payment_instruction("alice", ONCHAIN).unwrap();
+    let server = TestServer::start(vec![com, example]).await.unwrap();
+
+    // The proof checks offline against the same anchors, down from com.
+    let instruction = server.resolver().unwrap().resolve("alice", "example.com").await.unwrap();
+    let proof = instruction.proof.as_ref().unwrap();
+    let verified = verify_proof("alice", "example.com", proof, &server.trust_anchors(), &AlgorithmPolicy::default()).unwrap();
+    assert_eq!(verified, instruction);
+}
+
+#[tokio::test]
+async fn test_nsec3_zone() {
+    let mut zone = TestZone::new("example.com.").unwrap();
+    zone.set_denial(DenialConfig::Nsec3 { iterations: 0, salt: vec![0xab, 0xcd], opt_out: false });
+    zone.add_payment_instruction("alice", ONCHAIN).unwrap();
+    zone.add_txt("*.user._bitcoin-payment.example.com.", &["bitcoin:?lno=lno1qsgqmqvgm96frzdg8m0gc6n"]).unwrap();
+    let wildcard = TestServer::start(vec![zone.clone()]).await.unwrap();
+
+    let resolver = wildcard.resolver().unwrap();
+    assert_eq!(resolver.resolve("alice", "example.com").await.unwrap().uri, ONCHAIN);
+    assert!(resolver.resolve("anyone", "example.com").await.unwrap().is_wildcard());
+
+    // Without the wildcard, a missing name is proven absent by the NSEC3 chain
+    let mut zone = TestZone::new("example.com.").unwrap();
+    zone.set_denial(DenialConfig::Nsec3 { iterations: 0, salt: Vec::new(), opt_out: false });
+    zone.add_payment_instruction("alice", ONCHAIN).unwrap();
+    let server = TestServer::start(vec![zone]).await.unwrap();
+    let result = server.resolver().unwrap().resolve("carol", "example.com").await;
+    assert!(matches!(result, Err(Bip353Error::NotFound(_))));
+}
+
+#[tokio::test]
+async fn test_unsigned_child_zone() {
+    let com = TestZone::new("com.").unwrap();
+    let mut example = TestZone::new("example.com.").unwrap();
+    example.set_unsigned();
+    example.add_payment_instruction("alice", ONCHAIN).unwrap();
+    let server = TestServer::start(vec![com, example]).await.unwrap();
+
+    let result = server.resolver().unwrap().resolve("alice", "example.com").await;
+    assert!(matches!(result, Err(Bip353Error::DnssecError(msg)) if msg.contains("insecure")));
+
+    // The parent's NSEC proves there is no DS
+    let config = Bip353Config { validation: ValidationMode::AllowUnvalidated, ..server.config() };
+    let instruction = Resolver::from_config(config).unwrap().resolve("alice", "example.com").await.unwrap();
+    assert_eq!(instruction.uri, ONCHAIN);
+    assert_eq!(instruction.dnssec.status, DnssecStatus::Insecure);
+    assert_eq!(instruction.dnssec.signer, Some(Name::from_ascii("com.").unwrap()));
+}
+
+#[tokio::test]
+async fn test_signed_child_without_ds() {
+    for denial in [DenialConfig::Nsec, DenialConfig::Nsec3 { iterations: 0, salt: Vec::new(), opt_out: false }] {
+        let mut com = TestZone::new("com.").unwrap();
+        com.set_denial(denial.clone());
+        let mut example = TestZone::new("example.com.").unwrap();
+        example.set_without_ds();
+        example.add_payment_instruction("alice", ONCHAIN).unwrap();
+        let server = TestServer::start(vec![com, example]).await.unwrap();
+
+        // Signed, but its parent proves there is no DS: Insecure, not bogus
+        let result = server.resolver().unwrap().resolve("alice", "example.com").await;
+        assert!(matches!(result, Err(Bip353Error::DnssecError(msg)) if msg.contains("insecure")), "{:?}", denial);
+
+        let config = Bip353Config { validation: ValidationMode::AllowUnvalidated, ..server.config() };
+        let instruction = Resolver::from_config(config).unwrap().resolve("alice", "example.com").await.unwrap();
+        assert_eq!(instruction.dnssec.status, DnssecStatus::Insecure, "{:?}", denial);
+        assert_eq!(instruction.dnssec.signer, Some(Name::from_ascii("com.").unwrap()));
+    }
+}
+
+#[tokio::test]
+async fn test_unsigned_child_of_nsec3_zone() {
+    for opt_out in [false, true] {
+        let mut com = TestZone::new("com.").unwrap();
+        com.set_denial(DenialConfig::Nsec3 { iterations: 0, salt: Vec::new(), opt_out });
+        let mut example = TestZone::new("example.com.").unwrap();
+        example.set_unsigned();
+        example.add_payment_instruction("alice", ONCHAIN).unwrap();
+        let server = TestServer::start(vec![com, example]).await.unwrap();
+
+        let result = server.resolver().unwrap().resolve("alice", "example.com").await;
+        assert!(matches!(result, Err(Bip353Error::DnssecError(msg)) if msg.contains("insecure")), "opt-out {}", opt_out);
+
+        // The parent's NSEC3, or with opt-out the span covering the cut, proves there is no DS
+        let config = Bip353Config { validation: ValidationMode::AllowUnvalidated, ..server.config() };
+        let instruction = Resolver::from_config(config).unwrap().resolve("alice", "example.com").await.unwrap();
+        assert_eq!(instruction.dnssec.status, DnssecStatus::Insecure, "opt-out {}", opt_out);
+        assert_eq!(instruction.dnssec.signer, Some(Name::from_ascii("com.").unwrap()));
+    }
+}
+
+#[tokio::test]
+async fn test_wildcard_zone() {
+    let mut zone = TestZone::new("example.com.").unwrap();
+    zone.add_txt("*.user._bitcoin-payment.example.com.", &[ONCHAIN]).unwrap();
+    let server = TestServer::start(vec![zone]).await.unwrap();
+
+    let instruction = server.resolver().unwrap().resolve("anyone", "example.com").await.unwrap();
+    assert!(instruction.is_wildcard());
+}
+
+#[tokio::test]
+async fn test_invalid_dns_names() {
+    let server = example_server().await;
+    let resolver = server.resolver().unwrap();
+
+    // Invalid domain (RFC 1035 violation)
+    assert!(resolver.resolve("user", "invalid-domain-").await.is_err());
+
+    // Non-existent name, proven by the zone's NSEC chain
+    let result = resolver.resolve("user", "example.com").await;
+    assert!(matches!(result, Err(Bip353Error::NotFound(_))));
+
+    // Empty domain
+    assert!(resolver.resolve("user", "").await.is_err());
+}
+
+#[tokio::test]
+async fn test_dnssec_validation() {
+    // Anchored to some other server's keys, the signatures do not validate
+    let server = example_server().await;
+    let other = TestServer::start(vec![TestZone::new("example.com.").unwrap()]).await.unwrap();
+    let resolver = Resolver::from_config(Bip353Config { trust_anchors: other.trust_anchors(), ..server.config() }).unwrap();
+
+    let result = resolver.resolve("alice", "example.com").await;
+    assert!(matches!(result, Err(Bip353Error::DnssecError(msg)) if msg.contains("bogus")));
+}
+
+#[tokio::test]
+async fn test_udp_truncation() {
+    let mut zone = TestZone::new("example.com.").unwrap();
+    zone.add_payment_instruction("alice", ONCHAIN).unwrap();
+    zone.add_payment_instruction("carol", &format!("bitcoin:?lno=lno1{}", "q".repeat(600))).unwrap();
+    let server = TestServer::start(vec![zone]).await.unwrap();
+
+    // Without EDNS a UDP answer must fit in 512 bytes
+    let small = udp_query(&server, "alice").await;
+    assert!(!small.truncated());
+    assert!(!small.answers().is_empty());
+    let large = udp_query(&server, "carol").await;
+    assert!(large.truncated());
+    assert!(large.answers().is_empty());
+}
+
+#[tokio::test]
+async fn test_system_backend() {
+    let server = example_server().await;
+    let resolver = server.system_resolver();
+
+    let instruction = resolver.resolve("alice", "example.com").await.unwrap();
+    assert_eq!(instruction.uri, ONCHAIN);
+    assert_eq!(instruction.dnssec.status, DnssecStatus::Secure);
+
+    // The NSEC denial survives the UDP exchange
+    let result = resolver.resolve("user", "example.com").await;
+    assert!(matches!(result, Err(Bip353Error::NotFound(_))));
+}
+
+#[tokio::test]
+async fn test_system_backend_retries_truncated_answer_over_tcp() {
+    // Too large even for the 4096 bytes the query advertises over EDNS
+    let offer = format!("bitcoin:?lno=lno1{}", "q".repeat(5000));
+    let mut zone = TestZone::new("example.com.").unwrap();
+    zone.add_payment_instruction("carol", &offer).unwrap();
+    let server = TestServer::start(vec![zone]).await.unwrap();
+
+    let instruction = server.system_resolver().resolve("carol", "example.com").await.unwrap();
+    assert_eq!(instruction.uri, offer);
+}
+
+#[tokio::test]
+async fn test_system_backend_unsigned_child_zone() {
+    let com = TestZone::new("com.").unwrap();
+    let mut example = TestZone::new("example.com.").unwrap();
+    example.set_unsigned();
+    example.add_payment_instruction("alice", ONCHAIN).unwrap();
+    let server = TestServer::start(vec![com, example]).await.unwrap();
+
+    let result = server.system_resolver().resolve("alice", "example.com").await;
+    assert!(matches!(result, Err(Bip353Error::DnssecError(msg)) if msg.contains("insecure")));
+}
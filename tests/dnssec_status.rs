@@ -0,0 +1,109 @@
+use bip353::testing::{TestServer, TestZone};
+use bip353::{Bip353Config, Bip353Error, DnssecStatus, PaymentInstruction, Resolver, TransportConfig, TrustAnchors, ValidationMode};
+use trust_dns_proto::rr::dnssec::Algorithm;
+use trust_dns_proto::rr::Name;
+
+const URI: &str = "bitcoin:bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+
+fn resolver(server: &TestServer, trust_anchors: TrustAnchors, validation: ValidationMode) -> Resolver {
+    Resolver::from_config(Bip353Config {
+        transport: TransportConfig::Custom(server.transport()),
+        trust_anchors,
+        validation,
+        ..Default::default()
+    }).unwrap()
+}
+
+/// The root and `example.`, publishing a URI for alice
+async fn test_server() -> TestServer {
+    let mut example = TestZone::new("example.").unwrap();
+    example.add_payment_instruction("alice", URI).unwrap();
+    TestServer::start(vec![TestZone::new(".").unwrap(), example]).await.unwrap()
+}
+
+#[tokio::test]
+async fn test_secure_result_records_signature() {
+    let server = test_server().await;
+    let resolver = resolver(&server, server.trust_anchors(), ValidationMode::default());
+
+    let instruction = resolver.resolve("alice", "example").await.unwrap();
+    let dnssec = instruction.dnssec;
+    assert_eq!(dnssec.status, DnssecStatus::Secure);
+    assert_eq!(dnssec.signer, Some(Name::from_ascii("example.").unwrap()));
+    assert_eq!(dnssec.algorithm, Some(Algorithm::ED25519));
+    assert!(dnssec.inception.unwrap() < dnssec.expiration.unwrap());
+}
+
+#[tokio::test]
+async fn test_system_backend_records_signature() {
+    let mut zone = TestZone::new("example.com.").unwrap();
+    zone.add_payment_instruction("alice", URI).unwrap();
+    let server = TestServer::start(vec![zone]).await.unwrap();
+
+    // The system backend validates locally too, so it knows the RRSIG
+    let instruction = server.system_resolver().resolve("alice", "example.com").await.unwrap();
+    let dnssec = instruction.dnssec;
+    assert_eq!(dnssec.status, DnssecStatus::Secure);
+    assert_eq!(dnssec.signer, Some(Name::from_ascii("example.com.").unwrap()));
+    assert_eq!(dnssec.algorithm, Some(Algorithm::ED25519));
+    assert!(dnssec.key_tag.is_some());
+    assert!(dnssec.inception.unwrap() < dnssec.expiration.unwrap());
+}
+
+#[tokio::test]
+async fn test_insecure_delegation() {
+    let mut unsigned = TestZone::new("unsigned.example.").unwrap();
+    unsigned.set_unsigned();
+    unsigned.add_payment_instruction("alice", URI).unwrap();
+    let zones = vec![TestZone::new(".").unwrap(), TestZone::new("example.").unwrap(), unsigned];
+    let server = TestServer::start(zones).await.unwrap();
+    let anchors = server.trust_anchors();
+
+    // Refused by default
+    let result = resolver(&server, anchors.clone(), ValidationMode::RequireSecure).resolve("alice", "unsigned.example").await;
+    assert!(matches!(result, Err(Bip353Error::DnssecError(msg)) if msg.contains("insecure")));
+
+    let instruction = resolver(&server, anchors, ValidationMode::AllowUnvalidated)
+        .resolve("alice", "unsigned.example")
+        .await
+        .unwrap();
+    assert_eq!(instruction.uri, URI);
+    // The parent's NSEC is what proved the zone unsigned
+    assert_eq!(instruction.dnssec.status, DnssecStatus::Insecure);
+    assert_eq!(instruction.dnssec.signer, Some(Name::from_ascii("example.").unwrap()));
+}
+
+#[tokio::test]
+async fn test_stripped_signature_is_bogus() {
+    let server = test_server().await;
+    server.strip_rrsigs("alice.user._bitcoin-payment.example.").unwrap();
+    let anchors = server.trust_anchors();
+
+    // Bogus answers are refused even when unvalidated ones are allowed
+    let result = resolver(&server, anchors.clone(), ValidationMode::AllowUnvalidated).resolve("alice", "example").await;
+    assert!(matches!(result, Err(Bip353Error::DnssecError(msg)) if msg.contains("bogus")));
+
+    let instruction = resolver(&server, anchors, ValidationMode::Permissive).resolve("alice", "example").await.unwrap();
+    assert_eq!(instruction.dnssec.status, DnssecStatus::Bogus);
+}
+
+#[tokio::test]
+async fn test_uncovered_name_is_indeterminate() {
+    let server = test_server().await;
+
+    let result = resolver(&server, TrustAnchors::new(), ValidationMode::RequireSecure).resolve("alice", "example").await;
+    assert!(matches!(result, Err(Bip353Error::DnssecError(msg)) if msg.contains("indeterminate")));
+
+    let instruction = resolver(&server, TrustAnchors::new(), ValidationMode::AllowUnvalidated)
+        .resolve("alice", "example")
+        .await
+        .unwrap();
+    assert_eq!(instruction.dnssec.status, DnssecStatus::Indeterminate);
+    assert_eq!(instruction.dnssec.signer, None);
+}
+
+#[test]
+fn test_parsed_uri_is_indeterminate() {
+    let instruction = PaymentInstruction::from_uri(URI).unwrap();
+    assert_eq!(instruction.dnssec.status, DnssecStatus::Indeterminate);
+}
@@ -0,0 +1,95 @@
+use bip353::testing::{DohRequest, TestServer, TestZone};
+use bip353::{Bip353Config, Bip353Error, DohMethod, PaymentType, Resolver, TransportConfig, TrustAnchors};
+
+const NAME: &str = "alice.user._bitcoin-payment.example.";
+
+fn resolver(server: &TestServer, anchors: TrustAnchors, method: DohMethod) -> Resolver {
+    Resolver::from_config(Bip353Config {
+        transport: TransportConfig::Https(server.doh_config(method)),
+        trust_anchors: anchors,
+        ..Default::default()
+    }).unwrap()
+}
+
+/// A server for the root and `example.`, publishing `strings` for alice
+async fn test_server(strings: &[&str]) -> TestServer {
+    let mut example = TestZone::new("example.").unwrap();
+    example.add_txt(NAME, strings).unwrap();
+    TestServer::start(vec![TestZone::new(".").unwrap(), example]).await.unwrap()
+}
+
+#[tokio::test]
+async fn test_doh_post() {
+    let server = test_server(&["bitcoin:?lno=lno1qsgqmqvgm96frzdg8m0gc6n"]).await;
+
+    let instruction = resolver(&server, server.trust_anchors(), DohMethod::Post)
+        .resolve_address("₿alice@example")
+        .await
+        .unwrap();
+    assert_eq!(instruction.payment_type, PaymentType::LightningOffer);
+
+    // TXT, then DNSKEY/DS for example. and DNSKEY for the root
+    assert_eq!(server.doh_requests(), vec![DohRequest { method: DohMethod::Post, id: 0 }; 4]);
+}
+
+#[tokio::test]
+async fn test_doh_get_chunked_txt() {
+    let server = test_server(&["bitcoin:bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4", "?amount=0.1"]).await;
+
+    let instruction = resolver(&server, server.trust_anchors(), DohMethod::Get)
+        .resolve("alice", "example")
+        .await
+        .unwrap();
+    assert_eq!(instruction.payment_type, PaymentType::OnChain);
+    assert_eq!(instruction.amount(), Some("0.1"));
+    assert!(server.doh_requests().iter().all(|request| request.method == DohMethod::Get));
+}
+
+#[tokio::test]
+async fn test_doh_rejects_tampered_record() {
+    let server = test_server(&["bitcoin:bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"]).await;
+    server.tamper_txt(NAME, &["bitcoin:bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq"]).unwrap();
+
+    let result = resolver(&server, server.trust_anchors(), DohMethod::Post)
+        .resolve("alice", "example")
+        .await;
+    assert!(matches!(result, Err(Bip353Error::DnssecError(_))));
+}
+
+#[tokio::test]
+async fn test_doh_rejects_untrusted_anchor() {
+    let server = test_server(&["bitcoin:bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"]).await;
+
+    // The default anchors are the real root KSKs, which did not sign these zones
+    let result = resolver(&server, TrustAnchors::default(), DohMethod::Post)
+        .resolve("alice", "example")
+        .await;
+    assert!(matches!(result, Err(Bip353Error::DnssecError(_))));
+}
+
+#[tokio::test]
+async fn test_doh_rejects_unknown_certificate() {
+    let server = test_server(&["bitcoin:bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"]).await;
+
+    let mut config = server.doh_config(DohMethod::Post);
+    config.root_certificates = Vec::new();
+    let resolver = Resolver::from_config(Bip353Config {
+        transport: TransportConfig::Https(config),
+        trust_anchors: server.trust_anchors(),
+        ..Default::default()
+    }).unwrap();
+
+    let result = resolver.resolve("alice", "example").await;
+    assert!(matches!(result, Err(Bip353Error::TransportError(_))));
+}
+
+#[tokio::test]
+async fn test_doh_rejects_oversized_response() {
+    let server = test_server(&["bitcoin:bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"]).await;
+    server.set_doh_padding(1 << 20);
+
+    let result = resolver(&server, server.trust_anchors(), DohMethod::Post)
+        .resolve("alice", "example")
+        .await;
+    assert!(matches!(result, Err(Bip353Error::TransportError(_))));
+}
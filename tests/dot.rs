@@ -0,0 +1,68 @@
+use bip353::testing::{TestServer, TestZone, TLS_HOST};
+use bip353::{Bip353Config, Bip353Error, PaymentType, Resolver, TransportConfig};
+
+const URI: &str = "bitcoin:bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+
+/// A server for the root and `example.`, publishing a URI for alice
+async fn test_server() -> TestServer {
+    let mut example = TestZone::new("example.").unwrap();
+    example.add_payment_instruction("alice", URI).unwrap();
+    TestServer::start(vec![TestZone::new(".").unwrap(), example]).await.unwrap()
+}
+
+fn resolver(server: &TestServer, server_name: &str) -> Resolver {
+    let mut config = server.dot_config();
+    config.server_name = server_name.to_string();
+    Resolver::from_config(Bip353Config {
+        transport: TransportConfig::Tls(config),
+        trust_anchors: server.trust_anchors(),
+        ..Default::default()
+    }).unwrap()
+}
+
+#[tokio::test]
+async fn test_dot_reuses_connection() {
+    let server = test_server().await;
+    let resolver = resolver(&server, TLS_HOST);
+
+    for _ in 0..2 {
+        let instruction = resolver.resolve("alice", "example").await.unwrap();
+        assert_eq!(instruction.payment_type, PaymentType::OnChain);
+    }
+    assert_eq!(server.dot_connections(), 1);
+}
+
+#[tokio::test]
+async fn test_dot_concurrent_lookups_use_separate_connections() {
+    let server = test_server().await;
+    let resolver = resolver(&server, TLS_HOST);
+
+    let (first, second) = tokio::join!(resolver.resolve("alice", "example"), resolver.resolve("alice", "example"));
+    assert_eq!(first.unwrap().payment_type, PaymentType::OnChain);
+    assert_eq!(second.unwrap().payment_type, PaymentType::OnChain);
+    // Neither lookup waited for the other's connection, and both went back to the pool
+    assert_eq!(server.dot_connections(), 2);
+    resolver.resolve("alice", "example").await.unwrap();
+    assert_eq!(server.dot_connections(), 2);
+}
+
+#[tokio::test]
+async fn test_dot_reconnects_after_server_close() {
+    let server = test_server().await;
+    server.set_dot_queries_per_connection(1);
+    let resolver = resolver(&server, TLS_HOST);
+
+    let instruction = resolver.resolve("alice", "example").await.unwrap();
+    assert_eq!(instruction.payment_type, PaymentType::OnChain);
+    // One connection per query: TXT, DS and DNSKEY for example., DNSKEY for the root
+    assert_eq!(server.dot_connections(), 4);
+}
+
+#[tokio::test]
+async fn test_dot_verifies_server_name() {
+    let server = test_server().await;
+    let resolver = resolver(&server, "other.test");
+
+    let result = resolver.resolve("alice", "example").await;
+    assert!(matches!(result, Err(Bip353Error::TransportError(_))));
+}
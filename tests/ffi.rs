@@ -0,0 +1,109 @@
+//! Tests for the BIP-353 C API
+//!
+//! Run with: cargo test --features ffi
+
+#![cfg(feature = "ffi")]
+
+use std::ffi::{c_char, CStr};
+use std::ptr;
+use bip353::ffi::*;
+use bip353::PaymentInstruction;
+
+// Take ownership of a string returned by the library
+fn take_string(s: *mut c_char) -> Option<String> {
+    if s.is_null() {
+        return None;
+    }
+    let value = unsafe { CStr::from_ptr(s) }.to_str().unwrap().to_string();
+    bip353_string_free(s);
+    Some(value)
+}
+
+fn instruction_handle(uri: &str) -> *mut InstructionPtr {
+    let instruction = PaymentInstruction::from_uri(uri).unwrap();
+    Box::into_raw(Box::new(InstructionPtr::from(instruction)))
+}
+
+#[test]
+fn test_instruction_accessors() {
+    let uri = "bitcoin:bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4?amount=0.01&label=Test&lightning=lnbc1";
+    let handle = instruction_handle(uri);
+    
+    assert_eq!(take_string(bip353_instruction_uri(handle)).as_deref(), Some(uri));
+    assert_eq!(take_string(bip353_instruction_type(handle)).as_deref(), Some("lightning"));
+    assert!(!bip353_instruction_is_reusable(handle));
+    assert_eq!(
+        take_string(bip353_instruction_onchain_address(handle)).as_deref(),
+        Some("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4")
+    );
+    assert_eq!(take_string(bip353_instruction_lightning_invoice(handle)).as_deref(), Some("lnbc1"));
+    assert_eq!(take_string(bip353_instruction_lightning_offer(handle)), None);
+    assert_eq!(take_string(bip353_instruction_amount(handle)).as_deref(), Some("0.01"));
+    assert_eq!(take_string(bip353_instruction_label(handle)).as_deref(), Some("Test"));
+    assert_eq!(take_string(bip353_instruction_message(handle)), None);
+    
+    bip353_instruction_free(handle);
+}
+
+#[test]
+fn test_instruction_parameters() {
+    let handle = instruction_handle("bitcoin:?lno=lno1&label=Coffee&amount=0.01");
+    
+    assert_eq!(bip353_instruction_param_count(handle), 3);
+    let params: Vec<(String, String)> = (0..3)
+        .map(|i| (
+            take_string(bip353_instruction_param_key(handle, i)).unwrap(),
+            take_string(bip353_instruction_param_value(handle, i)).unwrap(),
+        ))
+        .collect();
+    assert_eq!(params, vec![
+        ("amount".to_string(), "0.01".to_string()),
+        ("label".to_string(), "Coffee".to_string()),
+        ("lno".to_string(), "lno1".to_string()),
+    ]);
+    
+    // Out of range
+    assert!(bip353_instruction_param_key(handle, 3).is_null());
+    assert!(bip353_instruction_param_value(handle, 3).is_null());
+    
+    bip353_instruction_free(handle);
+}
+
+#[test]
+fn test_null_handles() {
+    assert!(bip353_instruction_uri(ptr::null()).is_null());
+    assert!(!bip353_instruction_is_reusable(ptr::null()));
+    assert_eq!(bip353_instruction_param_count(ptr::null()), 0);
+    bip353_instruction_free(ptr::null_mut());
+}
+
+#[test]
+fn test_parse_address() {
+    let address = std::ffi::CString::new("₿alice@example.com").unwrap();
+    let mut user = ptr::null_mut();
+    let mut domain = ptr::null_mut();
+    assert!(bip353_parse_address(address.as_ptr(), &mut user, &mut domain));
+    assert_eq!(take_string(user).as_deref(), Some("alice"));
+    assert_eq!(take_string(domain).as_deref(), Some("example.com"));
+    
+    let address = std::ffi::CString::new("aliceexample.com").unwrap();
+    let mut user = ptr::null_mut();
+    let mut domain = ptr::null_mut();
+    assert!(!bip353_parse_address(address.as_ptr(), &mut user, &mut domain));
+    assert!(user.is_null());
+    assert!(domain.is_null());
+}
+
+#[test]
+fn test_parse_uri() {
+    let uri = std::ffi::CString::new("bitcoin:?lno=lno1").unwrap();
+    let handle = bip353_parse_uri(uri.as_ptr());
+    assert!(!handle.is_null());
+    assert_eq!(take_string(bip353_instruction_type(handle)).as_deref(), Some("lightning-offer"));
+    assert_eq!(take_string(bip353_instruction_lightning_offer(handle)).as_deref(), Some("lno1"));
+    bip353_instruction_free(handle);
+    
+    let uri = std::ffi::CString::new("lightning:lnbc1").unwrap();
+    assert!(bip353_parse_uri(uri.as_ptr()).is_null());
+    assert!(bip353_parse_uri(ptr::null()).is_null());
+}
@@ -0,0 +1,157 @@
+use std::time::Duration;
+
+use bip353::signer::{DenialConfig, SignerConfig, SigningKey, ZoneSigner};
+use bip353::testing::{TestServer, TestZone};
+use bip353::{lint_records, payment_record, Bip353Config, Finding, LintCheck, LintConfig, Resolver, Severity};
+use trust_dns_proto::rr::dnssec::Algorithm;
+use trust_dns_proto::rr::rdata::{SOA, TXT};
+use trust_dns_proto::rr::{Name, RData, Record};
+
+const ONCHAIN: &str = "bitcoin:bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+/// The BOLT11 specification's example invoice, long expired
+const EXPIRED_INVOICE: &str = "lnbc1pvjluezpp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqdpl2pkx2ctnv5sxxmmwwd5kgetjypeh2ursdae8g6twvus8g6rfwvs8qun0dfjkxaq8rkx3yf5tcsyz3d73gafnh3cax9rn449d9p5uxz9ezhhypd0elx87sjle52x86fux2ypatgddc6k63n7erqz25le42c4u4ecky03ylcqca784w";
+/// An invoice-shaped string dated 2100, with a valid checksum
+const FUTURE_INVOICE: &str = "lnbc1r6gv4cqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqv7wcdz";
+
+fn name() -> Name {
+    Name::from_ascii("alice.user._bitcoin-payment.example.").unwrap()
+}
+
+fn txt(strings: &[&str], ttl: u32) -> Record {
+    Record::from_rdata(name(), ttl, RData::TXT(TXT::new(strings.iter().map(|s| s.to_string()).collect())))
+}
+
+/// The checks of `findings`, for order-independent comparisons
+fn checks(findings: &[Finding]) -> Vec<(LintCheck, Severity)> {
+    let mut checks: Vec<_> = findings.iter().map(|f| (f.check, f.severity)).collect();
+    checks.sort_by_key(|(check, severity)| (check.as_str(), *severity));
+    checks
+}
+
+/// `records` plus a SOA, signed for `validity`
+fn signed(mut records: Vec<Record>, validity: Duration) -> Vec<Record> {
+    let apex = Name::from_ascii("example.").unwrap();
+    let soa = SOA::new(Name::from_ascii("ns.example.").unwrap(), Name::from_ascii("hostmaster.example.").unwrap(), 1, 3600, 600, 86400, 300);
+    records.push(Record::from_rdata(apex.clone(), 3600, RData::SOA(soa)));
+    let ksk = SigningKey::generate(Algorithm::ED25519, true).unwrap();
+    let zsk = SigningKey::generate(Algorithm::ED25519, false).unwrap();
+    let config = SignerConfig { validity, ..Default::default() };
+    ZoneSigner::new(apex, ksk, zsk, config).sign(&records).unwrap().into_records()
+}
+
+fn lint_uri(uri: &str) -> Vec<(LintCheck, Severity)> {
+    let records = signed(vec![payment_record("alice", "example", uri, 3600).unwrap()], Duration::from_secs(30 * 86400));
+    checks(&lint_records(&name(), &records, &LintConfig::default()))
+}
+
+#[test]
+fn test_clean_snippet() {
+    assert_eq!(lint_uri(ONCHAIN), vec![]);
+    assert_eq!(lint_uri("bitcoin:?lno=lno1qsgqmqvgm96frzdg8m0gc6n"), vec![]);
+}
+
+#[test]
+fn test_record_set_problems() {
+    let records = vec![txt(&[ONCHAIN], 60), txt(&["bitcoin:?lno=lno1qsgqmqvgm96frzdg8m0gc6n"], 60), txt(&["v=spf1 -all"], 60)];
+    let findings = lint_records(&name(), &signed(records, Duration::from_secs(30 * 86400)), &LintConfig::default());
+    assert_eq!(checks(&findings), vec![
+        (LintCheck::MultipleBitcoinRecords, Severity::Error),
+        (LintCheck::NonBitcoinTxt, Severity::Warning),
+        (LintCheck::ShortTtl, Severity::Warning),
+    ]);
+
+    let findings = lint_records(&name(), &[txt(&["v=spf1 -all"], 30 * 86400)], &LintConfig::default());
+    assert_eq!(checks(&findings), vec![
+        (LintCheck::LongTtl, Severity::Warning),
+        (LintCheck::NoBitcoinRecord, Severity::Error),
+        (LintCheck::NonBitcoinTxt, Severity::Warning),
+        (LintCheck::Unsigned, Severity::Error),
+    ]);
+}
+
+#[test]
+fn test_uri_problems() {
+    assert_eq!(lint_uri("BITCOIN:bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4?Amount=1&req-pop=x"), vec![
+        (LintCheck::NotLowercase, Severity::Warning),
+        (LintCheck::NotLowercase, Severity::Warning),
+        (LintCheck::UnknownRequiredParameter, Severity::Error),
+    ]);
+    assert_eq!(lint_uri("bitcoin:?lno=lnoNOTANOFFER"), vec![(LintCheck::InvalidInvoice, Severity::Error)]);
+}
+
+#[test]
+fn test_bolt11_in_record() {
+    let uri = |invoice: &str| format!("bitcoin:?lightning={}", invoice);
+    assert_eq!(lint_uri(&uri(EXPIRED_INVOICE)), vec![(LintCheck::SingleUseInvoice, Severity::Error)]);
+    assert_eq!(lint_uri(&uri(FUTURE_INVOICE)), vec![(LintCheck::SingleUseInvoice, Severity::Warning)]);
+    // One character changed breaks the checksum
+    let corrupted = EXPIRED_INVOICE.replacen("pp5", "pp6", 1);
+    assert_eq!(lint_uri(&uri(&corrupted)), vec![(LintCheck::InvalidInvoice, Severity::Error)]);
+}
+
+#[test]
+fn test_expiring_signature() {
+    let records = signed(vec![payment_record("alice", "example", ONCHAIN, 3600).unwrap()], Duration::from_secs(86400));
+    let findings = lint_records(&name(), &records, &LintConfig::default());
+    assert_eq!(checks(&findings), vec![(LintCheck::ExpiringSignature, Severity::Warning)]);
+}
+
+#[tokio::test]
+async fn test_lint_resolved_name() {
+    let mut zone = TestZone::new("example.com.").unwrap();
+    zone.add_payment_instruction("alice", ONCHAIN).unwrap();
+    zone.add_txt("bob.user._bitcoin-payment.example.com.", &[ONCHAIN]).unwrap();
+    zone.add_txt("bob.user._bitcoin-payment.example.com.", &["bitcoin:?lno=lno1qsgqmqvgm96frzdg8m0gc6n"]).unwrap();
+    let server = TestServer::start(vec![zone]).await.unwrap();
+    let resolver = server.resolver().unwrap();
+    let config = LintConfig::default();
+
+    assert_eq!(resolver.lint("alice", "example.com", &config).await.unwrap(), vec![]);
+    let findings = resolver.lint("bob", "example.com", &config).await.unwrap();
+    assert_eq!(checks(&findings), vec![(LintCheck::MultipleBitcoinRecords, Severity::Error)]);
+    let findings = resolver.lint("carol", "example.com", &config).await.unwrap();
+    assert_eq!(checks(&findings), vec![(LintCheck::Unresolvable, Severity::Error)]);
+
+    // Signatures that do not validate are reported rather than refused
+    let other = TestServer::start(vec![TestZone::new("example.com.").unwrap()]).await.unwrap();
+    let resolver = Resolver::from_config(Bip353Config { trust_anchors: other.trust_anchors(), ..server.config() }).unwrap();
+    let findings = resolver.lint("alice", "example.com", &config).await.unwrap();
+    assert_eq!(checks(&findings), vec![(LintCheck::Bogus, Severity::Error)]);
+}
+
+#[tokio::test]
+async fn test_lint_missing_ds() {
+    let mut example = TestZone::new("example.com.").unwrap();
+    example.set_unsigned();
+    example.add_payment_instruction("alice", ONCHAIN).unwrap();
+    let server = TestServer::start(vec![TestZone::new("com.").unwrap(), example]).await.unwrap();
+
+    let findings = server.resolver().unwrap().lint("alice", "example.com", &LintConfig::default()).await.unwrap();
+    assert_eq!(checks(&findings), vec![(LintCheck::MissingDs, Severity::Error)]);
+}
+
+#[tokio::test]
+async fn test_lint_missing_ds_below_nsec3_parent() {
+    let mut com = TestZone::new("com.").unwrap();
+    com.set_denial(DenialConfig::Nsec3 { iterations: 0, salt: Vec::new(), opt_out: true });
+    let mut example = TestZone::new("example.com.").unwrap();
+    example.set_unsigned();
+    example.add_payment_instruction("alice", ONCHAIN).unwrap();
+    let server = TestServer::start(vec![com, example]).await.unwrap();
+
+    let findings = server.resolver().unwrap().lint("alice", "example.com", &LintConfig::default()).await.unwrap();
+    assert_eq!(checks(&findings), vec![(LintCheck::MissingDs, Severity::Error)]);
+}
+
+#[tokio::test]
+async fn test_lint_signed_zone_missing_ds() {
+    // The common mistake: the zone is signed but its DS was never uploaded
+    let com = TestZone::new("com.").unwrap();
+    let mut example = TestZone::new("example.com.").unwrap();
+    example.set_without_ds();
+    example.add_payment_instruction("alice", ONCHAIN).unwrap();
+    let server = TestServer::start(vec![com, example]).await.unwrap();
+
+    let findings = server.resolver().unwrap().lint("alice", "example.com", &LintConfig::default()).await.unwrap();
+    assert_eq!(checks(&findings), vec![(LintCheck::MissingDs, Severity::Error)]);
+}
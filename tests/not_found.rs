@@ -0,0 +1,97 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bip353::signer::DenialConfig;
+use bip353::testing::{TestServer, TestZone};
+use bip353::{Bip353Config, Bip353Error, DnsTransport, Resolver, TransportConfig, ValidationMode};
+use trust_dns_proto::op::{Message, MessageType, ResponseCode};
+
+const URI: &str = "bitcoin:bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+
+fn resolver(server: &TestServer) -> Resolver {
+    Resolver::from_config(Bip353Config {
+        transport: TransportConfig::Custom(server.transport()),
+        trust_anchors: server.trust_anchors(),
+        validation: ValidationMode::AllowUnvalidated,
+        ..Default::default()
+    }).unwrap()
+}
+
+/// The root and `example.` denying with `denial`, publishing a URI for alice
+fn zones(denial: DenialConfig) -> Vec<TestZone> {
+    let mut example = TestZone::new("example.").unwrap();
+    example.set_denial(denial);
+    example.add_payment_instruction("alice", URI).unwrap();
+    vec![TestZone::new(".").unwrap(), example]
+}
+
+fn nsec3() -> DenialConfig {
+    DenialConfig::Nsec3 { iterations: 1, salt: vec![0xaa, 0xbb], opt_out: false }
+}
+
+#[tokio::test]
+async fn test_nxdomain_proven_by_nsec() {
+    let server = TestServer::start(zones(DenialConfig::Nsec)).await.unwrap();
+    let result = resolver(&server).resolve("carol", "example").await;
+    assert!(matches!(result, Err(Bip353Error::NotFound(_))));
+}
+
+#[tokio::test]
+async fn test_nxdomain_proven_by_nsec3() {
+    let server = TestServer::start(zones(nsec3())).await.unwrap();
+    let result = resolver(&server).resolve("carol", "example").await;
+    assert!(matches!(result, Err(Bip353Error::NotFound(_))));
+}
+
+#[tokio::test]
+async fn test_nodata_proven() {
+    // bob has no TXT records of his own, only a name below him does
+    for denial in [DenialConfig::Nsec, nsec3()] {
+        let mut zones = zones(denial);
+        zones[1].add_txt("x.bob.user._bitcoin-payment.example.", &[URI]).unwrap();
+        let server = TestServer::start(zones).await.unwrap();
+        let result = resolver(&server).resolve("bob", "example").await;
+        assert!(matches!(result, Err(Bip353Error::NotFound(_))));
+    }
+}
+
+#[tokio::test]
+async fn test_denial_without_proof_is_unverified() {
+    let mut zones = zones(DenialConfig::Nsec);
+    zones[1].set_without_denial_proofs();
+    let server = TestServer::start(zones).await.unwrap();
+    let result = resolver(&server).resolve("carol", "example").await;
+    assert!(matches!(result, Err(Bip353Error::UnverifiedNotFound(_))));
+}
+
+#[tokio::test]
+async fn test_unsigned_zone_denial_is_unverified() {
+    let mut unsigned = TestZone::new("unsigned.example.").unwrap();
+    unsigned.set_unsigned();
+    let zones = vec![TestZone::new(".").unwrap(), TestZone::new("example.").unwrap(), unsigned];
+    let server = TestServer::start(zones).await.unwrap();
+    let result = resolver(&server).resolve("carol", "unsigned.example").await;
+    assert!(matches!(result, Err(Bip353Error::UnverifiedNotFound(_))));
+}
+
+/// Answers every query with SERVFAIL
+struct ServFail;
+
+#[async_trait]
+impl DnsTransport for ServFail {
+    async fn exchange(&self, query: &[u8]) -> Result<Vec<u8>, Bip353Error> {
+        let mut message = Message::from_vec(query).unwrap();
+        message.set_message_type(MessageType::Response).set_response_code(ResponseCode::ServFail);
+        Ok(message.to_vec().unwrap())
+    }
+}
+
+#[tokio::test]
+async fn test_servfail_is_transport_error() {
+    let resolver = Resolver::from_config(Bip353Config {
+        transport: TransportConfig::Custom(Arc::new(ServFail)),
+        ..Default::default()
+    }).unwrap();
+    let result = resolver.resolve("alice", "example").await;
+    assert!(matches!(result, Err(Bip353Error::TransportError(_))));
+}
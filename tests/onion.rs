@@ -0,0 +1,119 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bip353::testing::{TestServer, TestZone};
+use bip353::{
+    decode_dnssec_proof, encode_dnssec_query, Bip353Config, Bip353Error, OnionMessageTransport, PaymentType,
+    Resolver, TransportConfig,
+};
+use trust_dns_proto::rr::Name;
+
+const NAME: &str = "alice.user._bitcoin-payment.example.";
+
+/// Plays the part of a Lightning node answering `dnssec_query` messages
+struct InMemoryOnionTransport {
+    server: Arc<TestServer>,
+    /// Answer with a proof for this name instead of the one asked for
+    answer_for: Option<String>,
+}
+
+#[async_trait]
+impl OnionMessageTransport for InMemoryOnionTransport {
+    async fn send_dnssec_query(&self, payload: &[u8]) -> Result<Vec<u8>, Bip353Error> {
+        let name_len = payload[0] as usize;
+        let name = std::str::from_utf8(&payload[1..1 + name_len]).unwrap();
+        let name = self.answer_for.as_deref().unwrap_or(name);
+
+        let proof = self.server.proof(name)?;
+        let mut reply = vec![name.len() as u8];
+        reply.extend_from_slice(name.as_bytes());
+        reply.extend_from_slice(&(proof.len() as u16).to_be_bytes());
+        reply.extend_from_slice(&proof);
+        Ok(reply)
+    }
+}
+
+/// A server for the root and `example.`, publishing `records` as (name, TXT)
+async fn test_server(records: &[(&str, &str)]) -> Arc<TestServer> {
+    let mut example = TestZone::new("example.").unwrap();
+    for (name, txt) in records {
+        example.add_txt(name, &[txt]).unwrap();
+    }
+    Arc::new(TestServer::start(vec![TestZone::new(".").unwrap(), example]).await.unwrap())
+}
+
+fn resolver(server: &Arc<TestServer>, answer_for: Option<&str>) -> Resolver {
+    let trust_anchors = server.trust_anchors();
+    let transport = InMemoryOnionTransport { server: server.clone(), answer_for: answer_for.map(String::from) };
+    Resolver::from_config(Bip353Config {
+        transport: TransportConfig::OnionMessage(Arc::new(transport)),
+        trust_anchors,
+        ..Default::default()
+    }).unwrap()
+}
+
+#[test]
+fn test_dnssec_query_encoding() {
+    let name = Name::from_ascii(NAME).unwrap();
+    let payload = encode_dnssec_query(&name).unwrap();
+    assert_eq!(payload[0] as usize, NAME.len());
+    assert_eq!(&payload[1..], NAME.as_bytes());
+
+    let mut proof = vec![payload[0]];
+    proof.extend_from_slice(NAME.as_bytes());
+    proof.extend_from_slice(&[0, 3, 1, 2, 3]);
+    assert_eq!(decode_dnssec_proof(&proof).unwrap(), (name, vec![1, 2, 3]));
+    assert!(decode_dnssec_proof(&proof[..proof.len() - 1]).is_err());
+}
+
+#[tokio::test]
+async fn test_onion_resolution() {
+    let server = test_server(&[(NAME, "bitcoin:?lno=lno1qsgqmqvgm96frzdg8m0gc6n")]).await;
+
+    let proof = server.proof(NAME).unwrap();
+    let instruction = resolver(&server, None).resolve("alice", "example").await.unwrap();
+    assert_eq!(instruction.payment_type, PaymentType::LightningOffer);
+    assert_eq!(instruction.lightning_offer(), Some("lno1qsgqmqvgm96frzdg8m0gc6n"));
+    // The node's proof is kept, e.g. to pass on to whoever checks the payment
+    assert_eq!(instruction.proof, Some(proof));
+}
+
+#[tokio::test]
+async fn test_onion_rejects_tampered_proof() {
+    let server = test_server(&[(NAME, "bitcoin:bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4")]).await;
+    server.tamper_txt(NAME, &["bitcoin:bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq"]).unwrap();
+
+    let result = resolver(&server, None).resolve("alice", "example").await;
+    assert!(matches!(result, Err(Bip353Error::DnssecError(_))));
+}
+
+#[tokio::test]
+async fn test_onion_rejects_proof_for_other_name() {
+    let server = test_server(&[
+        (NAME, "bitcoin:bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"),
+        ("mallory.user._bitcoin-payment.example.", "bitcoin:bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq"),
+    ]).await;
+
+    let result = resolver(&server, Some("mallory.user._bitcoin-payment.example."))
+        .resolve("alice", "example")
+        .await;
+    assert!(matches!(result, Err(Bip353Error::DnssecError(_))));
+}
+
+#[tokio::test]
+async fn test_onion_wildcard_proof() {
+    // The proof carries the expanded RRset and the NSEC denying a closer match
+    let server = test_server(&[("*.user._bitcoin-payment.example.", "bitcoin:?lno=lno1qsgqmqvgm96frzdg8m0gc6n")]).await;
+
+    let instruction = resolver(&server, None).resolve("alice", "example").await.unwrap();
+    assert!(instruction.is_wildcard());
+    assert_eq!(instruction.lightning_offer(), Some("lno1qsgqmqvgm96frzdg8m0gc6n"));
+}
+
+#[tokio::test]
+async fn test_onion_denial_proof() {
+    let server = test_server(&[(NAME, "bitcoin:?lno=lno1qsgqmqvgm96frzdg8m0gc6n")]).await;
+
+    let result = resolver(&server, None).resolve("carol", "example").await;
+    assert!(matches!(result, Err(Bip353Error::NotFound(_))));
+}
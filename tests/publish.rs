@@ -0,0 +1,107 @@
+use std::time::Duration;
+
+use bip353::testing::{TestServer, TestZone};
+use bip353::{Bip353Config, Bip353Error, Publisher, Resolver, TsigKey, UpdateConfig};
+use trust_dns_proto::rr::Name;
+
+const KEY: &str = "hmac-sha256:update-key.:c2VjcmV0LXNoYXJlZC13aXRoLXRoZS1wcmltYXJ5LXNlcnZlcg==";
+const ONCHAIN: &str = "bitcoin:bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+const OFFER: &str = "bitcoin:?lno=lno1qsgqmqvgm96frzdg8m0gc6n";
+
+async fn updatable_server() -> TestServer {
+    let mut zone = TestZone::new("example.com.").unwrap();
+    zone.allow_updates(TsigKey::parse(KEY).unwrap());
+    TestServer::start(vec![zone]).await.unwrap()
+}
+
+fn publisher(server: &TestServer, key: &str) -> Publisher {
+    let mut config = UpdateConfig::new(server.tcp_addr(), Name::from_ascii("example.com.").unwrap(), TsigKey::parse(key).unwrap());
+    config.verify_attempts = 2;
+    config.verify_interval = Duration::from_millis(10);
+    Publisher::new(config, server.resolver().unwrap())
+}
+
+#[tokio::test]
+async fn test_add_publishes_record() {
+    let server = updatable_server().await;
+    let publisher = publisher(&server, KEY);
+
+    let instruction = publisher.add("alice", ONCHAIN).await.unwrap();
+    assert_eq!(instruction.uri, ONCHAIN);
+    // Long URIs are split into several character-strings
+    let long = format!("bitcoin:?lno=lno1{}", "q".repeat(600));
+    publisher.add("carol", &long).await.unwrap();
+
+    let resolver = server.resolver().unwrap();
+    assert_eq!(resolver.resolve("alice", "example.com").await.unwrap().uri, ONCHAIN);
+    assert_eq!(resolver.resolve("carol", "example.com").await.unwrap().uri, long);
+}
+
+#[tokio::test]
+async fn test_add_refuses_existing_record() {
+    let server = updatable_server().await;
+    let publisher = publisher(&server, KEY);
+
+    publisher.add("alice", ONCHAIN).await.unwrap();
+    let result = publisher.add("alice", OFFER).await;
+    assert!(matches!(result, Err(Bip353Error::UpdateError(msg)) if msg.contains("RR Set should not exist")));
+    assert_eq!(server.resolver().unwrap().resolve("alice", "example.com").await.unwrap().uri, ONCHAIN);
+}
+
+#[tokio::test]
+async fn test_replace_and_delete() {
+    let server = updatable_server().await;
+    let publisher = publisher(&server, KEY);
+
+    publisher.replace("alice", ONCHAIN).await.unwrap();
+    let instruction = publisher.replace("alice", OFFER).await.unwrap();
+    assert_eq!(instruction.uri, OFFER);
+
+    publisher.delete("alice").await.unwrap();
+    let result = server.resolver().unwrap().resolve("alice", "example.com").await;
+    assert!(matches!(result, Err(Bip353Error::NotFound(_))));
+}
+
+#[tokio::test]
+async fn test_wrong_key_is_rejected() {
+    let server = updatable_server().await;
+    let publisher = publisher(&server, "hmac-sha256:update-key.:d3Jvbmctc2VjcmV0");
+
+    let result = publisher.add("alice", ONCHAIN).await;
+    assert!(matches!(result, Err(Bip353Error::UpdateError(msg)) if msg.contains("Not authorized")));
+    let result = server.resolver().unwrap().resolve("alice", "example.com").await;
+    assert!(matches!(result, Err(Bip353Error::NotFound(_))));
+}
+
+#[tokio::test]
+async fn test_zone_without_updates_refuses() {
+    let server = TestServer::start(vec![TestZone::new("example.com.").unwrap()]).await.unwrap();
+    let result = publisher(&server, KEY).add("alice", ONCHAIN).await;
+    assert!(matches!(result, Err(Bip353Error::UpdateError(msg)) if msg.contains("Query Refused")));
+}
+
+#[tokio::test]
+async fn test_change_must_resolve() {
+    // The update goes through, but the verifying resolver trusts other keys
+    let server = updatable_server().await;
+    let other = TestServer::start(vec![TestZone::new("example.com.").unwrap()]).await.unwrap();
+    let resolver = Resolver::from_config(Bip353Config { trust_anchors: other.trust_anchors(), ..server.config() }).unwrap();
+    let mut config = UpdateConfig::new(server.tcp_addr(), Name::from_ascii("example.com.").unwrap(), TsigKey::parse(KEY).unwrap());
+    config.verify_attempts = 1;
+
+    let result = Publisher::new(config, resolver).add("alice", ONCHAIN).await;
+    assert!(matches!(result, Err(Bip353Error::UpdateError(msg)) if msg.contains("Update accepted")));
+}
+
+#[test]
+fn test_tsig_key_parsing() {
+    let key = TsigKey::parse("update-key:c2VjcmV0").unwrap();
+    assert_eq!(key.name(), &Name::from_ascii("update-key.").unwrap());
+    assert_eq!(key.algorithm().to_string(), "hmac-sha256");
+    assert!(!format!("{:?}", key).contains("secret"));
+
+    assert!(TsigKey::parse("hmac-sha512:update-key:c2VjcmV0").is_ok());
+    assert!(TsigKey::parse("hmac-md5:update-key:c2VjcmV0").is_err());
+    assert!(TsigKey::parse("update-key:not base64").is_err());
+    assert!(TsigKey::parse("update-key").is_err());
+}
@@ -0,0 +1,85 @@
+"""Tests for the parse-only Python API (no DNS involved)."""
+
+import json
+
+import pytest
+
+import bip353
+
+ONCHAIN = "bitcoin:bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"
+OFFER = "lno1pg257enxv4ezn9w8effvuw9h2f3upwuv9kzq8lqcc2cxk9gw29mkzmfxvtvz9j8c7dm4wa4zqnywept9xscrzve2qgrap0s4h6fe4m3pqnswk29uy087sx50tjj75s"
+
+
+def test_parse_address():
+    assert bip353.parse_address("₿alice@example.com") == ("alice", "example.com")
+    assert bip353.parse_address("  bob@example.org ") == ("bob", "example.org")
+
+
+@pytest.mark.parametrize("address", ["aliceexample.com", "@example.com", "alice@", "a@b@c", ""])
+def test_parse_invalid_address(address):
+    with pytest.raises(bip353.InvalidAddressError):
+        bip353.parse_address(address)
+
+
+def test_parse_uri_types():
+    assert bip353.parse_uri(ONCHAIN).payment_type == bip353.PaymentType.OnChain
+    assert bip353.parse_uri("bitcoin:?lightning=lnbc1").payment_type == bip353.PaymentType.Lightning
+    assert bip353.parse_uri(f"bitcoin:?lno={OFFER}").payment_type == bip353.PaymentType.LightningOffer
+    assert bip353.parse_uri("bitcoin:").payment_type == bip353.PaymentType.Unknown
+    assert str(bip353.PaymentType.LightningOffer) == "lightning-offer"
+
+
+def test_parse_invalid_uri():
+    with pytest.raises(bip353.InvalidUriError):
+        bip353.parse_uri("lightning:lnbc1")
+
+
+def test_invalid_input_is_value_error():
+    # Callers written before the hierarchy existed catch ValueError
+    with pytest.raises(ValueError):
+        bip353.parse_address("not-an-address")
+    with pytest.raises(ValueError):
+        bip353.parse_uri("lightning:lnbc1")
+    for exc in (bip353.InvalidAddressError, bip353.InvalidUriError):
+        assert issubclass(exc, ValueError)
+        assert exc.__module__ == "bip353"
+
+
+def test_exception_hierarchy():
+    for exc in (
+        bip353.DnsError,
+        bip353.DnssecError,
+        bip353.InvalidAddressError,
+        bip353.InvalidUriError,
+        bip353.NoRecordError,
+        bip353.MultipleRecordsError,
+    ):
+        assert issubclass(exc, bip353.Bip353Error)
+    # Absence and transport failures are DNS errors too
+    for exc in (bip353.NotFoundError, bip353.UnverifiedNotFoundError, bip353.TransportError):
+        assert issubclass(exc, bip353.DnsError)
+
+
+def test_instruction_conversions():
+    instruction = bip353.parse_uri(f"{ONCHAIN}?amount=0.01&label=Test")
+    assert instruction.is_reusable
+    assert instruction.parameters == {"amount": "0.01", "label": "Test"}
+
+    expected = {
+        "uri": f"{ONCHAIN}?amount=0.01&label=Test",
+        "payment_type": "on-chain",
+        "is_reusable": True,
+        "parameters": {"amount": "0.01", "label": "Test"},
+        # Parsed, not resolved, so nothing was validated
+        "dnssec_status": "indeterminate",
+    }
+    assert instruction.to_dict() == expected
+    assert json.loads(instruction.to_json()) == expected
+    assert "on-chain" in repr(instruction)
+
+
+def test_instruction_equality():
+    assert bip353.parse_uri(ONCHAIN) == bip353.parse_uri(ONCHAIN)
+    assert bip353.parse_uri(ONCHAIN) != bip353.parse_uri("bitcoin:?lightning=lnbc1")
+    assert bip353.parse_uri(ONCHAIN) != ONCHAIN
+    assert len({bip353.parse_uri(ONCHAIN), bip353.parse_uri(ONCHAIN)}) == 1
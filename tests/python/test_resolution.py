@@ -0,0 +1,102 @@
+"""Tests for resolution against the in-process signed DNS server.
+
+``bip353.TestServer`` only exists in builds with the ``test-support``
+feature; its resolvers validate DNSSEC against the server's own keys.
+"""
+
+import asyncio
+
+import pytest
+
+import bip353
+
+ONCHAIN = "bitcoin:bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"
+NAME = "alice.user._bitcoin-payment.example.com"
+
+
+def test_resolve_onchain():
+    server = bip353.TestServer({NAME: [ONCHAIN]})
+    instruction = server.resolver().resolve_address("₿alice@example.com")
+    assert instruction.uri == ONCHAIN
+    assert instruction.payment_type == bip353.PaymentType.OnChain
+    assert instruction.dnssec_status == "secure"
+    assert instruction.to_dict()["dnssec_status"] == "secure"
+
+
+def test_resolutions_compare_equal():
+    # Each carries its own proof; equality only looks at the payment
+    server = bip353.TestServer({NAME: [ONCHAIN]})
+    resolved = server.resolver().resolve_address("alice@example.com")
+    assert resolved == server.resolver().resolve_address("alice@example.com")
+    assert resolved == bip353.parse_uri(ONCHAIN)
+    assert hash(resolved) == hash(bip353.parse_uri(ONCHAIN))
+
+
+def test_resolve_chunked_record():
+    # TXT records longer than 255 bytes are split into several strings
+    offer = "lno1" + "q" * 300
+    uri = f"bitcoin:?lno={offer}"
+    server = bip353.TestServer({NAME: [[uri[:255], uri[255:]]]})
+    instruction = server.resolver().resolve_address("alice@example.com")
+    assert instruction.uri == uri
+    assert instruction.payment_type == bip353.PaymentType.LightningOffer
+
+
+def test_resolve_ignores_other_txt():
+    server = bip353.TestServer({NAME: ["v=spf1 -all", ONCHAIN]})
+    assert server.resolver().resolve_address("alice@example.com").uri == ONCHAIN
+
+
+def test_resolve_no_bitcoin_record():
+    server = bip353.TestServer({NAME: ["v=spf1 -all"]})
+    with pytest.raises(bip353.NoRecordError):
+        server.resolver().resolve_address("alice@example.com")
+
+
+def test_resolve_multiple_records():
+    server = bip353.TestServer({NAME: [ONCHAIN, "bitcoin:?lightning=lnbc1"]})
+    with pytest.raises(bip353.MultipleRecordsError):
+        server.resolver().resolve_address("alice@example.com")
+
+
+def test_resolve_missing_name():
+    # The zone's NSEC chain proves the absence
+    server = bip353.TestServer({NAME: [ONCHAIN]})
+    with pytest.raises(bip353.NotFoundError):
+        server.resolver().resolve_address("nobody@example.com")
+
+
+def test_nameservers_still_validate():
+    # Pointed at the server directly, the resolver trusts only the root
+    # anchors, which never signed the test zone
+    server = bip353.TestServer({NAME: [ONCHAIN]})
+    resolver = bip353.PyResolver(nameservers=[server.address])
+    with pytest.raises(bip353.DnssecError):
+        resolver.resolve_address("alice@example.com")
+
+
+def test_resolve_invalid_address():
+    server = bip353.TestServer({})
+    with pytest.raises(bip353.InvalidAddressError):
+        server.resolver().resolve_address("not-an-address")
+
+
+def test_async_resolve_concurrently():
+    server = bip353.TestServer({
+        NAME: [ONCHAIN],
+        "bob.user._bitcoin-payment.example.com": ["bitcoin:?lightning=lnbc1"],
+    })
+
+    async def main():
+        resolver = server.async_resolver()
+        return await asyncio.gather(
+            resolver.resolve_address("alice@example.com"),
+            resolver.resolve_address("bob@example.com"),
+            resolver.resolve_address("nobody@example.com"),
+            return_exceptions=True,
+        )
+
+    alice, bob, nobody = asyncio.run(main())
+    assert alice.payment_type == bip353.PaymentType.OnChain
+    assert bob.payment_type == bip353.PaymentType.Lightning
+    assert isinstance(nobody, bip353.NotFoundError)
@@ -0,0 +1,91 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use bip353::testing::{TestServer, TestZone};
+use bip353::{
+    verify_proof, AlgorithmPolicy, AsyncIoTransport, Bip353Config, Bip353Error, DnssecStatus, Resolver,
+    Socks5Config, TcpConfig, TransportConfig,
+};
+use futures_lite::future::block_on;
+
+const NAME: &str = "alice.user._bitcoin-payment.example.";
+const URI: &str = "bitcoin:bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+
+/// Start a server for the root and `example.` on `runtime`, publishing a
+/// URI for alice
+fn test_server(runtime: &tokio::runtime::Runtime) -> TestServer {
+    runtime.block_on(async {
+        let mut example = TestZone::new("example.").unwrap();
+        example.add_payment_instruction("alice", URI).unwrap();
+        TestServer::start(vec![TestZone::new(".").unwrap(), example]).await.unwrap()
+    })
+}
+
+#[test]
+fn test_verify_proof() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let server = test_server(&runtime);
+    let anchors = server.trust_anchors();
+    let policy = AlgorithmPolicy::default();
+
+    let instruction = verify_proof("alice", "example", &server.proof(NAME).unwrap(), &anchors, &policy).unwrap();
+    assert_eq!(instruction.uri, URI);
+    assert_eq!(instruction.dnssec.status, DnssecStatus::Secure);
+
+    // A proof for one name proves nothing about another
+    let result = verify_proof("bob", "example", &server.proof(NAME).unwrap(), &anchors, &policy);
+    assert!(result.is_err());
+    let proof = server.proof("carol.user._bitcoin-payment.example.").unwrap();
+    let result = verify_proof("carol", "example", &proof, &anchors, &policy);
+    assert!(matches!(result, Err(Bip353Error::NotFound(_))));
+
+    server.tamper_txt(NAME, &["bitcoin:bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq"]).unwrap();
+    let result = verify_proof("alice", "example", &server.proof(NAME).unwrap(), &anchors, &policy);
+    assert!(matches!(result, Err(Bip353Error::DnssecError(_))));
+}
+
+#[test]
+fn test_custom_transport_without_tokio() {
+    // The test server runs on Tokio; its in-process transport does not
+    let server_runtime = tokio::runtime::Runtime::new().unwrap();
+    let server = test_server(&server_runtime);
+    let resolver = Resolver::from_config(Bip353Config {
+        transport: TransportConfig::Custom(server.transport()),
+        trust_anchors: server.trust_anchors(),
+        ..Default::default()
+    }).unwrap();
+
+    // No Tokio runtime anywhere on this thread
+    assert_eq!(block_on(resolver.resolve("alice", "example")).unwrap().uri, URI);
+    let results = block_on(resolver.resolve_many(&["alice@example", "carol@example"], 2, |_, _| {}));
+    assert_eq!(results[0].as_ref().unwrap().uri, URI);
+    assert!(matches!(results[1], Err(Bip353Error::NotFound(_))));
+}
+
+#[test]
+fn test_async_io_transport() {
+    // The test server runs on Tokio; the resolver does not
+    let server_runtime = tokio::runtime::Runtime::new().unwrap();
+    let server = server_runtime.block_on(async {
+        let mut zone = TestZone::new("example.com.").unwrap();
+        zone.add_payment_instruction("alice", URI).unwrap();
+        TestServer::start(vec![zone]).await.unwrap()
+    });
+
+    let transport = AsyncIoTransport::new(TcpConfig::new(server.tcp_addr())).unwrap();
+    let resolver = Resolver::from_config(Bip353Config {
+        transport: TransportConfig::Custom(Arc::new(transport)),
+        trust_anchors: server.trust_anchors(),
+        ..Default::default()
+    }).unwrap();
+    let instruction = block_on(resolver.resolve("alice", "example.com")).unwrap();
+    assert_eq!(instruction.uri, URI);
+    assert_eq!(instruction.dnssec.status, DnssecStatus::Secure);
+}
+
+#[test]
+fn test_async_io_transport_refuses_proxy() {
+    let proxy: SocketAddr = "127.0.0.1:9050".parse().unwrap();
+    let config = TcpConfig { proxy: Some(Socks5Config::new(proxy)), ..TcpConfig::new("127.0.0.1:53".parse().unwrap()) };
+    assert!(matches!(AsyncIoTransport::new(config), Err(Bip353Error::DnsError(_))));
+}
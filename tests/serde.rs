@@ -0,0 +1,171 @@
+//! Snapshots of the JSON shapes; a failure here means a breaking change
+//! for anyone storing or sending these values
+
+use bip353::{
+    Alias, AliasKind, Bip353Error, DnssecInfo, DnssecStatus, Finding, HumanReadableName, KeyId, LintCheck,
+    PaymentInstruction, PaymentType, Pin, Severity, ValidationMode,
+};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use trust_dns_proto::rr::dnssec::Algorithm;
+use trust_dns_proto::rr::Name;
+
+fn name(name: &str) -> Name {
+    Name::from_ascii(name).unwrap()
+}
+
+/// Check `value` serializes to `expected` and back to itself
+fn assert_snapshot<T: Serialize + DeserializeOwned + PartialEq + std::fmt::Debug>(value: &T, expected: &str) {
+    let json = serde_json::to_string_pretty(value).unwrap();
+    assert_eq!(json, expected);
+    assert_eq!(&serde_json::from_str::<T>(&json).unwrap(), value);
+}
+
+fn signed_info() -> DnssecInfo {
+    DnssecInfo {
+        status: DnssecStatus::Secure,
+        signer: Some(name("example.com.")),
+        algorithm: Some(Algorithm::ED25519),
+        key_tag: Some(12345),
+        anchor_algorithm: Some(Algorithm::ED25519),
+        anchor_key_tag: Some(54321),
+        inception: Some(1700000000),
+        expiration: Some(1702592000),
+        wildcard: None,
+    }
+}
+
+#[test]
+fn test_payment_instruction() {
+    let mut instruction = PaymentInstruction::from_uri(
+        "bitcoin:bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4?lno=lno1qsgqmqvgm96frzdg8m0gc6n&label=Alice",
+    ).unwrap();
+    instruction.dnssec = signed_info();
+    instruction.aliases = vec![Alias {
+        kind: AliasKind::Cname,
+        owner: name("alice.user._bitcoin-payment.example.org."),
+        name: name("alice.user._bitcoin-payment.example.org."),
+        target: name("alice.user._bitcoin-payment.example.com."),
+        dnssec: DnssecInfo { status: DnssecStatus::Insecure, ..Default::default() },
+    }];
+    instruction.proof = Some(vec![0x00, 0x00, 0x10, 0x00, 0x01, 0xab]);
+
+    assert_snapshot(&instruction, r#"{
+  "uri": "bitcoin:bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4?lno=lno1qsgqmqvgm96frzdg8m0gc6n&label=Alice",
+  "payment_type": "lightning-offer",
+  "is_reusable": true,
+  "parameters": {
+    "label": "Alice",
+    "lno": "lno1qsgqmqvgm96frzdg8m0gc6n"
+  },
+  "dnssec": {
+    "status": "secure",
+    "signer": "example.com.",
+    "algorithm": 15,
+    "key_tag": 12345,
+    "anchor_algorithm": 15,
+    "anchor_key_tag": 54321,
+    "inception": 1700000000,
+    "expiration": 1702592000,
+    "wildcard": null
+  },
+  "aliases": [
+    {
+      "kind": "cname",
+      "owner": "alice.user._bitcoin-payment.example.org.",
+      "name": "alice.user._bitcoin-payment.example.org.",
+      "target": "alice.user._bitcoin-payment.example.com.",
+      "dnssec": {
+        "status": "insecure",
+        "signer": null,
+        "algorithm": null,
+        "key_tag": null,
+        "anchor_algorithm": null,
+        "anchor_key_tag": null,
+        "inception": null,
+        "expiration": null,
+        "wildcard": null
+      }
+    }
+  ],
+  "proof": "0000100001ab"
+}"#);
+
+    // Instructions stored before proofs were kept still load
+    let mut json: serde_json::Value = serde_json::to_value(&instruction).unwrap();
+    json.as_object_mut().unwrap().remove("proof");
+    let stored: PaymentInstruction = serde_json::from_value(json.clone()).unwrap();
+    assert_eq!(stored.proof, None);
+
+    // So do those stored before the anchoring key was recorded
+    let dnssec = json["dnssec"].as_object_mut().unwrap();
+    dnssec.remove("anchor_algorithm");
+    dnssec.remove("anchor_key_tag");
+    let stored: PaymentInstruction = serde_json::from_value(json).unwrap();
+    assert_eq!(stored.dnssec.anchor_key_tag, None);
+}
+
+#[test]
+fn test_enums() {
+    let payment_types = [PaymentType::OnChain, PaymentType::Lightning, PaymentType::LightningOffer, PaymentType::Unknown];
+    for payment_type in payment_types {
+        // The same names as `as_str` and the bindings
+        assert_snapshot(&payment_type, &format!("\"{}\"", payment_type.as_str()));
+    }
+    for status in [DnssecStatus::Secure, DnssecStatus::Insecure, DnssecStatus::Bogus, DnssecStatus::Indeterminate] {
+        assert_snapshot(&status, &format!("\"{}\"", status.as_str()));
+    }
+    assert_snapshot(&AliasKind::Dname, "\"dname\"");
+    assert_snapshot(&ValidationMode::AllowUnvalidated, "\"allow-unvalidated\"");
+    assert_snapshot(&LintCheck::UnknownRequiredParameter, "\"unknown-required-parameter\"");
+    assert_snapshot(&Severity::Warning, "\"warning\"");
+}
+
+#[test]
+fn test_errors() {
+    let json = serde_json::to_string(&Bip353Error::NotFound("alice has no TXT records".into())).unwrap();
+    assert_eq!(json, r#"{"kind":"not-found","message":"alice has no TXT records"}"#);
+    let json = serde_json::to_string(&Bip353Error::UnverifiedNotFound("x".into())).unwrap();
+    assert_eq!(json, r#"{"kind":"unverified-not-found","message":"x"}"#);
+    let json = serde_json::to_string(&Bip353Error::DnssecError("x".into())).unwrap();
+    assert_eq!(json, r#"{"kind":"dnssec-error","message":"x"}"#);
+
+    let error: Bip353Error = serde_json::from_str(r#"{"kind":"transport-error","message":"timed out"}"#).unwrap();
+    assert!(matches!(error, Bip353Error::TransportError(msg) if msg == "timed out"));
+}
+
+#[test]
+fn test_address_book_types() {
+    let name: HumanReadableName = "₿alice@example.com".parse().unwrap();
+    assert_snapshot(&name, "\"₿alice@example.com\"");
+    assert!(serde_json::from_str::<HumanReadableName>("\"alice\"").is_err());
+
+    let pin = Pin {
+        uri: "bitcoin:bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4".into(),
+        status: DnssecStatus::Secure,
+        key: KeyId::of(&signed_info()),
+        first_seen: 1700000000,
+        last_seen: 1700086400,
+    };
+    assert_snapshot(&pin, r#"{
+  "uri": "bitcoin:bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+  "status": "secure",
+  "key": {
+    "zone": "example.com.",
+    "algorithm": 15,
+    "key_tag": 54321
+  },
+  "first_seen": 1700000000,
+  "last_seen": 1700086400
+}"#);
+}
+
+#[test]
+fn test_finding() {
+    let findings = bip353::lint_records(&name("alice.user._bitcoin-payment.example.com."), &[], &Default::default());
+    let finding: &Finding = &findings[0];
+    let json = serde_json::to_value(finding).unwrap();
+    assert_eq!(json["check"], "no-bitcoin-record");
+    assert_eq!(json["severity"], "error");
+    assert!(json["message"].is_string());
+}
@@ -0,0 +1,190 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bip353::signer::{DenialConfig, SignedZone, SignerConfig, SigningKey, ZoneSigner};
+use bip353::{
+    payment_record, Bip353Config, Bip353Error, DnssecStatus, OnionMessageTransport, Resolver, TransportConfig,
+    TrustAnchors,
+};
+use trust_dns_proto::rr::dnssec::rdata::DNSSECRData;
+use trust_dns_proto::rr::dnssec::Algorithm;
+use trust_dns_proto::rr::rdata::SOA;
+use trust_dns_proto::rr::{Name, RData, Record, RecordType};
+use trust_dns_proto::serialize::binary::{BinEncodable, BinEncoder, EncodeMode};
+
+const URI: &str = "bitcoin:bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+
+fn name(name: &str) -> Name {
+    Name::from_ascii(name).unwrap()
+}
+
+fn zone_records() -> Vec<Record> {
+    let soa = SOA::new(name("ns.example."), name("hostmaster.example."), 1, 3600, 600, 86400, 300);
+    vec![
+        Record::from_rdata(name("example."), 3600, RData::SOA(soa)),
+        Record::from_rdata(name("example."), 3600, RData::NS(name("ns.example."))),
+        payment_record("alice", "example", URI, 3600).unwrap(),
+    ]
+}
+
+fn signer(algorithm: Algorithm, denial: DenialConfig) -> ZoneSigner {
+    let ksk = SigningKey::generate(algorithm, true).unwrap();
+    let zsk = SigningKey::generate(algorithm, false).unwrap();
+    ZoneSigner::new(name("example."), ksk, zsk, SignerConfig { denial, ..Default::default() })
+}
+
+/// Answers every `dnssec_query` with the whole signed zone as the proof
+struct ZoneProof(SignedZone);
+
+#[async_trait]
+impl OnionMessageTransport for ZoneProof {
+    async fn send_dnssec_query(&self, payload: &[u8]) -> Result<Vec<u8>, Bip353Error> {
+        let mut proof = Vec::new();
+        let mut encoder = BinEncoder::with_mode(&mut proof, EncodeMode::Signing);
+        for record in self.0.records() {
+            record.emit(&mut encoder).unwrap();
+        }
+        let mut reply = payload.to_vec();
+        reply.extend_from_slice(&(proof.len() as u16).to_be_bytes());
+        reply.extend_from_slice(&proof);
+        Ok(reply)
+    }
+}
+
+fn resolver(zone: SignedZone) -> Resolver {
+    let mut trust_anchors = TrustAnchors::new();
+    if let Some(RData::DNSSEC(DNSSECRData::DS(ds))) = zone.ds_record().data() {
+        trust_anchors.add_ds(zone.apex().clone(), ds.clone());
+    }
+    Resolver::from_config(Bip353Config {
+        transport: TransportConfig::OnionMessage(Arc::new(ZoneProof(zone))),
+        trust_anchors,
+        ..Default::default()
+    }).unwrap()
+}
+
+fn nsec3() -> DenialConfig {
+    DenialConfig::Nsec3 { iterations: 0, salt: vec![0xaa, 0xbb], opt_out: false }
+}
+
+#[tokio::test]
+async fn test_signed_zone_validates() {
+    for algorithm in [Algorithm::ED25519, Algorithm::ECDSAP256SHA256] {
+        let zone = signer(algorithm, nsec3()).sign(&zone_records()).unwrap();
+        let instruction = resolver(zone).resolve("alice", "example").await.unwrap();
+        assert_eq!(instruction.uri, URI);
+        assert_eq!(instruction.dnssec.status, DnssecStatus::Secure);
+    }
+}
+
+#[tokio::test]
+async fn test_denial_chains_prove_absence() {
+    for denial in [DenialConfig::Nsec, nsec3()] {
+        let mut records = zone_records();
+        // An empty non-terminal: bob only exists as the parent of another name
+        records.push(payment_record("x.bob", "example", URI, 3600).unwrap());
+        let zone = signer(Algorithm::ED25519, denial).sign(&records).unwrap();
+        let resolver = resolver(zone);
+
+        let result = resolver.resolve("carol", "example").await;
+        assert!(matches!(result, Err(Bip353Error::NotFound(_))), "{:?}", result);
+        let result = resolver.resolve("bob", "example").await;
+        assert!(matches!(result, Err(Bip353Error::NotFound(_))), "{:?}", result);
+    }
+}
+
+#[tokio::test]
+async fn test_loaded_keys_sign_like_generated_ones() {
+    let original = signer(Algorithm::ED25519, nsec3());
+    let ksk = SigningKey::from_pkcs8(Algorithm::ED25519, original.ksk().to_pkcs8(), true).unwrap();
+    let zsk = SigningKey::from_pkcs8(Algorithm::ED25519, original.zsk().to_pkcs8(), false).unwrap();
+    assert_eq!(ksk.dnskey(), original.ksk().dnskey());
+    assert_eq!(ksk.key_tag(), original.ksk().key_tag());
+
+    let loaded = ZoneSigner::new(name("example."), ksk, zsk, SignerConfig::default());
+    let zone = loaded.sign(&zone_records()).unwrap();
+    assert_eq!(zone.ds_record(), original.sign(&zone_records()).unwrap().ds_record());
+    assert!(resolver(zone).resolve("alice", "example").await.is_ok());
+}
+
+#[test]
+fn test_delegations_and_glue() {
+    let mut records = zone_records();
+    for child in ["secure.example.", "insecure.example."] {
+        records.push(Record::from_rdata(name(child), 3600, RData::NS(name(&format!("ns.{}", child)))));
+        records.push(Record::from_rdata(name(&format!("ns.{}", child)), 3600, RData::A([192, 0, 2, 1].into())));
+    }
+    let child_ksk = SigningKey::generate(Algorithm::ED25519, true).unwrap();
+    let ds = child_ksk.ds(&name("secure.example.")).unwrap();
+    records.push(Record::from_rdata(name("secure.example."), 3600, RData::DNSSEC(DNSSECRData::DS(ds))));
+
+    let opt_out = DenialConfig::Nsec3 { iterations: 0, salt: Vec::new(), opt_out: true };
+    let zone = signer(Algorithm::ED25519, opt_out).sign(&records).unwrap();
+    let signed = |owner: &str, covered: RecordType| {
+        zone.records().iter().any(|r| match r.data() {
+            Some(RData::DNSSEC(DNSSECRData::SIG(sig))) => r.name() == &name(owner) && sig.type_covered() == covered,
+            _ => false,
+        })
+    };
+    assert!(signed("secure.example.", RecordType::DS));
+    assert!(!signed("secure.example.", RecordType::NS));
+    assert!(!signed("ns.secure.example.", RecordType::A));
+    assert!(signed("alice.user._bitcoin-payment.example.", RecordType::TXT));
+
+    // Opt-out leaves the insecure delegation and the glue out of the chain
+    let nsec3_count = zone.records().iter().filter(|r| r.record_type() == RecordType::NSEC3).count();
+    // example, secure, alice and the empty non-terminals user and _bitcoin-payment
+    assert_eq!(nsec3_count, 5);
+}
+
+#[test]
+fn test_zone_file() {
+    let zone = signer(Algorithm::ED25519, nsec3()).sign(&zone_records()).unwrap();
+    let zone_file = zone.to_zone_file();
+    let lines: Vec<&str> = zone_file.lines().collect();
+
+    assert_eq!(lines[0], "$ORIGIN example.");
+    assert!(lines[1].starts_with("example. 3600 IN SOA ns.example. hostmaster.example. 1 "));
+    assert!(zone_file.contains(&format!("alice.user._bitcoin-payment.example. 3600 IN TXT \"{}\"", URI)));
+    assert!(lines.iter().any(|l| l.contains(" IN RRSIG TXT ED25519 ")));
+    assert!(lines.iter().any(|l| l.starts_with("example. 3600 IN DNSKEY 257 3 15 ")));
+    assert!(lines.iter().any(|l| l.starts_with("example. 0 IN NSEC3PARAM 1 0 0 AABB")));
+    assert!(lines.iter().any(|l| l.contains(" IN NSEC3 1 0 0 AABB ")));
+    assert_eq!(lines.len(), zone.records().len() + 1);
+
+    let ds = zone.ds_record().to_string();
+    assert!(ds.starts_with(&format!("example. 3600 IN DS {} 15 2 ", ksk_tag(&zone))));
+}
+
+fn ksk_tag(zone: &SignedZone) -> u16 {
+    zone.records().iter()
+        .find_map(|r| match r.data() {
+            Some(RData::DNSSEC(DNSSECRData::DNSKEY(key))) if key.secure_entry_point() => key.calculate_key_tag().ok(),
+            _ => None,
+        })
+        .unwrap()
+}
+
+#[test]
+fn test_signing_errors() {
+    let signer = signer(Algorithm::ED25519, DenialConfig::Nsec);
+
+    // No SOA
+    let result = signer.sign(&[payment_record("alice", "example", URI, 3600).unwrap()]);
+    assert!(matches!(result, Err(Bip353Error::InvalidRecord(_))));
+
+    // Out of zone
+    let mut records = zone_records();
+    records.push(payment_record("alice", "example.org", URI, 3600).unwrap());
+    assert!(matches!(signer.sign(&records), Err(Bip353Error::InvalidRecord(_))));
+
+    // Too many NSEC3 iterations for validators to accept
+    let config = SignerConfig { denial: DenialConfig::Nsec3 { iterations: 500, salt: Vec::new(), opt_out: false }, ..Default::default() };
+    let ksk = SigningKey::generate(Algorithm::ED25519, true).unwrap();
+    let zsk = SigningKey::generate(Algorithm::ED25519, false).unwrap();
+    let result = ZoneSigner::new(name("example."), ksk, zsk, config).sign(&zone_records());
+    assert!(matches!(result, Err(Bip353Error::DnssecError(_))));
+
+    // Unsupported algorithm
+    assert!(SigningKey::generate(Algorithm::RSASHA256, true).is_err());
+}
@@ -0,0 +1,161 @@
+use std::collections::HashSet;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+
+use bip353::testing::{TestServer, TestZone, TLS_HOST};
+use bip353::{Bip353Config, DohMethod, PaymentType, Resolver, Socks5Config, StreamIsolation, TcpConfig, TransportConfig};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+const NAME: &str = "alice.user._bitcoin-payment.example.";
+
+/// A CONNECT request seen by the proxy
+#[derive(Debug, Clone)]
+struct ProxyRequest {
+    credentials: Option<(String, String)>,
+    target: String,
+}
+
+/// A SOCKS5 proxy that logs requests and connects every host name to localhost
+async fn start_proxy() -> (SocketAddr, Arc<Mutex<Vec<ProxyRequest>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let log = Arc::new(Mutex::new(Vec::new()));
+
+    let requests = log.clone();
+    tokio::spawn(async move {
+        loop {
+            let (mut client, _) = listener.accept().await.unwrap();
+            let requests = requests.clone();
+            tokio::spawn(async move {
+                let mut head = [0u8; 2];
+                client.read_exact(&mut head).await.unwrap();
+                let mut methods = vec![0u8; head[1] as usize];
+                client.read_exact(&mut methods).await.unwrap();
+
+                let credentials = if methods.contains(&0x02) {
+                    client.write_all(&[0x05, 0x02]).await.unwrap();
+                    let mut version_len = [0u8; 2];
+                    client.read_exact(&mut version_len).await.unwrap();
+                    let mut username = vec![0u8; version_len[1] as usize];
+                    client.read_exact(&mut username).await.unwrap();
+                    let mut password = vec![0u8; client.read_u8().await.unwrap() as usize];
+                    client.read_exact(&mut password).await.unwrap();
+                    client.write_all(&[0x01, 0x00]).await.unwrap();
+                    Some((String::from_utf8(username).unwrap(), String::from_utf8(password).unwrap()))
+                } else {
+                    client.write_all(&[0x05, 0x00]).await.unwrap();
+                    None
+                };
+
+                let mut request = [0u8; 4];
+                client.read_exact(&mut request).await.unwrap();
+                let (target, host) = match request[3] {
+                    0x01 => {
+                        let mut ip = [0u8; 4];
+                        client.read_exact(&mut ip).await.unwrap();
+                        (Ipv4Addr::from(ip).to_string(), Ipv4Addr::from(ip))
+                    }
+                    0x03 => {
+                        let mut name = vec![0u8; client.read_u8().await.unwrap() as usize];
+                        client.read_exact(&mut name).await.unwrap();
+                        (String::from_utf8(name).unwrap(), Ipv4Addr::LOCALHOST)
+                    }
+                    atyp => panic!("unexpected address type {}", atyp),
+                };
+                let port = client.read_u16().await.unwrap();
+                requests.lock().unwrap().push(ProxyRequest { credentials, target: format!("{}:{}", target, port) });
+
+                let mut upstream = TcpStream::connect((host, port)).await.unwrap();
+                client.write_all(&[0x05, 0x00, 0x00, 0x01, 0, 0, 0, 0, 0, 0]).await.unwrap();
+                let _ = tokio::io::copy_bidirectional(&mut client, &mut upstream).await;
+            });
+        }
+    });
+
+    (address, log)
+}
+
+/// A server for the root and `example.`, publishing a URI for alice
+async fn test_server() -> TestServer {
+    let mut example = TestZone::new("example.").unwrap();
+    example.add_txt(NAME, &["bitcoin:bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"]).unwrap();
+    TestServer::start(vec![TestZone::new(".").unwrap(), example]).await.unwrap()
+}
+
+#[tokio::test]
+async fn test_tcp_through_proxy_isolates_lookups() {
+    let server = test_server().await;
+    let dns = server.tcp_addr();
+    let (proxy, log) = start_proxy().await;
+
+    let mut tcp = TcpConfig::new(dns);
+    tcp.proxy = Some(Socks5Config::new(proxy));
+    let resolver = Resolver::from_config(Bip353Config {
+        transport: TransportConfig::Tcp(tcp),
+        trust_anchors: server.trust_anchors(),
+        ..Default::default()
+    }).unwrap();
+
+    for _ in 0..2 {
+        let instruction = resolver.resolve("alice", "example").await.unwrap();
+        assert_eq!(instruction.payment_type, PaymentType::OnChain);
+    }
+
+    let requests = log.lock().unwrap();
+    assert_eq!(requests.len(), 8);
+    assert!(requests.iter().all(|r| r.target == dns.to_string()));
+
+    // Queries within a lookup share credentials; the two lookups do not
+    let first: HashSet<_> = requests[..4].iter().map(|r| r.credentials.clone().unwrap()).collect();
+    let second: HashSet<_> = requests[4..].iter().map(|r| r.credentials.clone().unwrap()).collect();
+    assert_eq!(first.len(), 1);
+    assert_eq!(second.len(), 1);
+    assert_ne!(first, second);
+}
+
+#[tokio::test]
+async fn test_doh_through_proxy_sends_host_name() {
+    let server = test_server().await;
+    let (proxy, log) = start_proxy().await;
+
+    let mut doh = server.doh_config(DohMethod::Post);
+    doh.bootstrap_ips = Vec::new();
+    doh.proxy = Some(Socks5Config {
+        address: proxy,
+        isolation: StreamIsolation::Credentials { username: "wallet".into(), password: "secret".into() },
+    });
+    let resolver = Resolver::from_config(Bip353Config {
+        transport: TransportConfig::Https(doh),
+        trust_anchors: server.trust_anchors(),
+        ..Default::default()
+    }).unwrap();
+
+    let instruction = resolver.resolve("alice", "example").await.unwrap();
+    assert_eq!(instruction.payment_type, PaymentType::OnChain);
+
+    let requests = log.lock().unwrap();
+    assert!(!requests.is_empty());
+    for request in requests.iter() {
+        // The proxy, not the local resolver, resolves the DoH host name
+        assert_eq!(request.target, format!("{}:{}", TLS_HOST, server.doh_addr().port()));
+        assert_eq!(request.credentials, Some(("wallet".into(), "secret".into())));
+    }
+}
+
+#[tokio::test]
+async fn test_proxy_without_isolation() {
+    let server = test_server().await;
+    let (proxy, log) = start_proxy().await;
+
+    let mut tcp = TcpConfig::new(server.tcp_addr());
+    tcp.proxy = Some(Socks5Config { address: proxy, isolation: StreamIsolation::None });
+    let resolver = Resolver::from_config(Bip353Config {
+        transport: TransportConfig::Tcp(tcp),
+        trust_anchors: server.trust_anchors(),
+        ..Default::default()
+    }).unwrap();
+
+    resolver.resolve("alice", "example").await.unwrap();
+    assert!(log.lock().unwrap().iter().all(|r| r.credentials.is_none()));
+}
@@ -0,0 +1,162 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use bip353::testing::{TestServer, TestTransport, TestZone};
+use bip353::{
+    AddressBook, Bip353Config, Bip353Error, CacheConfig, Cipher, FileStorage, HumanReadableName, MemoryStorage,
+    PaymentInstruction, Resolver, Storage, TransportConfig,
+};
+
+const URI: &str = "bitcoin:bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+
+/// A path in the temporary directory that is removed when dropped
+struct TempPath(PathBuf);
+
+impl TempPath {
+    fn new() -> Self {
+        Self(std::env::temp_dir().join(format!("bip353-storage-{:016x}.json", rand::random::<u64>())))
+    }
+}
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// XOR with a fixed byte; enough to show the file is passed through the cipher
+struct XorCipher(u8);
+
+impl Cipher for XorCipher {
+    fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, Bip353Error> {
+        Ok(plaintext.iter().map(|b| b ^ self.0).collect())
+    }
+
+    fn decrypt(&self, ciphertext: &[u8]) -> Result<Vec<u8>, Bip353Error> {
+        Ok(ciphertext.iter().map(|b| b ^ self.0).collect())
+    }
+}
+
+/// A server for the root and `example.`, publishing a URI for alice
+async fn test_server() -> TestServer {
+    let mut example = TestZone::new("example.").unwrap();
+    example.add_payment_instruction("alice", URI).unwrap();
+    TestServer::start(vec![TestZone::new(".").unwrap(), example]).await.unwrap()
+}
+
+fn cached_resolver(server: &TestServer, transport: Arc<TestTransport>, storage: Arc<dyn Storage>) -> Resolver {
+    Resolver::from_config(Bip353Config {
+        transport: TransportConfig::Custom(transport),
+        trust_anchors: server.trust_anchors(),
+        cache: Some(CacheConfig { storage: Some(storage), ..Default::default() }),
+        ..Default::default()
+    }).unwrap()
+}
+
+#[test]
+fn test_file_storage_round_trip() {
+    let path = TempPath::new();
+    let storage = FileStorage::open(&path.0).unwrap();
+    assert_eq!(storage.get("a", "key").unwrap(), None);
+    storage.put("a", "key", b"value").unwrap();
+    storage.put("a", "other", b"\x00\xff").unwrap();
+    storage.put("b", "key", b"b").unwrap();
+    storage.remove("a", "other").unwrap();
+    storage.remove("a", "missing").unwrap();
+
+    let reopened = FileStorage::open(&path.0).unwrap();
+    assert_eq!(reopened.get("a", "key").unwrap(), Some(b"value".to_vec()));
+    assert_eq!(reopened.entries("a").unwrap(), vec![("key".to_string(), b"value".to_vec())]);
+    assert_eq!(reopened.entries("c").unwrap(), vec![]);
+    // The temporary file was renamed into place
+    assert!(!PathBuf::from(format!("{}.tmp", path.0.display())).exists());
+}
+
+#[test]
+fn test_file_storage_versions() {
+    let path = TempPath::new();
+    fs::write(&path.0, r#"{"version": 2, "namespaces": {}}"#).unwrap();
+    assert!(matches!(FileStorage::open(&path.0), Err(Bip353Error::StorageError(msg)) if msg.contains("version 2")));
+
+    fs::write(&path.0, "not json").unwrap();
+    assert!(matches!(FileStorage::open(&path.0), Err(Bip353Error::StorageError(_))));
+
+    fs::write(&path.0, r#"{"version": 1, "namespaces": {"a": {"key": "dmFsdWU="}}}"#).unwrap();
+    assert_eq!(FileStorage::open(&path.0).unwrap().get("a", "key").unwrap(), Some(b"value".to_vec()));
+}
+
+#[test]
+fn test_encrypted_file_storage() {
+    let path = TempPath::new();
+    let storage = FileStorage::open_encrypted(&path.0, Arc::new(XorCipher(0x5a))).unwrap();
+    storage.put("address-book", "alice@example.com", b"pin").unwrap();
+
+    let contents = fs::read(&path.0).unwrap();
+    assert!(!String::from_utf8_lossy(&contents).contains("alice"));
+    assert!(FileStorage::open(&path.0).is_err());
+    let reopened = FileStorage::open_encrypted(&path.0, Arc::new(XorCipher(0x5a))).unwrap();
+    assert_eq!(reopened.get("address-book", "alice@example.com").unwrap(), Some(b"pin".to_vec()));
+}
+
+#[tokio::test]
+async fn test_cache_survives_restart() {
+    let server = test_server().await;
+    let transport = server.transport();
+    let path = TempPath::new();
+
+    let storage: Arc<dyn Storage> = Arc::new(FileStorage::open(&path.0).unwrap());
+    let first = cached_resolver(&server, transport.clone(), storage).resolve("alice", "example").await.unwrap();
+    let queries = transport.queries();
+
+    // A new resolver over the reopened file answers without querying
+    let storage: Arc<dyn Storage> = Arc::new(FileStorage::open(&path.0).unwrap());
+    let resolver = cached_resolver(&server, transport.clone(), storage.clone());
+    assert_eq!(resolver.resolve("alice", "example").await.unwrap(), first);
+    assert_eq!(transport.queries(), queries);
+    assert_eq!(resolver.cache_stats().unwrap().hits, 1);
+
+    resolver.flush("alice", "example");
+    assert_eq!(storage.entries("cache").unwrap(), vec![]);
+}
+
+#[tokio::test]
+async fn test_unreadable_cache_entries_are_dropped() {
+    let storage = Arc::new(MemoryStorage::new());
+    storage.put("cache", "alice@example", br#"{"version": 99}"#).unwrap();
+    let server = test_server().await;
+    let transport = server.transport();
+
+    let resolver = cached_resolver(&server, transport.clone(), storage.clone());
+    assert_eq!(resolver.cache_stats().unwrap().entries, 0);
+    assert_eq!(resolver.resolve("alice", "example").await.unwrap().uri, URI);
+    assert!(transport.queries() > 0);
+    assert_eq!(storage.entries("cache").unwrap().len(), 1);
+}
+
+#[test]
+fn test_address_book_survives_restart() {
+    let path = TempPath::new();
+    let alice = HumanReadableName::new("alice", "example.com").unwrap();
+    let instruction = PaymentInstruction::from_uri(URI).unwrap();
+
+    let book = AddressBook::with_storage(Arc::new(FileStorage::open(&path.0).unwrap())).unwrap();
+    assert!(book.check(&alice, instruction.clone()).unwrap().is_first_use());
+
+    let book = AddressBook::with_storage(Arc::new(FileStorage::open(&path.0).unwrap())).unwrap();
+    assert_eq!(book.names(), vec![alice.clone()]);
+    let check = book.check(&alice, instruction).unwrap();
+    assert!(!check.is_first_use());
+    assert_eq!(check.previous.unwrap().uri, URI);
+
+    book.remove(&alice).unwrap();
+    let book = AddressBook::with_storage(Arc::new(FileStorage::open(&path.0).unwrap())).unwrap();
+    assert_eq!(book.names(), vec![]);
+}
+
+#[test]
+fn test_unreadable_pins_are_an_error() {
+    let storage = Arc::new(MemoryStorage::new());
+    storage.put("address-book", "alice@example.com", br#"{"version": 99}"#).unwrap();
+    assert!(matches!(AddressBook::with_storage(storage), Err(Bip353Error::StorageError(_))));
+}
@@ -0,0 +1,88 @@
+use bip353::testing::{TestServer, TestZone};
+use bip353::{AlgorithmPolicy, Bip353Config, Bip353Error, PaymentType, Resolver, TransportConfig, TrustAnchors};
+use trust_dns_proto::rr::dnssec::Algorithm;
+
+const URI: &str = "bitcoin:bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+
+/// A server for the root and `example.`, publishing a URI for alice
+async fn test_server() -> TestServer {
+    let mut example = TestZone::new("example.").unwrap();
+    example.add_payment_instruction("alice", URI).unwrap();
+    TestServer::start(vec![TestZone::new(".").unwrap(), example]).await.unwrap()
+}
+
+fn resolver(server: &TestServer, trust_anchors: TrustAnchors, algorithm_policy: AlgorithmPolicy) -> Resolver {
+    Resolver::from_config(Bip353Config {
+        transport: TransportConfig::Custom(server.transport()),
+        trust_anchors,
+        algorithm_policy,
+        ..Default::default()
+    }).unwrap()
+}
+
+#[test]
+fn test_parse_root_anchors() {
+    let text = "
+        ; IANA root KSKs
+        . 172800 IN DS 20326 8 2 E06D44B80B8F1D39A95C0B0D7C65D08458E880409BBC683457104237C7F8EC8D
+        . IN DS 38696 8 2 (
+            683d2d0acb8c9b712a1948b27f741219
+            298d0a450d612c483af444a4c0fb2b16 ) ; KSK-2024
+        . 86400 IN NS a.root-servers.net.
+    ";
+    assert_eq!(TrustAnchors::parse(text).unwrap(), TrustAnchors::default());
+}
+
+#[test]
+fn test_parse_rejects_malformed_anchor() {
+    let result = TrustAnchors::parse(". IN DS 20326 8 2 not-hex");
+    assert!(matches!(result, Err(Bip353Error::DnssecError(_))));
+}
+
+#[tokio::test]
+async fn test_dnskey_anchor() {
+    let server = test_server().await;
+    let anchors = TrustAnchors::parse(server.dnskey_anchors()).unwrap();
+
+    let instruction = resolver(&server, anchors, AlgorithmPolicy::default()).resolve("alice", "example").await.unwrap();
+    assert_eq!(instruction.payment_type, PaymentType::OnChain);
+}
+
+#[tokio::test]
+async fn test_allowed_algorithm() {
+    let server = test_server().await;
+    let policy = AlgorithmPolicy { allowed: vec![Algorithm::ECDSAP256SHA256, Algorithm::ED25519], ..Default::default() };
+
+    let instruction = resolver(&server, server.trust_anchors(), policy).resolve("alice", "example").await.unwrap();
+    assert_eq!(instruction.dnssec.algorithm, Some(Algorithm::ED25519));
+}
+
+#[tokio::test]
+async fn test_algorithm_not_allowed() {
+    let server = test_server().await;
+    let policy = AlgorithmPolicy { allowed: vec![Algorithm::ECDSAP256SHA256], ..Default::default() };
+
+    let result = resolver(&server, server.trust_anchors(), policy).resolve("alice", "example").await;
+    assert!(matches!(result, Err(Bip353Error::DnssecError(msg)) if msg.contains("forbidden")));
+}
+
+#[tokio::test]
+async fn test_forbidden_algorithm_wins() {
+    let server = test_server().await;
+    let policy = AlgorithmPolicy { allowed: vec![Algorithm::ED25519], forbidden: vec![Algorithm::ED25519] };
+
+    let result = resolver(&server, server.trust_anchors(), policy).resolve("alice", "example").await;
+    assert!(matches!(result, Err(Bip353Error::DnssecError(_))));
+}
+
+#[tokio::test]
+async fn test_system_transport_applies_policy() {
+    let mut zone = TestZone::new("example.com.").unwrap();
+    zone.add_payment_instruction("alice", URI).unwrap();
+    let server = TestServer::start(vec![zone]).await.unwrap();
+
+    // The system backend validates locally, so the policy binds it too
+    let policy = AlgorithmPolicy { forbidden: vec![Algorithm::ED25519], ..Default::default() };
+    let result = server.system_resolver_with_policy(policy).resolve("alice", "example.com").await;
+    assert!(matches!(result, Err(Bip353Error::DnssecError(msg)) if msg.contains("forbidden")));
+}
@@ -163,4 +163,36 @@ fn test_case_sensitivity() {
     assert!(matches!(instruction.payment_type, PaymentType::Unknown));
     assert_eq!(instruction.parameters.get("LiGhTnInG"), Some(&"lnbc1".to_string()));
     assert_eq!(instruction.parameters.get("AmOuNt"), Some(&"0.01".to_string()));
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_payment_method_accessors() {
+    let uri = "bitcoin:bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4?amount=0.01&label=Test&message=Payment&lightning=lnbc1&lno=lno1";
+    let instruction = PaymentInstruction::from_uri(uri).unwrap();
+    assert_eq!(instruction.onchain_address(), Some("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"));
+    assert_eq!(instruction.lightning_invoice(), Some("lnbc1"));
+    assert_eq!(instruction.lightning_offer(), Some("lno1"));
+    assert_eq!(instruction.amount(), Some("0.01"));
+    assert_eq!(instruction.label(), Some("Test"));
+    assert_eq!(instruction.message(), Some("Payment"));
+    
+    // Parameters are iterated in key order
+    let keys: Vec<&str> = instruction.sorted_parameters().iter().map(|(k, _)| *k).collect();
+    assert_eq!(keys, vec!["amount", "label", "lightning", "lno", "message"]);
+    
+    // Missing fields
+    let instruction = PaymentInstruction::from_uri("bitcoin:?lno=lno1").unwrap();
+    assert_eq!(instruction.onchain_address(), None);
+    assert_eq!(instruction.lightning_invoice(), None);
+    assert_eq!(instruction.amount(), None);
+    assert_eq!(instruction.label(), None);
+    assert_eq!(instruction.message(), None);
+
+    // Hand-built or deserialized instructions need not hold a valid URI
+    let mut instruction = PaymentInstruction::from_uri("BITCOIN:bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4").unwrap();
+    assert_eq!(instruction.onchain_address(), Some("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"));
+    for uri in ["", "btc", "bitcoi₿:x", "lightning:lnbc1"] {
+        instruction.uri = uri.to_string();
+        assert_eq!(instruction.onchain_address(), None);
+    }
+}
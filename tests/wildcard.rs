@@ -0,0 +1,138 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bip353::signer::{DenialConfig, SigningKey};
+use bip353::testing::{TestServer, TestTransport, TestZone};
+use bip353::{Bip353Config, Bip353Error, DnsTransport, DnssecStatus, Resolver, TransportConfig};
+use ring::signature::Ed25519KeyPair;
+use trust_dns_proto::op::Message;
+use trust_dns_proto::rr::dnssec::rdata::{DNSSECRData, NSEC3};
+use trust_dns_proto::rr::dnssec::{tbs, Algorithm};
+use trust_dns_proto::rr::{DNSClass, Name, RData, Record, RecordType};
+
+const URI: &str = "bitcoin:bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+const WILDCARD: &str = "*.user._bitcoin-payment.example.";
+
+fn resolver(server: &TestServer, transport: Arc<dyn DnsTransport>) -> Resolver {
+    Resolver::from_config(Bip353Config {
+        transport: TransportConfig::Custom(transport),
+        trust_anchors: server.trust_anchors(),
+        ..Default::default()
+    }).unwrap()
+}
+
+/// The root and `example.` denying with `denial`, publishing a wildcard URI
+fn wildcard_zones(denial: DenialConfig) -> Vec<TestZone> {
+    let mut example = TestZone::new("example.").unwrap();
+    example.set_denial(denial);
+    example.add_txt(WILDCARD, &[URI]).unwrap();
+    vec![TestZone::new(".").unwrap(), example]
+}
+
+fn nsec3() -> DenialConfig {
+    DenialConfig::Nsec3 { iterations: 0, salt: Vec::new(), opt_out: false }
+}
+
+/// Passes answers through with every NSEC3 claiming `iterations`, signed
+/// again with the zone's ZSK so that only the iteration count is wrong
+struct Nsec3Iterations {
+    inner: Arc<TestTransport>,
+    zsk: Ed25519KeyPair,
+    iterations: u16,
+}
+
+#[async_trait]
+impl DnsTransport for Nsec3Iterations {
+    async fn exchange(&self, query: &[u8]) -> Result<Vec<u8>, Bip353Error> {
+        let mut response = Message::from_vec(&self.inner.exchange(query).await?).unwrap();
+        let mut authority = response.take_name_servers();
+        for record in &mut authority {
+            if let Some(RData::DNSSEC(DNSSECRData::NSEC3(nsec3))) = record.data().cloned() {
+                let nsec3 = NSEC3::new(
+                    nsec3.hash_algorithm(),
+                    nsec3.opt_out(),
+                    self.iterations,
+                    nsec3.salt().to_vec(),
+                    nsec3.next_hashed_owner_name().to_vec(),
+                    nsec3.type_bit_maps().to_vec(),
+                );
+                record.set_data(Some(RData::DNSSEC(DNSSECRData::NSEC3(nsec3))));
+            }
+        }
+        let nsec3s: Vec<Record> = authority.iter().filter(|r| r.record_type() == RecordType::NSEC3).cloned().collect();
+        for record in &mut authority {
+            let Some(RData::DNSSEC(DNSSECRData::SIG(sig))) = record.data().cloned() else { continue };
+            if sig.type_covered() != RecordType::NSEC3 {
+                continue;
+            }
+            let rrset: Vec<Record> = nsec3s.iter().filter(|r| r.name() == record.name()).cloned().collect();
+            let tbs = tbs::rrset_tbs_with_sig(record.name(), DNSClass::IN, &sig, &rrset).unwrap();
+            let sig = sig.set_sig(self.zsk.sign(tbs.as_ref()).as_ref().to_vec());
+            record.set_data(Some(RData::DNSSEC(DNSSECRData::SIG(sig))));
+        }
+        response.add_name_servers(authority);
+        Ok(response.to_vec().unwrap())
+    }
+}
+
+#[tokio::test]
+async fn test_wildcard_with_nsec_proof() {
+    let server = TestServer::start(wildcard_zones(DenialConfig::Nsec)).await.unwrap();
+    let instruction = resolver(&server, server.transport()).resolve("alice", "example").await.unwrap();
+    assert_eq!(instruction.uri, URI);
+    assert!(instruction.is_wildcard());
+    assert_eq!(instruction.dnssec.status, DnssecStatus::Secure);
+    assert_eq!(instruction.dnssec.wildcard, Some(Name::from_ascii(WILDCARD).unwrap()));
+}
+
+#[tokio::test]
+async fn test_wildcard_with_nsec3_proof() {
+    let server = TestServer::start(wildcard_zones(nsec3())).await.unwrap();
+    let instruction = resolver(&server, server.transport()).resolve("bob", "example").await.unwrap();
+    assert!(instruction.is_wildcard());
+    assert_eq!(instruction.dnssec.status, DnssecStatus::Secure);
+}
+
+#[tokio::test]
+async fn test_wildcard_without_proof_is_refused() {
+    let mut zones = wildcard_zones(DenialConfig::Nsec);
+    zones[1].set_without_denial_proofs();
+    let server = TestServer::start(zones).await.unwrap();
+    let result = resolver(&server, server.transport()).resolve("alice", "example").await;
+    assert!(matches!(result, Err(Bip353Error::DnssecError(msg)) if msg.contains("wildcard")));
+}
+
+#[tokio::test]
+async fn test_nsec3_with_excessive_iterations_is_refused() {
+    // The signer refuses to make such a chain, so the proof is rewritten
+    let ksk = SigningKey::generate(Algorithm::ED25519, true).unwrap();
+    let zsk = SigningKey::generate(Algorithm::ED25519, false).unwrap();
+    let mut zones = wildcard_zones(nsec3());
+    zones[1].set_keys(&ksk, &zsk);
+    let server = TestServer::start(zones).await.unwrap();
+    let transport = Nsec3Iterations {
+        inner: server.transport(),
+        zsk: Ed25519KeyPair::from_pkcs8(zsk.to_pkcs8()).unwrap(),
+        iterations: 500,
+    };
+
+    let result = resolver(&server, Arc::new(transport)).resolve("alice", "example").await;
+    assert!(matches!(result, Err(Bip353Error::DnssecError(msg)) if msg.contains("wildcard")));
+}
+
+#[tokio::test]
+async fn test_exact_record_is_not_wildcard() {
+    let mut zones = wildcard_zones(DenialConfig::Nsec);
+    zones[1].add_payment_instruction("alice", "bitcoin:?lno=lno1alice").unwrap();
+    let server = TestServer::start(zones).await.unwrap();
+    let resolver = resolver(&server, server.transport());
+
+    let alice = resolver.resolve("alice", "example").await.unwrap();
+    assert_eq!(alice.uri, "bitcoin:?lno=lno1alice");
+    assert!(!alice.is_wildcard());
+
+    // Other users still get the wildcard
+    let bob = resolver.resolve("bob", "example").await.unwrap();
+    assert_eq!(bob.uri, URI);
+    assert!(bob.is_wildcard());
+}